@@ -0,0 +1,106 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use respire::math::int_mod_cyclo::IntModCyclo;
+use respire::math::int_mod_poly::IntModPoly;
+use respire::math::matrix::Matrix;
+use respire::pir::respire::Respire;
+use respire::pir::respire_harness::{RespireTest, RESPIRE_TEST_PARAMS};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// Global allocator that counts calls to `alloc`, so each benchmark can report how many heap
+/// allocations one call of the measured function performs.
+///
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn count_allocations<F: FnMut()>(name: &str, mut f: F) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    eprintln!("{name}: {} allocations per call", after - before);
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    const T: usize = RESPIRE_TEST_PARAMS.T_PROJ_SHORT;
+    const Z: u64 = RESPIRE_TEST_PARAMS.Z_PROJ_SHORT;
+    const DROP: usize = RESPIRE_TEST_PARAMS.DROP_PROJ_SHORT;
+
+    let s = RespireTest::encode_setup();
+    let auto_key = RespireTest::auto_setup::<T, Z, DROP>(3, &s);
+    let x = <RespireTest as Respire>::RingP::from(IntModPoly::x());
+    let ct = RespireTest::encode_rlwe(&s, &x.scale_up_into());
+    let ct_other = RespireTest::encode_rlwe(&s, &x.scale_up_into());
+    let gsw = RespireTest::encode_gsw(&s, &IntModCyclo::from(1_u64));
+
+    let mut out = Matrix::zero();
+    let mut ct_mut = ct.clone();
+    let mut diff = Matrix::zero();
+    let mut g_inv_auto = Matrix::zero();
+    let mut g_inv_gsw = Matrix::zero();
+
+    count_allocations("alloc::auto_hom", || {
+        black_box(RespireTest::auto_hom::<T, Z, DROP>(&auto_key, &ct));
+    });
+    count_allocations("alloc::auto_hom_into", || {
+        RespireTest::auto_hom_into::<T, Z, DROP>(&auto_key, &ct, &mut out, &mut g_inv_auto);
+    });
+    count_allocations("alloc::select_hom", || {
+        black_box(RespireTest::select_hom(&ct, &ct_other, &gsw));
+    });
+    count_allocations("alloc::select_hom_assign", || {
+        RespireTest::select_hom_assign(&mut ct_mut, &ct_other, &gsw, &mut diff, &mut g_inv_gsw);
+    });
+    count_allocations("alloc::add", || {
+        black_box(&ct + &ct_other);
+    });
+    count_allocations("alloc::add_into", || {
+        Matrix::add_into(&mut out, &ct, &ct_other);
+    });
+
+    c.bench_function("alloc::auto_hom", |b| {
+        b.iter(|| RespireTest::auto_hom::<T, Z, DROP>(black_box(&auto_key), black_box(&ct)));
+    });
+    c.bench_function("alloc::auto_hom_into", |b| {
+        b.iter(|| {
+            RespireTest::auto_hom_into::<T, Z, DROP>(
+                black_box(&auto_key),
+                black_box(&ct),
+                &mut out,
+                &mut g_inv_auto,
+            )
+        });
+    });
+    c.bench_function("alloc::select_hom", |b| {
+        b.iter(|| RespireTest::select_hom(black_box(&ct), black_box(&ct_other), black_box(&gsw)));
+    });
+    c.bench_function("alloc::select_hom_assign", |b| {
+        b.iter(|| {
+            RespireTest::select_hom_assign(
+                &mut ct_mut,
+                black_box(&ct_other),
+                black_box(&gsw),
+                &mut diff,
+                &mut g_inv_gsw,
+            )
+        });
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
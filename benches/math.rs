@@ -1,13 +1,13 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use fhe_psi::math::gadget::{base_from_len, gadget_inverse};
-use fhe_psi::math::int_mod_cyclo::IntModCyclo;
-use fhe_psi::math::int_mod_cyclo_crt::IntModCycloCRT;
-use fhe_psi::math::int_mod_cyclo_crt_eval::IntModCycloCRTEval;
-use fhe_psi::math::matrix::Matrix;
-use fhe_psi::math::ntt::{ntt_neg_backward, ntt_neg_forward};
-use fhe_psi::math::rand_sampled::RandUniformSampled;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
+use respire::math::gadget::{base_from_len, gadget_inverse, gadget_inverse_into};
+use respire::math::int_mod_cyclo::IntModCyclo;
+use respire::math::int_mod_cyclo_crt::IntModCycloCRT;
+use respire::math::int_mod_cyclo_crt_eval::IntModCycloCRTEval;
+use respire::math::matrix::{Matrix, PreparedMatrixMul};
+use respire::math::ntt::{ntt_neg_backward, ntt_neg_forward};
+use respire::math::rand_sampled::RandUniformSampled;
 
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("math::ntt_neg_forward", |b| {
@@ -59,14 +59,43 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| elem.auto(101));
     });
 
+    c.bench_function("math::IntModCycloCRT to IntModCycloCRTEval", |b| {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let elem = RingCRT::rand_uniform(&mut rng);
+        b.iter(|| RingCRTEval::from(black_box(&elem)));
+    });
+
+    c.bench_function("math::IntModCycloCRTEval to IntModCycloCRT", |b| {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let elem = RingCRTEval::rand_uniform(&mut rng);
+        b.iter_batched(
+            || elem.clone(),
+            |elem| RingCRT::from(black_box(elem)),
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("math::IntModCyclo to IntModCycloCRTEval", |b| {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let elem = IntModCyclo::<D, Q>::rand_uniform(&mut rng);
+        b.iter(|| RingCRTEval::from(black_box(&elem)));
+    });
+
+    c.bench_function("math::IntModCycloCRTEval mul", |b| {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let e1 = RingCRTEval::rand_uniform(&mut rng);
+        let e2 = RingCRTEval::rand_uniform(&mut rng);
+        b.iter(|| black_box(&e1) * black_box(&e2));
+    });
+
     c.bench_function("math::IntModCycloCRT Matrix zero", |b| {
         type M = Matrix<2, 2, RingCRT>;
-        b.iter(|| M::zero());
+        b.iter(M::zero);
     });
 
     c.bench_function("math::IntModCycloCRTEval Matrix zero", |b| {
         type M = Matrix<2, 2, RingCRTEval>;
-        b.iter(|| M::zero());
+        b.iter(M::zero);
     });
 
     c.bench_function("math::IntModCycloCRT Matrix 2x2 add", |b| {
@@ -125,6 +154,46 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         b.iter(|| gadget_inverse::<_, 2, { 2 * T }, 2, Z, T>(black_box(&m1)));
     });
+
+    // The two halves of a GSW selection: decomposing the difference, and the GSW product
+    c.bench_function("math::gadget inverse IntModCycloCRTEval 2x1, T = 8", |b| {
+        type M = Matrix<2, 1, RingCRTEval>;
+        const T: usize = 8;
+        const Z: u64 = base_from_len(T, Q);
+        let mut rng = ChaCha20Rng::from_entropy();
+        let m1 = M::rand_uniform(&mut rng);
+        let mut out = Matrix::zero();
+
+        b.iter(|| gadget_inverse_into::<_, 2, { 2 * T }, 1, Z, T>(black_box(&m1), &mut out));
+    });
+
+    c.bench_function(
+        "math::IntModCycloCRTEval Matrix 2x16 x 16x1 add_eq_mul",
+        |b| {
+            let mut rng = ChaCha20Rng::from_entropy();
+            let gsw = Matrix::<2, 16, RingCRTEval>::rand_uniform(&mut rng);
+            let g_inv = Matrix::<16, 1, RingCRTEval>::rand_uniform(&mut rng);
+            let mut out = Matrix::zero();
+
+            b.iter(|| out.add_eq_mul(black_box(&gsw), black_box(&g_inv)));
+        },
+    );
+
+    c.bench_function(
+        "math::IntModCycloCRTEval prepared 2x16 x 16x1 add_eq_mul, 8 vectors",
+        |b| {
+            let mut rng = ChaCha20Rng::from_entropy();
+            let gsw = RingCRTEval::prepare(&Matrix::<2, 16, RingCRTEval>::rand_uniform(&mut rng));
+            let g_invs = (0..8)
+                .map(|_| Matrix::<16, 1, RingCRTEval>::rand_uniform(&mut rng))
+                .collect::<Vec<_>>();
+            let mut outs = vec![Matrix::zero(); 8];
+
+            b.iter(|| {
+                RingCRTEval::add_eq_mul_prepared(black_box(&gsw), &mut outs, black_box(&g_invs))
+            });
+        },
+    );
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,204 +1,100 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use fhe_psi::math::int_mod::IntMod;
-use fhe_psi::math::int_mod_cyclo::IntModCyclo;
-use fhe_psi::math::matrix::Matrix;
-use fhe_psi::math::rand_sampled::RandUniformSampled;
-use fhe_psi::pir::respire::{RespireImpl, RespireParams, RespireParamsExpanded, PIR};
-use fhe_psi::respire;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use respire::pir::pir::PIR;
+use respire::pir::respire::{Respire, RespireParamsExpanded};
+use respire::pir::respire_harness::{filled_record, FactoryParams};
+use respire::respire;
+use std::cmp::min;
+
+///
+/// Benchmarks each stage of answering a query, plus encoding and extraction, for one parameter
+/// set. Every stage runs on the real output of the previous one.
+///
+macro_rules! bench_stages {
+    ($c: expr, $name: literal, $params: expr) => {{
+        const PARAMS: RespireParamsExpanded = $params;
+        type ThePIR = respire!(PARAMS);
+
+        let mut group = $c.benchmark_group(concat!("pir::", $name));
+        group.sample_size(10);
+
+        let records_generator = filled_record::<ThePIR>;
+        group.bench_function("encode_db", |b| {
+            b.iter(|| ThePIR::encode_db(black_box(records_generator), None))
+        });
 
-fn criterion_benchmark(c: &mut Criterion) {
-    const SPIRAL_TEST_PARAMS: RespireParamsExpanded = RespireParams {
-        Q_A: 268369921,
-        Q_B: 249561089,
-        D: 2048,
-        T_GSW: 8,
-        T_CONV: 4,
-        T_COEFF_REGEV: 8,
-        T_COEFF_GSW: 56,
-        NOISE_WIDTH_MILLIONTHS: 6_400_000,
-        P: 1 << 8,
-        ETA1: 9,
-        ETA2: 6,
-        Z_FOLD: 2,
-        Q_SWITCH1: 1 << 10, // 4P
-        Q_SWITCH2: 2056193, // must be prime
-        D_SWITCH: 1024,
-        T_SWITCH: 21,
-    }
-    .expand();
-    type SPIRALTest = respire!(SPIRAL_TEST_PARAMS);
-
-    c.bench_function("pir::automorphism with T_COEFF_REGEV", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let scalar_key = SPIRALTest::encode_setup();
-        let auto_key_regev = SPIRALTest::auto_setup::<
-            { SPIRAL_TEST_PARAMS.T_COEFF_REGEV },
-            { SPIRAL_TEST_PARAMS.Z_COEFF_REGEV },
-        >(3, &scalar_key);
-        let ct = Matrix::rand_uniform(&mut rng);
-        b.iter(|| {
-            SPIRALTest::auto_hom::<
-                { SPIRAL_TEST_PARAMS.T_COEFF_REGEV },
-                { SPIRAL_TEST_PARAMS.Z_COEFF_REGEV },
-            >(black_box(&auto_key_regev), black_box(&ct))
-        })
-    });
-
-    c.bench_function("pir::automorphism with T_COEFF_GSW", |b| {
         let mut rng = ChaCha20Rng::from_entropy();
-        let scalar_key = SPIRALTest::encode_setup();
-        let auto_key_regev = SPIRALTest::auto_setup::<
-            { SPIRAL_TEST_PARAMS.T_COEFF_GSW },
-            { SPIRAL_TEST_PARAMS.Z_COEFF_GSW },
-        >(3, &scalar_key);
-        let ct = Matrix::rand_uniform(&mut rng);
-        b.iter(|| {
-            SPIRALTest::auto_hom::<
-                { SPIRAL_TEST_PARAMS.T_COEFF_GSW },
-                { SPIRAL_TEST_PARAMS.Z_COEFF_GSW },
-            >(black_box(&auto_key_regev), black_box(&ct))
-        })
-    });
-
-    let mut group = c.benchmark_group("pir::do_expand_iter with T_COEFF_REGEV");
-    for i in 0..4 {
-        group.bench_with_input(BenchmarkId::from_parameter(i), &i, |b, &i| {
-            let mut rng = ChaCha20Rng::from_entropy();
-            let scalar_key = SPIRALTest::encode_setup();
-            let auto_key_regev = SPIRALTest::auto_setup::<
-                { SPIRAL_TEST_PARAMS.T_COEFF_REGEV },
-                { SPIRAL_TEST_PARAMS.Z_COEFF_REGEV },
-            >(SPIRAL_TEST_PARAMS.D / (1 << i) + 1, &scalar_key);
-            let mut cts = Vec::with_capacity(1 << i);
-            for _ in 0..(1 << i) {
-                cts.push(Matrix::rand_uniform(&mut rng));
-            }
-            b.iter(|| {
-                SPIRALTest::do_coeff_expand_iter::<
-                    { SPIRAL_TEST_PARAMS.T_COEFF_REGEV },
-                    { SPIRAL_TEST_PARAMS.Z_COEFF_REGEV },
-                >(
-                    black_box(i),
-                    black_box(cts.as_slice()),
-                    black_box(&auto_key_regev),
-                )
-            });
+        let (db, _) = ThePIR::encode_db(records_generator, None);
+        let (qk, pp) = ThePIR::setup(None);
+        let answers_per_chunk = min(ThePIR::BATCH_SIZE, ThePIR::RESPONSE_CHUNK_SIZE);
+        let queries = (0..answers_per_chunk)
+            .map(|_| ThePIR::query_one(&qk, rng.gen_range(0..ThePIR::DB_SIZE), None))
+            .collect::<Vec<_>>();
+        let q = &queries[0];
+
+        group.bench_function("answer_query_unpack", |b| {
+            b.iter(|| ThePIR::answer_query_unpack(black_box(&pp), black_box(q), None, None))
         });
-    }
-    group.finish();
+        let (rlwes, gsws_fold, gsws_rot) = ThePIR::answer_query_unpack(&pp, q, None, None);
 
-    let mut group = c.benchmark_group("pir::do_expand_iter with T_COEFF_GSW");
-    for i in 0..4 {
-        group.bench_with_input(BenchmarkId::from_parameter(i), &i, |b, &i| {
-            let mut rng = ChaCha20Rng::from_entropy();
-            let scalar_key = SPIRALTest::encode_setup();
-            let auto_key_regev = SPIRALTest::auto_setup::<
-                { SPIRAL_TEST_PARAMS.T_COEFF_GSW },
-                { SPIRAL_TEST_PARAMS.Z_COEFF_GSW },
-            >(SPIRAL_TEST_PARAMS.D / (1 << i) + 1, &scalar_key);
-            let mut cts = Vec::with_capacity(1 << i);
-            for _ in 0..(1 << i) {
-                cts.push(Matrix::rand_uniform(&mut rng));
-            }
-            b.iter(|| {
-                SPIRALTest::do_coeff_expand_iter::<
-                    { SPIRAL_TEST_PARAMS.T_COEFF_GSW },
-                    { SPIRAL_TEST_PARAMS.Z_COEFF_GSW },
-                >(
-                    black_box(i),
-                    black_box(cts.as_slice()),
-                    black_box(&auto_key_regev),
-                )
-            });
+        group.bench_function("answer_first_dim", |b| {
+            b.iter(|| ThePIR::answer_first_dim(black_box(&db), black_box(&rlwes)))
         });
-    }
-    group.finish();
+        let c_firstdim = ThePIR::answer_first_dim(&db, &rlwes).unwrap();
 
-    c.bench_function("pir::scalar_regev_mul_x_pow", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let ct = Matrix::rand_uniform(&mut rng);
-        b.iter(|| SPIRALTest::regev_mul_x_pow(black_box(&ct), black_box(101)))
-    });
-
-    c.bench_function("pir::regev to gsw", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let s = SPIRALTest::encode_setup();
-        let regev_to_gsw_key = SPIRALTest::regev_to_gsw_setup(&s);
-
-        let msg: IntModCyclo<{ SPIRAL_TEST_PARAMS.D }, { SPIRAL_TEST_PARAMS.Q }> =
-            IntModCyclo::rand_uniform(&mut rng);
-        let mut msg_curr = msg.include_into();
-        let mut encrypt_vec = Vec::with_capacity(SPIRAL_TEST_PARAMS.T_GSW);
-        for _ in 0..SPIRAL_TEST_PARAMS.T_GSW {
-            encrypt_vec.push(SPIRALTest::encode_regev(&s, &msg_curr));
-            msg_curr *= IntMod::from(SPIRAL_TEST_PARAMS.Z_GSW);
-        }
-
-        b.iter(|| {
-            SPIRALTest::regev_to_gsw(
-                black_box(&regev_to_gsw_key),
-                black_box(encrypt_vec.as_slice()),
+        group.bench_function("answer_fold", |b| {
+            b.iter_batched(
+                || c_firstdim.clone(),
+                |c_firstdim| ThePIR::answer_fold(c_firstdim, black_box(&gsws_fold)),
+                BatchSize::LargeInput,
             )
         });
-    });
+        let c_fold = ThePIR::answer_fold(c_firstdim, &gsws_fold);
 
-    c.bench_function("pir::regev_sub_hom", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let m1 = Matrix::rand_uniform(&mut rng);
-        let m2 = Matrix::rand_uniform(&mut rng);
-        b.iter(|| SPIRALTest::regev_sub_hom(black_box(&m1), black_box(&m2)));
-    });
+        group.bench_function("answer_rotate", |b| {
+            b.iter(|| ThePIR::answer_rotate(black_box(&c_fold), black_box(&gsws_rot)))
+        });
+        let c_rot = ThePIR::answer_rotate(&c_fold, &gsws_rot);
 
-    c.bench_function("pir::hybrid_mul_hom", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let m1 = Matrix::rand_uniform(&mut rng);
-        let m2 = Matrix::rand_uniform(&mut rng);
-        b.iter(|| SPIRALTest::hybrid_mul_hom(black_box(&m1), black_box(&m2)));
-    });
+        group.bench_function("answer_project", |b| {
+            b.iter(|| ThePIR::answer_project(black_box(&pp), black_box(&c_rot)))
+        });
 
-    c.bench_function("pir::answer_query_expand", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let (qk, pp) = SPIRALTest::setup();
-        let idx = rng.gen_range(0..<SPIRALTest as PIR>::PACKED_DB_SIZE);
-        let q = SPIRALTest::query(&qk, idx);
-        b.iter(|| SPIRALTest::answer_query_expand(black_box(&pp), black_box(&q)))
-    });
+        let chunk = queries
+            .iter()
+            .map(|q| ThePIR::answer_one(&pp, &db, q, None, None))
+            .collect::<Vec<_>>();
+        group.bench_function("answer_compress_chunk", |b| {
+            b.iter(|| ThePIR::answer_compress_chunk(black_box(&pp), black_box(&chunk), None, None))
+        });
+        let compressed = ThePIR::answer_compress_chunk(&pp, &chunk, None, None);
 
-    c.bench_function("pir::answer_first_dim", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let records: Vec<_> = (0..)
-            .map(|_| <SPIRALTest as PIR>::RingP::rand_uniform(&mut rng).project_dim())
-            .take(SPIRALTest::PACKED_DB_SIZE)
-            .collect();
-        let db = SPIRALTest::encode_db(records.iter());
-        let regevs: Vec<_> = (0..)
-            .map(|_| Matrix::rand_uniform(&mut rng))
-            .take(1 << SPIRALTest::ETA1)
-            .collect();
-        b.iter(|| SPIRALTest::answer_first_dim(black_box(&db), black_box(regevs.as_slice())));
-    });
+        group.bench_function("extract_one", |b| {
+            b.iter(|| ThePIR::extract_one(black_box(&qk), black_box(&compressed), None))
+        });
 
-    c.bench_function("pir::answer_fold", |b| {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let first_dim_folded: Vec<_> = (0..)
-            .map(|_| Matrix::rand_uniform(&mut rng))
-            .take(1 << SPIRALTest::ETA1)
-            .collect();
-        let gsws: Vec<_> = (0..)
-            .map(|_| Matrix::rand_uniform(&mut rng))
-            .take(SPIRALTest::ETA2 * (SPIRAL_TEST_PARAMS.Z_FOLD - 1))
-            .collect();
+        group.finish();
+    }};
+}
 
-        // Note: this includes the time it takes to clone first_dim_folded
-        b.iter(|| {
-            SPIRALTest::answer_fold(
-                black_box(first_dim_folded.clone()),
-                black_box(gsws.as_slice()),
-            )
-        });
-    });
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_stages!(
+        c,
+        "64m256",
+        FactoryParams::single_record_256(8, 8).expand().expand()
+    );
+    // Base Respire of the 256m256_8 and 1g256_128 Cuckoo configurations
+    bench_stages!(
+        c,
+        "256m256_8",
+        FactoryParams::batch_256(13, 4, 8, 8).expand().expand()
+    );
+    bench_stages!(
+        c,
+        "1g256_128",
+        FactoryParams::batch_256(194, 8, 7, 7).expand().expand()
+    );
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,426 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use respire::pir::cuckoo_respire::CuckooRespireImpl;
+use respire::pir::param_search::{search, SearchSpace, SearchTarget};
+use respire::pir::pir::PIR;
+use respire::pir::respire::RespireParamsExpanded;
+use respire::pir::respire_harness::{
+    harness_record, harness_run, FactoryParams, HarnessArgs, RunReport,
+};
+use respire::pir::security::DEFAULT_MIN_SECURITY_BITS;
+use respire::pir::shard::{serve_shard, shard_buckets, ShardRespire};
+use respire::respire;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+const KIB: usize = 1 << 10;
+const MIB: usize = 1 << 20;
+const GIB: usize = 1 << 30;
+
+/// A compiled-in configuration, identified by its database size, record size and batch size.
+struct Config {
+    db_bytes: usize,
+    record_bytes: usize,
+    batch_size: usize,
+    run: fn(&HarnessArgs) -> Result<RunReport<RespireParamsExpanded>, String>,
+    shard: fn(&ShardArgs) -> io::Result<()>,
+}
+
+impl Config {
+    /// `<DB size with suffix><record size>[_<batch size>]`, e.g. `256m256_4`.
+    fn name(&self) -> String {
+        let batch = if self.batch_size > 1 {
+            format!("_{}", self.batch_size)
+        } else {
+            String::new()
+        };
+        format!(
+            "{}{}{}",
+            format_size(self.db_bytes),
+            self.record_bytes,
+            batch
+        )
+    }
+}
+
+/// Plain Respire retrieving a single 256 byte record.
+macro_rules! single {
+    ($db_bytes: expr, $nu1: expr, $nu2: expr) => {{
+        const PARAMS: RespireParamsExpanded = FactoryParams::single_record_256($nu1, $nu2)
+            .expand()
+            .expand();
+        type ThePIR = respire!(PARAMS);
+        Config {
+            db_bytes: $db_bytes,
+            record_bytes: 256,
+            batch_size: 1,
+            run: harness_run::<ThePIR>,
+            shard: serve_columns::<ThePIR>,
+        }
+    }};
+}
+
+/// Cuckoo hashing of `$batch` 256 byte records into the `$num_bucket` records of a batched Respire.
+macro_rules! cuckoo {
+    ($db_bytes: expr, $batch: expr, $num_bucket: expr, $n_vec: expr, $nu1: expr, $nu2: expr) => {{
+        const BASE_PARAMS: RespireParamsExpanded =
+            FactoryParams::batch_256($num_bucket, $n_vec, $nu1, $nu2)
+                .expand()
+                .expand();
+        type BasePIR = respire!(BASE_PARAMS);
+        type CuckooPIR = CuckooRespireImpl<$batch, $num_bucket, { $db_bytes / 256 }, BasePIR>;
+        Config {
+            db_bytes: $db_bytes,
+            record_bytes: 256,
+            batch_size: $batch,
+            run: harness_run::<CuckooPIR>,
+            shard: serve_buckets::<CuckooPIR, BasePIR>,
+        }
+    }};
+}
+
+const CONFIGS: &[Config] = &[
+    single!(64 * KIB, 3, 3),
+    single!(64 * MIB, 8, 8),
+    single!(256 * MIB, 9, 9),
+    single!(512 * MIB, 9, 10),
+    single!(GIB, 10, 10),
+    single!(2 * GIB, 10, 11),
+    single!(4 * GIB, 11, 11),
+    single!(8 * GIB, 11, 12),
+    cuckoo!(256 * MIB, 4, 7, 2, 9, 8),
+    cuckoo!(256 * MIB, 8, 13, 4, 8, 8),
+    cuckoo!(256 * MIB, 16, 25, 7, 8, 7),
+    cuckoo!(256 * MIB, 32, 49, 8, 7, 7),
+    cuckoo!(256 * MIB, 64, 98, 8, 7, 6),
+    cuckoo!(256 * MIB, 128, 197, 8, 6, 6),
+    cuckoo!(256 * MIB, 256, 398, 8, 6, 5),
+    cuckoo!(GIB, 4, 7, 2, 10, 9),
+    cuckoo!(GIB, 8, 13, 4, 9, 9),
+    cuckoo!(GIB, 16, 25, 7, 9, 8),
+    cuckoo!(GIB, 32, 49, 8, 8, 8),
+    cuckoo!(GIB, 64, 97, 8, 8, 7),
+    cuckoo!(GIB, 128, 194, 8, 7, 7),
+    cuckoo!(GIB, 256, 391, 8, 7, 6),
+];
+
+fn format_size(bytes: usize) -> String {
+    if bytes.is_multiple_of(GIB) {
+        format!("{}g", bytes / GIB)
+    } else if bytes.is_multiple_of(MIB) {
+        format!("{}m", bytes / MIB)
+    } else if bytes.is_multiple_of(KIB) {
+        format!("{}k", bytes / KIB)
+    } else {
+        bytes.to_string()
+    }
+}
+
+/// Parses a byte count with an optional `k`, `m` or `g` (binary) suffix.
+fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim().to_ascii_lowercase();
+    let (digits, unit) = match s.chars().last() {
+        Some('k') => (&s[..s.len() - 1], KIB),
+        Some('m') => (&s[..s.len() - 1], MIB),
+        Some('g') => (&s[..s.len() - 1], GIB),
+        _ => (s.as_str(), 1),
+    };
+    digits
+        .parse::<usize>()
+        .map(|n| n * unit)
+        .map_err(|e| format!("invalid size '{}': {}", s, e))
+}
+
+/// Run Respire benchmarks and tools.
+#[derive(Parser, Debug)]
+#[command(name = "respire-bench", args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// List the supported configurations
+    #[arg(long)]
+    list: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Benchmark the configuration matching the given database, record and batch sizes
+    Run(RunArgs),
+    /// Search for parameters and print the Pareto frontier as `RespireParams` literals
+    ParamSearch(ParamSearchArgs),
+    /// Measure the per-stage noise on small databases and compare it with the estimates
+    NoiseProfile(NoiseProfileArgs),
+    /// Serve one shard of a configuration's database to a shard coordinator
+    Shard(ShardArgs),
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Database size in bytes, with an optional k, m or g suffix (e.g. 256m)
+    #[arg(long, value_parser = parse_size)]
+    db_size: usize,
+    /// Size of each record in bytes
+    #[arg(long, default_value_t = 256)]
+    record_size: usize,
+    /// Number of records retrieved per query
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Write the JSON or CSV output to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    harness: HarnessArgs,
+}
+
+#[derive(Args, Debug)]
+struct ShardArgs {
+    /// Database size in bytes, with an optional k, m or g suffix (e.g. 256m)
+    #[arg(long, value_parser = parse_size)]
+    db_size: usize,
+    /// Size of each record in bytes
+    #[arg(long, default_value_t = 256)]
+    record_size: usize,
+    /// Number of records retrieved per query
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+    /// Number of shards the columns (or Cuckoo buckets) are split into
+    #[arg(long)]
+    num_shards: usize,
+    /// Which of the shards to serve, from 0
+    #[arg(long)]
+    shard: usize,
+    /// Address to listen on; the bound address is printed on stdout
+    #[arg(long, default_value = "127.0.0.1:0")]
+    listen: String,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// Human readable summary on stderr
+    Text,
+    /// A single JSON document with the parameters, sizes, estimates, and all measured times
+    Json,
+    /// One row per trial with the measured times
+    Csv,
+}
+
+#[derive(Args, Debug)]
+struct ParamSearchArgs {
+    /// Number of records in the database
+    #[arg(long)]
+    num_records: usize,
+    /// Size of each record in bytes
+    #[arg(long, default_value_t = 256)]
+    record_bytes: usize,
+    /// Number of records retrieved per query
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+    /// Largest acceptable log2 of the failure probability
+    #[arg(long, default_value_t = -40_f64, allow_negative_numbers = true)]
+    max_error_rate_log2: f64,
+    /// Smallest acceptable security level in bits
+    #[arg(long, default_value_t = DEFAULT_MIN_SECURITY_BITS)]
+    min_security_bits: f64,
+}
+
+#[derive(Args, Debug)]
+struct NoiseProfileArgs {
+    /// Number of query/answer cycles per parameter set
+    #[arg(long, default_value_t = 16)]
+    trials: usize,
+    /// Also profile a batched parameter set
+    #[arg(long)]
+    batch: bool,
+}
+
+fn list() {
+    println!(
+        "{:<12} {:>10} {:>12} {:>10}",
+        "name", "db size", "record size", "batch size"
+    );
+    for config in CONFIGS.iter() {
+        println!(
+            "{:<12} {:>10} {:>12} {:>10}",
+            config.name(),
+            format_size(config.db_bytes),
+            config.record_bytes,
+            config.batch_size
+        );
+    }
+}
+
+/// The configuration with the given sizes, exiting with an error if there is none.
+fn find_config(db_size: usize, record_size: usize, batch_size: usize) -> &'static Config {
+    let config = CONFIGS.iter().find(|c| {
+        c.db_bytes == db_size && c.record_bytes == record_size && c.batch_size == batch_size
+    });
+    match config {
+        Some(config) => {
+            eprintln!("Configuration {}", config.name());
+            config
+        }
+        None => {
+            eprintln!(
+                "No configuration with a {} database, {} byte records and batch size {}; \
+                 see --list for the supported ones",
+                format_size(db_size),
+                record_size,
+                batch_size
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run(args: &RunArgs) {
+    let config = find_config(args.db_size, args.record_size, args.batch_size);
+    let report = (config.run)(&args.harness).unwrap_or_else(|e| {
+        eprintln!("Refusing to run: {}", e);
+        std::process::exit(1);
+    });
+    write_report(&report, args.format, args.output.as_ref());
+}
+
+fn format_report(
+    report: &RunReport<RespireParamsExpanded>,
+    format: OutputFormat,
+) -> Option<String> {
+    match format {
+        OutputFormat::Text => None,
+        OutputFormat::Json => Some(serde_json::to_string_pretty(report).unwrap() + "\n"),
+        OutputFormat::Csv => Some(report.to_csv()),
+    }
+}
+
+fn write_report(
+    report: &RunReport<RespireParamsExpanded>,
+    format: OutputFormat,
+    output: Option<&PathBuf>,
+) {
+    let Some(out) = format_report(report, format) else {
+        return;
+    };
+    match output {
+        Some(path) => fs::write(path, out).unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }),
+        None => print!("{}", out),
+    }
+}
+
+fn param_search(args: &ParamSearchArgs) {
+    let target = SearchTarget {
+        num_records: args.num_records,
+        record_bytes: args.record_bytes,
+        batch_size: args.batch_size,
+        max_error_rate_log2: args.max_error_rate_log2,
+        min_security_bits: args.min_security_bits,
+    };
+    let frontier = search(&target, &SearchSpace::default());
+    eprintln!("{} parameter set(s) on the frontier", frontier.len());
+    for candidate in frontier {
+        println!("{}\n", candidate);
+    }
+}
+
+fn noise_profile(args: &NoiseProfileArgs) {
+    const SINGLE: RespireParamsExpanded = FactoryParams::single_record_256(3, 3).expand().expand();
+    const BATCH: RespireParamsExpanded = FactoryParams::batch_256(8, 2, 3, 3).expand().expand();
+    type Single = respire!(SINGLE);
+    type Batch = respire!(BATCH);
+
+    let mut rng = ChaCha20Rng::from_entropy();
+    println!("== Single record, NU1 = NU2 = 3 ==");
+    println!("{}", Single::noise_profile(args.trials, &mut rng));
+    if args.batch {
+        println!();
+        println!("== Batch of 8, N_VEC = 2, NU1 = NU2 = 3 ==");
+        println!("{}", Batch::noise_profile(args.trials, &mut rng));
+    }
+}
+
+fn shard(args: &ShardArgs) {
+    let config = find_config(args.db_size, args.record_size, args.batch_size);
+    if args.shard >= args.num_shards {
+        eprintln!(
+            "Shard {} does not exist with {} shards",
+            args.shard, args.num_shards
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = (config.shard)(args) {
+        eprintln!("Shard server failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Serves a column range of the database `harness_run` benchmarks.
+fn serve_columns<R: ShardRespire>(args: &ShardArgs) -> io::Result<()> {
+    let (db, _) = R::encode_db(harness_record::<R>, None);
+    let shard = R::shard_columns(&db, args.num_shards).swap_remove(args.shard);
+    drop(db);
+    listen_and_serve::<R>(args, &[shard])
+}
+
+/// Serves a bucket range of the Cuckoo database `harness_run` benchmarks.
+fn serve_buckets<C: PIR<Database = Vec<<R as PIR>::Database>>, R: ShardRespire>(
+    args: &ShardArgs,
+) -> io::Result<()> {
+    let (dbs, _) = C::encode_db(harness_record::<C>, None);
+    let shard = shard_buckets(dbs, args.num_shards).swap_remove(args.shard);
+    listen_and_serve::<R>(args, &shard)
+}
+
+fn listen_and_serve<R: ShardRespire>(
+    args: &ShardArgs,
+    dbs: &[<R as PIR>::Database],
+) -> io::Result<()> {
+    let listener = TcpListener::bind(&args.listen)?;
+    println!("listening on {}", listener.local_addr()?);
+    io::stdout().flush()?;
+    serve_shard::<R>(&listener, dbs)
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    match cli.command {
+        _ if cli.list => list(),
+        Some(Command::Run(args)) => run(&args),
+        Some(Command::ParamSearch(args)) => param_search(&args),
+        Some(Command::NoiseProfile(args)) => noise_profile(&args),
+        Some(Command::Shard(args)) => shard(&args),
+        None => list(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use respire::pir::respire_harness::{
+        run_pir, RespireSmall as Small, RESPIRE_SMALL_PARAMS as SMALL,
+    };
+
+    #[test]
+    fn test_json_report() {
+        let run_result = run_pir::<Small, _>([0_usize, 201].into_iter());
+        let report = RunReport::new::<Small>(&run_result);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_report(&report, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["summary"]["params"]["NU1"], 3);
+        assert_eq!(json["summary"]["query_size"], SMALL.query_size());
+        assert_eq!(json["trials"][1]["indices"][0], 201);
+        assert_eq!(json["trials"][1]["correct"], true);
+        assert!(json["trials"][0]["times_ns"]["answer_fold"].is_u64());
+        assert_eq!(json["failures"], 0);
+        assert!(format_report(&report, OutputFormat::Text).is_none());
+    }
+}
@@ -27,20 +27,43 @@ pub fn build_gadget<
     const G_BASE: u64,
     const G_LEN: usize,
 >() -> Matrix<N, M, R>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    build_gadget_approx::<R, N, M, G_BASE, G_LEN, 0>()
+}
+
+/// Builds the approximate gadget matrix, which omits the lowest `G_DROP` powers of `G_BASE`: each
+/// row is `(G_BASE^G_DROP, ..., G_BASE^(G_DROP + G_LEN - 1))`. This pairs with
+/// [`gadget_inverse_approx`].
+pub fn build_gadget_approx<
+    R: RingElementDecomposable<G_BASE, G_LEN>,
+    const N: usize,
+    const M: usize,
+    const G_BASE: u64,
+    const G_LEN: usize,
+    const G_DROP: usize,
+>() -> Matrix<N, M, R>
 where
     for<'a> &'a R: RingElementRef<R>,
 {
     let mut gadget = Matrix::zero();
 
-    let mut x = 1;
+    // Powers are computed in the ring, since `G_BASE^(G_LEN - 1)` need not fit in a `u64`.
+    let base = R::from(G_BASE);
+    let mut first = R::one();
+    for _ in 0..G_DROP {
+        first = &first * &base;
+    }
+    let mut x = first.clone();
     let mut i = 0;
 
     for j in 0..M {
-        gadget[(i, j)] = x.into();
-        x *= G_BASE;
+        let next = &x * &base;
+        gadget[(i, j)] = std::mem::replace(&mut x, next);
         if j % G_LEN == G_LEN - 1 {
             i += 1;
-            x = 1;
+            x = first.clone();
         }
     }
 
@@ -59,6 +82,22 @@ where
         i: usize,
         j: usize,
     );
+
+    /// Like `decompose_into_mat`, but rounds away the lowest `DROP` digits first, so that the `LEN`
+    /// digits written are the coefficients of `BASE^DROP, ..., BASE^(DROP + LEN - 1)`. Types that
+    /// only support exact decomposition keep the default, which requires `DROP == 0`.
+    fn decompose_approx_into_mat<const DROP: usize, const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
+    ) {
+        assert_eq!(
+            DROP, 0,
+            "approximate decomposition is not supported for this ring"
+        );
+        self.decompose_into_mat(mat, i, j);
+    }
 }
 
 pub struct IntModDecomposition<const BASE: u64, const LEN: usize> {
@@ -86,6 +125,33 @@ impl<const BASE: u64, const LEN: usize> IntModDecomposition<BASE, LEN> {
         sum
     }
 
+    /// Decomposes `a` with the lowest `drop` digits rounded away: the digits produced are those of
+    /// the balanced representative of `a` divided by `BASE^drop`, rounded to nearest. Requires
+    /// `n <= BASE^(LEN + drop)`.
+    pub fn new_approx(a: u64, n: u64, drop: usize) -> Self {
+        if drop == 0 {
+            return Self::new(a, n);
+        }
+        let fits = (BASE as u128)
+            .checked_pow((LEN + drop) as u32)
+            .is_none_or(|b| n as u128 <= b);
+        assert!(
+            fits,
+            "approximate decomposition requires modulus <= base^(len + drop)"
+        );
+
+        let negate_all = a > n / 2;
+        let mag = if negate_all { n - a } else { a } as u128;
+        let scale = (BASE as u128).saturating_pow(drop as u32);
+        let rounded = (mag + scale / 2) / scale;
+        Self {
+            a: rounded as u64,
+            negate_all,
+            k: 0,
+            n,
+        }
+    }
+
     pub fn new(mut a: u64, n: u64) -> Self {
         let negate_all = a > Self::max_positive(n);
         if negate_all {
@@ -143,13 +209,71 @@ where
     for<'a> &'a R: RingElementRef<R>,
 {
     let mut m_expanded: Matrix<M, K, R> = Matrix::zero();
+    gadget_inverse_into::<R, N, M, K, G_BASE, G_LEN>(m, &mut m_expanded);
+    m_expanded
+}
+
+/// Computes G^(-1) of an `N x K` matrix, writing the `M x K` result into `out`. Every entry of `out`
+/// is overwritten, so `out` can be reused across calls without being cleared.
+pub fn gadget_inverse_into<
+    R: RingElementDecomposable<G_BASE, G_LEN>,
+    const N: usize,
+    const M: usize,
+    const K: usize,
+    const G_BASE: u64,
+    const G_LEN: usize,
+>(
+    m: &Matrix<N, K, R>,
+    out: &mut Matrix<M, K, R>,
+) where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    gadget_inverse_approx_into::<R, N, M, K, G_BASE, G_LEN, 0>(m, out);
+}
 
+/// Computes the approximate G^(-1) of an `N x K` matrix, dropping the lowest `G_DROP` digits, so
+/// that `build_gadget_approx * gadget_inverse_approx(m)` equals `m` up to a per-coefficient error
+/// of at most `G_BASE^G_DROP / 2`.
+pub fn gadget_inverse_approx<
+    R: RingElementDecomposable<G_BASE, G_LEN>,
+    const N: usize,
+    const M: usize,
+    const K: usize,
+    const G_BASE: u64,
+    const G_LEN: usize,
+    const G_DROP: usize,
+>(
+    m: &Matrix<N, K, R>,
+) -> Matrix<M, K, R>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    let mut m_expanded: Matrix<M, K, R> = Matrix::zero();
+    gadget_inverse_approx_into::<R, N, M, K, G_BASE, G_LEN, G_DROP>(m, &mut m_expanded);
+    m_expanded
+}
+
+/// Computes the approximate G^(-1) of an `N x K` matrix into `out`; see [`gadget_inverse_approx`].
+pub fn gadget_inverse_approx_into<
+    R: RingElementDecomposable<G_BASE, G_LEN>,
+    const N: usize,
+    const M: usize,
+    const K: usize,
+    const G_BASE: u64,
+    const G_LEN: usize,
+    const G_DROP: usize,
+>(
+    m: &Matrix<N, K, R>,
+    out: &mut Matrix<M, K, R>,
+) where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    debug_assert_eq!(N * G_LEN, M, "gadget dimensions do not match");
     for i in 0..N {
         for j in 0..K {
-            m[(i, j)].decompose_into_mat(&mut m_expanded, i * G_LEN, j);
+            m[(i, j)].decompose_approx_into_mat::<G_DROP, M, K>(out, i * G_LEN, j);
         }
     }
-    m_expanded
 }
 
 pub fn gadget_inverse_scalar<
@@ -172,6 +296,7 @@ mod test {
     use super::*;
     use crate::math::int_mod::IntMod;
     use crate::math::utils::ceil_log;
+    use std::cmp::min;
 
     const N: usize = 2;
     const M: usize = 8;
@@ -213,6 +338,67 @@ mod test {
         assert_eq!(m, m_hopefully, "gadget inverse was not correct");
     }
 
+    #[test]
+    fn gadget_inverse_crt_eval_matches_coeff() {
+        use crate::math::int_mod_cyclo_crt::IntModCycloCRT;
+        use crate::math::int_mod_cyclo_crt_eval::IntModCycloCRTEval;
+        use crate::math::rand_sampled::RandUniformSampled;
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha20Rng;
+
+        const D: usize = 2048;
+        const P1: u64 = 268369921;
+        const P2: u64 = 249561089;
+        const LEN: usize = 5;
+        const BASE: u64 = base_from_len(LEN, P1 * P2);
+        type Coeff = IntModCycloCRT<D, P1, P2>;
+        type Eval = IntModCycloCRTEval<D, P1, P2>;
+
+        let mut rng = ChaCha20Rng::from_seed([4; 32]);
+        let mut m: Matrix<1, 1, Coeff> = Matrix::zero();
+        m[(0, 0)] = Coeff::rand_uniform(&mut rng);
+        let mut m_eval: Matrix<1, 1, Eval> = Matrix::zero();
+        m_eval[(0, 0)] = Eval::from(&m[(0, 0)]);
+
+        let expected = gadget_inverse::<Coeff, 1, LEN, 1, BASE, LEN>(&m);
+        // Garbage in the output must be overwritten
+        let mut actual: Matrix<LEN, 1, Eval> = Matrix::zero();
+        for k in 0..LEN {
+            actual[(k, 0)] = Eval::rand_uniform(&mut rng);
+        }
+        gadget_inverse_into::<Eval, 1, LEN, 1, BASE, LEN>(&m_eval, &mut actual);
+        for k in 0..LEN {
+            assert_eq!(actual[(k, 0)], Eval::from(&expected[(k, 0)]));
+        }
+    }
+
+    #[test]
+    fn gadget_inverse_approx_is_close() {
+        const Q: u64 = 268369921;
+        const BASE: u64 = 4;
+        const DROP: usize = 3;
+        const LEN: usize = ceil_log(BASE, Q) - DROP;
+        const M: usize = N * LEN;
+
+        let mut m: Matrix<N, 1, IntMod<Q>> = Matrix::zero();
+        let g_mat = build_gadget_approx::<IntMod<Q>, N, M, BASE, LEN, DROP>();
+        for a in (0..Q).step_by(9973).chain(Q - 100..Q) {
+            m[(0, 0)] = a.into();
+            m[(1, 0)] = (Q - 1 - a).into();
+            let g_inv_m = gadget_inverse_approx::<IntMod<Q>, N, M, 1, BASE, LEN, DROP>(&m);
+            let diff = &(&g_mat * &g_inv_m) - &m;
+            for i in 0..N {
+                let d = u64::from(diff[(i, 0)]);
+                assert!(
+                    min(d, Q - d) <= BASE.pow(DROP as u32) / 2,
+                    "approximate gadget inverse of {} is off by {}",
+                    u64::from(m[(i, 0)]),
+                    d
+                );
+            }
+        }
+    }
+
     #[test]
     fn gadget_factors() {
         let q = 268369921 * 249561089;
@@ -178,7 +178,18 @@ impl<const NN: u64, const BASE: u64, const LEN: usize> RingElementDecomposable<B
         i: usize,
         j: usize,
     ) {
-        let decomp = IntModDecomposition::<BASE, LEN>::new(u64::from(*self), NN);
+        <Self as RingElementDecomposable<BASE, LEN>>::decompose_approx_into_mat::<0, N, M>(
+            self, mat, i, j,
+        );
+    }
+
+    fn decompose_approx_into_mat<const DROP: usize, const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
+    ) {
+        let decomp = IntModDecomposition::<BASE, LEN>::new_approx(u64::from(*self), NN, DROP);
         for (k, u) in decomp.enumerate() {
             mat[(i + k, j)] = IntMod::from(u);
         }
@@ -381,7 +392,7 @@ impl<const N: u64> IntMod<N> {
 
     pub const fn mul_const(lhs: Self, rhs: Self) -> Self {
         let result = (lhs.a as u128) * (rhs.a as u128);
-        Self::from_u64_const(result as u64)
+        Self::from_u64_const((result % N as u128) as u64)
     }
 }
 
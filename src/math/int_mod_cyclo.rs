@@ -238,12 +238,24 @@ impl<const D: usize, const NN: u64, const BASE: u64, const LEN: usize>
         mat: &mut Matrix<N, M, Self>,
         i: usize,
         j: usize,
+    ) {
+        <Self as RingElementDecomposable<BASE, LEN>>::decompose_approx_into_mat::<0, N, M>(
+            self, mat, i, j,
+        );
+    }
+
+    fn decompose_approx_into_mat<const DROP: usize, const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
     ) {
         let mut decomps = Vec::<IntModDecomposition<BASE, LEN>>::with_capacity(D);
         for coeff_idx in 0..D {
-            decomps.push(IntModDecomposition::<BASE, LEN>::new(
+            decomps.push(IntModDecomposition::<BASE, LEN>::new_approx(
                 u64::from(self.coeff[coeff_idx]),
                 NN,
+                DROP,
             ));
         }
         for k in 0..LEN {
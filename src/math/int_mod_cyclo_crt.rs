@@ -210,13 +210,24 @@ impl<const D: usize, const N1: u64, const N2: u64, const BASE: u64, const LEN: u
         mat: &mut Matrix<N, M, Self>,
         i: usize,
         j: usize,
+    ) {
+        <Self as RingElementDecomposable<BASE, LEN>>::decompose_approx_into_mat::<0, N, M>(
+            self, mat, i, j,
+        );
+    }
+
+    fn decompose_approx_into_mat<const DROP: usize, const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
     ) {
         for coeff_idx in 0..D {
             let coeff = u64::from(IntModCRT::<N1, N2>::from((
                 self.proj1.coeff[coeff_idx],
                 self.proj2.coeff[coeff_idx],
             )));
-            let decomp = IntModDecomposition::<BASE, LEN>::new(coeff, N1 * N2);
+            let decomp = IntModDecomposition::<BASE, LEN>::new_approx(coeff, N1 * N2, DROP);
             for (k, u) in decomp.enumerate() {
                 let u_crt = IntModCRT::<N1, N2>::from(u);
                 mat[(i + k, j)].proj1.coeff[coeff_idx] = u_crt.proj1;
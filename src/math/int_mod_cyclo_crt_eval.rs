@@ -1,13 +1,15 @@
-use crate::math::gadget::RingElementDecomposable;
+use crate::math::gadget::{IntModDecomposition, RingElementDecomposable};
 use crate::math::int_mod::IntMod;
 use crate::math::int_mod_crt::IntModCRT;
 use crate::math::int_mod_cyclo::IntModCyclo;
 use crate::math::int_mod_cyclo_crt::IntModCycloCRT;
 use crate::math::int_mod_cyclo_eval::IntModCycloEval;
 use crate::math::int_mod_poly::IntModPoly;
-use crate::math::matrix::Matrix;
+use crate::math::matrix::{Matrix, PreparedMatrixMul};
+use crate::math::ntt::{ntt_neg_backward, ntt_neg_forward};
 use crate::math::rand_sampled::*;
 use crate::math::ring_elem::*;
+use crate::math::simd_utils::Aligned32;
 use rand::Rng;
 use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
@@ -227,21 +229,123 @@ impl<const D: usize, const N1: u64, const N2: u64, const BASE: u64, const LEN: u
         i: usize,
         j: usize,
     ) {
-        // TODO: do the decomposition and/or NTTs in place
-        let self_coeff = IntModCycloCRT::<D, N1, N2>::from(self);
-        let mut tmp = Matrix::<LEN, 1, IntModCycloCRT<D, N1, N2>>::zero();
-        <IntModCycloCRT<D, N1, N2> as RingElementDecomposable<BASE, LEN>>::decompose_into_mat(
-            &self_coeff,
-            &mut tmp,
-            0,
-            0,
+        <Self as RingElementDecomposable<BASE, LEN>>::decompose_approx_into_mat::<0, N, M>(
+            self, mat, i, j,
         );
+    }
+
+    /// Fused decomposition: inverse NTTs into stack buffers, CRT reconstruction and balanced digit
+    /// extraction per coefficient, with each digit written straight into `mat` and then forward
+    /// NTT'd in place. The NTTs use the AVX2 kernels when available.
+    fn decompose_approx_into_mat<const DROP: usize, const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
+    ) {
+        let mut coeff1 = Aligned32(self.proj1.evals);
+        let mut coeff2 = Aligned32(self.proj2.evals);
+        ntt_neg_backward::<D, N1>(&mut coeff1);
+        ntt_neg_backward::<D, N2>(&mut coeff2);
+
+        for coeff_idx in 0..D {
+            let coeff = u64::from(IntModCRT::<N1, N2>::from((
+                coeff1.0[coeff_idx],
+                coeff2.0[coeff_idx],
+            )));
+            let decomp = IntModDecomposition::<BASE, LEN>::new_approx(coeff, N1 * N2, DROP);
+            for (k, u) in decomp.enumerate() {
+                let out = &mut mat[(i + k, j)];
+                out.proj1.evals[coeff_idx] = u.into();
+                out.proj2.evals[coeff_idx] = u.into();
+            }
+        }
+
         for k in 0..LEN {
-            mat[(i + k, j)] = IntModCycloCRTEval::from(tmp[(k, 0)].clone());
+            let out = &mut mat[(i + k, j)];
+            ntt_neg_forward::<D, N1>(out.proj1.as_aligned_mut());
+            ntt_neg_forward::<D, N2>(out.proj2.as_aligned_mut());
+        }
+    }
+}
+
+/// A matrix over [`IntModCycloCRTEval`] laid out by evaluation point; see
+/// [`PreparedMatrixMul`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntModCycloCRTEvalPrepared<const N: usize, const M: usize, const D: usize> {
+    // `proj1[(i * N + r) * M + k]` is evaluation `i` of entry `(r, k)`, and likewise for `proj2`
+    proj1: Vec<u64>,
+    proj2: Vec<u64>,
+}
+
+impl<const N: usize, const M: usize, const D: usize, const N1: u64, const N2: u64>
+    PreparedMatrixMul<N, M> for IntModCycloCRTEval<D, N1, N2>
+{
+    type Prepared = IntModCycloCRTEvalPrepared<N, M, D>;
+
+    fn prepare(mat: &Matrix<N, M, Self>) -> Self::Prepared {
+        assert!(N1 != 0 && N2 != 0 && N1 <= u32::MAX as u64 && N2 <= u32::MAX as u64);
+        let mut proj1 = Vec::with_capacity(D * N * M);
+        let mut proj2 = Vec::with_capacity(D * N * M);
+        for i in 0..D {
+            for r in 0..N {
+                for k in 0..M {
+                    proj1.push(u64::from(mat[(r, k)].proj1.evals[i]));
+                    proj2.push(u64::from(mat[(r, k)].proj2.evals[i]));
+                }
+            }
+        }
+        IntModCycloCRTEvalPrepared { proj1, proj2 }
+    }
+
+    /// Each output evaluation sums its `M` products in a `u64` and is reduced once (or once every
+    /// `lazy_terms` products), instead of once per product.
+    fn add_eq_mul_prepared(
+        mat: &Self::Prepared,
+        outs: &mut [Matrix<N, 1, Self>],
+        vecs: &[Matrix<M, 1, Self>],
+    ) {
+        assert_eq!(outs.len(), vecs.len());
+        for i in 0..D {
+            let mat1 = &mat.proj1[i * N * M..(i + 1) * N * M];
+            let mat2 = &mat.proj2[i * N * M..(i + 1) * N * M];
+            for (out, vec) in outs.iter_mut().zip(vecs.iter()) {
+                for r in 0..N {
+                    let sum1 = dot_lazy::<N1>(&mat1[r * M..(r + 1) * M], |k| {
+                        u64::from(vec[(k, 0)].proj1.evals[i])
+                    });
+                    let sum2 = dot_lazy::<N2>(&mat2[r * M..(r + 1) * M], |k| {
+                        u64::from(vec[(k, 0)].proj2.evals[i])
+                    });
+                    let out = &mut out[(r, 0)];
+                    out.proj1.evals[i] += IntMod::from(sum1);
+                    out.proj2.evals[i] += IntMod::from(sum2);
+                }
+            }
         }
     }
 }
 
+/// Number of products of residues mod `q` that can be added to a reduced sum without overflowing.
+const fn lazy_terms(q: u64) -> usize {
+    let max_product = (q - 1) as u128 * (q - 1) as u128;
+    ((u64::MAX - (q - 1)) as u128 / max_product) as usize
+}
+
+/// `sum_k row[k] * x(k) mod Q`, reducing only as often as needed to avoid overflow.
+#[inline(always)]
+fn dot_lazy<const Q: u64>(row: &[u64], x: impl Fn(usize) -> u64) -> u64 {
+    let terms = lazy_terms(Q);
+    let mut sum = 0_u64;
+    for (chunk_idx, chunk) in row.chunks(terms).enumerate() {
+        for (k, a) in chunk.iter().enumerate() {
+            sum += a * x(chunk_idx * terms + k);
+        }
+        sum %= Q;
+    }
+    sum
+}
+
 /// Random sampling
 
 impl<const D: usize, const N1: u64, const N2: u64> RandUniformSampled
@@ -279,6 +383,12 @@ impl<const D: usize, const N1: u64, const N2: u64> IntModCycloCRTEval<D, N1, N2>
     pub fn mul_x_pow(&self, k: usize) -> Self {
         (self.proj1.mul_x_pow(k), self.proj2.mul_x_pow(k)).into()
     }
+
+    /// Same as `mul_x_pow`, but writes the result into `out`.
+    pub fn mul_x_pow_into(&self, k: usize, out: &mut Self) {
+        self.proj1.mul_x_pow_into(k, &mut out.proj1);
+        self.proj2.mul_x_pow_into(k, &mut out.proj2);
+    }
 }
 
 // TODO: this should be a TryFrom
@@ -37,6 +37,13 @@ impl<const D: usize, const N: u64> IntModCycloEval<D, N> {
     pub fn into_aligned(self) -> Aligned32<[IntMod<N>; D]> {
         Aligned32(self.evals)
     }
+
+    /// Views the evaluations as an aligned array, e.g. to run an NTT in place.
+    pub fn as_aligned_mut(&mut self) -> &mut Aligned32<[IntMod<N>; D]> {
+        // Safety: `Self` is `repr(C, align(32))` with `evals` as its only field, which is the
+        // same layout as `Aligned32<[IntMod<N>; D]>`.
+        unsafe { &mut *(self as *mut Self as *mut Aligned32<[IntMod<N>; D]>) }
+    }
 }
 
 /// Conversions
@@ -249,14 +256,19 @@ impl<const D: usize, const N: u64> IntModCycloEval<D, N> {
     /// Multiply by x^k
     pub fn mul_x_pow(&self, k: usize) -> Self {
         let mut result = Self::zero();
+        self.mul_x_pow_into(k, &mut result);
+        result
+    }
+
+    /// Same as `mul_x_pow`, but writes the result into `out`.
+    pub fn mul_x_pow_into(&self, k: usize, out: &mut Self) {
         let mut w_curr = IntMod::from(mod_pow(Self::W, k as u64, N));
         let w_k_sq = w_curr * w_curr;
         for i in 0..D {
             let i_rev = reverse_bits_fast::<D>(i);
-            result.evals[i_rev] = self.evals[i_rev] * w_curr;
+            out.evals[i_rev] = self.evals[i_rev] * w_curr;
             w_curr *= w_k_sq;
         }
-        result
     }
 }
 
@@ -0,0 +1,721 @@
+//! The cyclotomic ring `Z_q[x]/(x^d + 1)`, where `q = q_1 * ... * q_k` is a product of any number of
+//! NTT-friendly primes, and `d` is assumed to be a power of `2`. Elements are stored in evaluation
+//! form modulo each prime (a residue number system), so unlike [`IntModCycloCRTEval`], `q` may exceed
+//! 64 bits. Each prime must be below `2^62` and congruent to `1` modulo `2d`, and `q` must be below
+//! `2^127` so that coefficients can be reconstructed in a `u128`. `RespireImpl` does not compute in
+//! this ring: its database layout assumes two primes below `2^32` (see `RingQ1Eval`).
+//!
+//! The primes are supplied by a zero-sized [`RNSBasis`] type, e.g.
+//! ```
+//! use respire::math::int_mod_cyclo_rns_eval::{IntModCycloRNSEval, RNSBasis};
+//!
+//! #[derive(Debug, Clone, PartialEq, Eq)]
+//! struct ThreePrimes;
+//! impl RNSBasis<3> for ThreePrimes {
+//!     const MODULI: [u64; 3] = [268369921, 249561089, 34359709697];
+//! }
+//!
+//! type Ring = IntModCycloRNSEval<2048, 3, ThreePrimes>;
+//! assert!(Ring::MODULUS > u64::MAX as u128);
+//! ```
+
+use crate::math::gadget::RingElementDecomposable;
+use crate::math::int_mod::IntMod;
+use crate::math::int_mod_cyclo::IntModCyclo;
+use crate::math::int_mod_cyclo_crt_eval::IntModCycloCRTEval;
+use crate::math::int_mod_cyclo_eval::IntModCycloEval;
+use crate::math::matrix::{Matrix, PreparedMatrixMul};
+use crate::math::ntt::{ntt_neg_backward_dyn, ntt_neg_forward_dyn, powers_bit_reversed_dyn};
+use crate::math::number_theory::{find_sqrt_primitive_root, mod_pow};
+use crate::math::rand_sampled::*;
+use crate::math::ring_elem::*;
+use crate::math::utils::reverse_bits_fast;
+use rand::Rng;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+///
+/// The list of primes `q_1, ..., q_k` underlying an [`IntModCycloRNSEval`].
+///
+pub trait RNSBasis<const K: usize>: 'static + Debug + Clone + PartialEq + Eq {
+    const MODULI: [u64; K];
+}
+
+/// Compile time lookup tables for a given basis and degree.
+struct RNSTable<const D: usize, const K: usize, B: RNSBasis<K>> {
+    _basis: PhantomData<B>,
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> RNSTable<D, K, B> {
+    const MODULUS: u128 = modulus_product::<K>(B::MODULI);
+    const W: [u64; K] = sqrt_primitive_roots::<D, K>(B::MODULI);
+    const W_POWERS: [[u64; D]; K] = powers_tables::<D, K>(B::MODULI, Self::W, false);
+    const W_INV_POWERS: [[u64; D]; K] = powers_tables::<D, K>(B::MODULI, Self::W, true);
+    const INV_D: [u64; K] = inverses::<K>(B::MODULI, D as u64);
+    /// `(q_1 * ... * q_{i-1})^{-1} mod q_i`, used for Garner's algorithm.
+    const PREFIX_INV: [u64; K] = prefix_inverses::<K>(B::MODULI);
+}
+
+const fn modulus_product<const K: usize>(moduli: [u64; K]) -> u128 {
+    let mut product = 1_u128;
+    let mut i = 0;
+    while i < K {
+        if moduli[i] >= (1 << 62) {
+            panic!("RNS moduli must be below 2^62");
+        }
+        product = match product.checked_mul(moduli[i] as u128) {
+            Some(p) if p < (1 << 127) => p,
+            _ => panic!("RNS modulus must be below 2^127"),
+        };
+        i += 1;
+    }
+    product
+}
+
+const fn sqrt_primitive_roots<const D: usize, const K: usize>(moduli: [u64; K]) -> [u64; K] {
+    let mut result = [0_u64; K];
+    let mut i = 0;
+    while i < K {
+        result[i] = find_sqrt_primitive_root(D, moduli[i]);
+        i += 1;
+    }
+    result
+}
+
+const fn powers_tables<const D: usize, const K: usize>(
+    moduli: [u64; K],
+    roots: [u64; K],
+    invert: bool,
+) -> [[u64; D]; K] {
+    let mut result = [[0_u64; D]; K];
+    let mut i = 0;
+    while i < K {
+        let root = if invert {
+            mod_pow(roots[i], moduli[i] - 2, moduli[i])
+        } else {
+            roots[i]
+        };
+        result[i] = powers_bit_reversed_dyn::<D>(root, moduli[i]);
+        i += 1;
+    }
+    result
+}
+
+const fn inverses<const K: usize>(moduli: [u64; K], a: u64) -> [u64; K] {
+    let mut result = [0_u64; K];
+    let mut i = 0;
+    while i < K {
+        result[i] = mod_pow(a % moduli[i], moduli[i] - 2, moduli[i]);
+        i += 1;
+    }
+    result
+}
+
+const fn prefix_inverses<const K: usize>(moduli: [u64; K]) -> [u64; K] {
+    let mut result = [0_u64; K];
+    let mut i = 0;
+    while i < K {
+        let mut prefix = 1_u64;
+        let mut j = 0;
+        while j < i {
+            prefix = ((prefix as u128 * moduli[j] as u128) % moduli[i] as u128) as u64;
+            j += 1;
+        }
+        result[i] = mod_pow(prefix, moduli[i] - 2, moduli[i]);
+        i += 1;
+    }
+    result
+}
+
+fn mul_mod(a: u64, b: u64, q: u64) -> u64 {
+    ((a as u128 * b as u128) % q as u128) as u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(C, align(32))]
+pub struct IntModCycloRNSEval<const D: usize, const K: usize, B: RNSBasis<K>> {
+    /// `limbs[i]` holds the evaluations modulo `B::MODULI[i]`, in the same bit-reversed order as
+    /// [`IntModCycloEval`].
+    pub limbs: [[u64; D]; K],
+    _basis: PhantomData<B>,
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> IntModCycloRNSEval<D, K, B> {
+    /// The modulus `q = q_1 * ... * q_k`.
+    pub const MODULUS: u128 = RNSTable::<D, K, B>::MODULUS;
+
+    fn from_limbs(limbs: [[u64; D]; K]) -> Self {
+        Self {
+            limbs,
+            _basis: PhantomData,
+        }
+    }
+
+    /// Converts coefficient-form limbs into evaluation form in place.
+    fn forward(limbs: &mut [[u64; D]; K]) {
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            ntt_neg_forward_dyn::<D>(limb, B::MODULI[i], &RNSTable::<D, K, B>::W_POWERS[i]);
+        }
+    }
+
+    /// Returns the limbs in coefficient form.
+    fn to_coeff_limbs(&self) -> [[u64; D]; K] {
+        let mut limbs = self.limbs;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            ntt_neg_backward_dyn::<D>(
+                limb,
+                B::MODULI[i],
+                &RNSTable::<D, K, B>::W_INV_POWERS[i],
+                RNSTable::<D, K, B>::INV_D[i],
+            );
+        }
+        limbs
+    }
+
+    /// Reconstructs the coefficient at `idx` of coefficient-form `limbs` as an integer in `[0, q)`
+    /// using Garner's algorithm.
+    fn crt_coeff(limbs: &[[u64; D]; K], idx: usize) -> u128 {
+        let mut x = limbs[0][idx] as u128;
+        let mut prefix = B::MODULI[0] as u128;
+        for (i, limb) in limbs.iter().enumerate().skip(1) {
+            let q = B::MODULI[i];
+            let x_mod = (x % q as u128) as u64;
+            let diff = if limb[idx] >= x_mod {
+                limb[idx] - x_mod
+            } else {
+                limb[idx] + q - x_mod
+            };
+            let v = mul_mod(diff, RNSTable::<D, K, B>::PREFIX_INV[i], q);
+            x += prefix * v as u128;
+            prefix *= q as u128;
+        }
+        x
+    }
+
+    /// Reconstructs every coefficient as an integer in `[0, q)`.
+    pub fn crt_reconstruct(&self) -> Vec<u128> {
+        let limbs = self.to_coeff_limbs();
+        (0..D).map(|idx| Self::crt_coeff(&limbs, idx)).collect()
+    }
+
+    /// Maps `Z_M[x]/(x^d + 1)` into this ring by scaling each coefficient by `floor(q / M)`. This is
+    /// the analogue of `IntModCyclo::scale_up_into`, for moduli that do not fit in a `u64`.
+    pub fn scale_up_from<const M: u64>(a: &IntModCyclo<D, M>) -> Self {
+        assert!(M as u128 <= Self::MODULUS);
+        let ratio = Self::MODULUS / M as u128;
+        let mut limbs = [[0_u64; D]; K];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let q = B::MODULI[i];
+            let ratio_mod = (ratio % q as u128) as u64;
+            for (idx, c) in limb.iter_mut().enumerate() {
+                *c = mul_mod(u64::from(a.coeff[idx]) % q, ratio_mod, q);
+            }
+        }
+        Self::forward(&mut limbs);
+        Self::from_limbs(limbs)
+    }
+
+    /// Maps into `Z_M[x]/(x^d + 1)` by rounding each coefficient `0 <= a < q` to the nearest
+    /// multiple of `floor(q / M)`, and dividing. This acts like an inverse of `scale_up_from`, with
+    /// tolerance to additive noise.
+    pub fn round_down_into<const M: u64>(&self) -> IntModCyclo<D, M> {
+        assert!(Self::MODULUS >= M as u128);
+        let ratio = Self::MODULUS / M as u128;
+        let limbs = self.to_coeff_limbs();
+        let mut rounded_coeffs = [IntMod::zero(); D];
+        for (idx, out) in rounded_coeffs.iter_mut().enumerate() {
+            let coeff = Self::crt_coeff(&limbs, idx);
+            *out = ((((coeff + ratio / 2) / ratio) % M as u128) as u64).into();
+        }
+        rounded_coeffs.into()
+    }
+
+    /// The largest absolute value of a coefficient, viewed as an integer in `(-q/2, q/2]`.
+    pub fn norm(&self) -> u128 {
+        let limbs = self.to_coeff_limbs();
+        (0..D)
+            .map(|idx| {
+                let coeff = Self::crt_coeff(&limbs, idx);
+                coeff.min(Self::MODULUS - coeff)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn auto(&self, k: usize) -> Self {
+        let mut result = Self::zero();
+        let k_half = (k - 1) / 2;
+        for i in 0..D {
+            let rev_i = reverse_bits_fast::<D>(i);
+            let from = reverse_bits_fast::<D>((2 * k_half * rev_i + k_half + rev_i) % D);
+            for l in 0..K {
+                result.limbs[l][i] = self.limbs[l][from];
+            }
+        }
+        result
+    }
+
+    /// Multiply by x^k
+    pub fn mul_x_pow(&self, k: usize) -> Self {
+        let mut result = Self::zero();
+        self.mul_x_pow_into(k, &mut result);
+        result
+    }
+
+    /// Same as `mul_x_pow`, but writes the result into `out`.
+    pub fn mul_x_pow_into(&self, k: usize, out: &mut Self) {
+        for l in 0..K {
+            let q = B::MODULI[l];
+            let mut w_curr = mod_pow(RNSTable::<D, K, B>::W[l], k as u64, q);
+            let w_k_sq = mul_mod(w_curr, w_curr, q);
+            for i in 0..D {
+                let i_rev = reverse_bits_fast::<D>(i);
+                out.limbs[l][i_rev] = mul_mod(self.limbs[l][i_rev], w_curr, q);
+                w_curr = mul_mod(w_curr, w_k_sq, q);
+            }
+        }
+    }
+}
+
+// Conversions
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> From<u64> for IntModCycloRNSEval<D, K, B> {
+    fn from(a: u64) -> Self {
+        let mut limbs = [[0_u64; D]; K];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            limb.fill(a % B::MODULI[i]);
+        }
+        Self::from_limbs(limbs)
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>, const N: u64> From<&IntModCyclo<D, N>>
+    for IntModCycloRNSEval<D, K, B>
+{
+    /// Includes each coefficient `0 <= a < N` as the integer `a`. We require `N <= q`.
+    fn from(a: &IntModCyclo<D, N>) -> Self {
+        assert!(N as u128 <= Self::MODULUS);
+        let mut limbs = [[0_u64; D]; K];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            for (idx, c) in limb.iter_mut().enumerate() {
+                *c = u64::from(a.coeff[idx]) % B::MODULI[i];
+            }
+        }
+        Self::forward(&mut limbs);
+        Self::from_limbs(limbs)
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>, const N: u64>
+    From<&IntModCycloRNSEval<D, K, B>> for IntModCyclo<D, N>
+{
+    /// Reduces each coefficient `0 <= a < q` modulo `N`; the inverse of the inclusion above when
+    /// `N = q`.
+    fn from(a: &IntModCycloRNSEval<D, K, B>) -> Self {
+        let coeffs = a.crt_reconstruct();
+        let mut result = IntModCyclo::zero();
+        for (out, c) in result.coeff.iter_mut().zip(coeffs) {
+            *out = ((c % N as u128) as u64).into();
+        }
+        result
+    }
+}
+
+impl<const D: usize, const N1: u64, const N2: u64, B: RNSBasis<2>>
+    From<&IntModCycloCRTEval<D, N1, N2>> for IntModCycloRNSEval<D, 2, B>
+{
+    fn from(a: &IntModCycloCRTEval<D, N1, N2>) -> Self {
+        assert_eq!(B::MODULI, [N1, N2]);
+        Self::from_limbs([a.proj1.evals.map(u64::from), a.proj2.evals.map(u64::from)])
+    }
+}
+
+impl<const D: usize, const N1: u64, const N2: u64, B: RNSBasis<2>>
+    From<&IntModCycloRNSEval<D, 2, B>> for IntModCycloCRTEval<D, N1, N2>
+{
+    fn from(a: &IntModCycloRNSEval<D, 2, B>) -> Self {
+        assert_eq!(B::MODULI, [N1, N2]);
+        let mut proj1 = IntModCycloEval::<D, N1>::zero();
+        let mut proj2 = IntModCycloEval::<D, N2>::zero();
+        for idx in 0..D {
+            proj1.evals[idx] = a.limbs[0][idx].into();
+            proj2.evals[idx] = a.limbs[1][idx].into();
+        }
+        (proj1, proj2).into()
+    }
+}
+
+// [`RingElementRef`] implementation
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> RingElementRef<IntModCycloRNSEval<D, K, B>>
+    for &IntModCycloRNSEval<D, K, B>
+{
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> Add for &IntModCycloRNSEval<D, K, B> {
+    type Output = IntModCycloRNSEval<D, K, B>;
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut result = self.clone();
+        result += rhs;
+        result
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> Sub for &IntModCycloRNSEval<D, K, B> {
+    type Output = IntModCycloRNSEval<D, K, B>;
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut result = self.clone();
+        result -= rhs;
+        result
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> Mul for &IntModCycloRNSEval<D, K, B> {
+    type Output = IntModCycloRNSEval<D, K, B>;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut result = self.clone();
+        result *= rhs;
+        result
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> Neg for &IntModCycloRNSEval<D, K, B> {
+    type Output = IntModCycloRNSEval<D, K, B>;
+    fn neg(self) -> Self::Output {
+        let mut result = self.clone();
+        for (l, limb) in result.limbs.iter_mut().enumerate() {
+            let q = B::MODULI[l];
+            for x in limb.iter_mut() {
+                *x = if *x == 0 { 0 } else { q - *x };
+            }
+        }
+        result
+    }
+}
+
+// [`RingElement`] implementation
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> RingElement for IntModCycloRNSEval<D, K, B> {
+    fn zero() -> Self {
+        Self::from_limbs([[0_u64; D]; K])
+    }
+    fn one() -> Self {
+        Self::from(1_u64)
+    }
+
+    fn add_eq_mul(&mut self, a: &Self, b: &Self) {
+        for l in 0..K {
+            let q = B::MODULI[l];
+            for i in 0..D {
+                let sum = self.limbs[l][i] + mul_mod(a.limbs[l][i], b.limbs[l][i], q);
+                self.limbs[l][i] = if sum >= q { sum - q } else { sum };
+            }
+        }
+    }
+}
+
+impl<'a, const D: usize, const K: usize, B: RNSBasis<K>> AddAssign<&'a Self>
+    for IntModCycloRNSEval<D, K, B>
+{
+    fn add_assign(&mut self, rhs: &'a Self) {
+        for l in 0..K {
+            let q = B::MODULI[l];
+            for i in 0..D {
+                let sum = self.limbs[l][i] + rhs.limbs[l][i];
+                self.limbs[l][i] = if sum >= q { sum - q } else { sum };
+            }
+        }
+    }
+}
+
+impl<'a, const D: usize, const K: usize, B: RNSBasis<K>> SubAssign<&'a Self>
+    for IntModCycloRNSEval<D, K, B>
+{
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        for l in 0..K {
+            let q = B::MODULI[l];
+            for i in 0..D {
+                let (x, y) = (self.limbs[l][i], rhs.limbs[l][i]);
+                self.limbs[l][i] = if x >= y { x - y } else { x + q - y };
+            }
+        }
+    }
+}
+
+impl<'a, const D: usize, const K: usize, B: RNSBasis<K>> MulAssign<&'a Self>
+    for IntModCycloRNSEval<D, K, B>
+{
+    fn mul_assign(&mut self, rhs: &'a Self) {
+        for l in 0..K {
+            let q = B::MODULI[l];
+            for i in 0..D {
+                self.limbs[l][i] = mul_mod(self.limbs[l][i], rhs.limbs[l][i], q);
+            }
+        }
+    }
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>, const BASE: u64, const LEN: usize>
+    RingElementDecomposable<BASE, LEN> for IntModCycloRNSEval<D, K, B>
+{
+    /// Balanced `BASE`-ary decomposition of each coefficient, with the same digit convention as
+    /// [`IntModDecomposition`](crate::math::gadget::IntModDecomposition).
+    fn decompose_into_mat<const N: usize, const M: usize>(
+        &self,
+        mat: &mut Matrix<N, M, Self>,
+        i: usize,
+        j: usize,
+    ) {
+        let base = BASE as u128;
+        let max_positive = match base.checked_pow(LEN as u32) {
+            Some(b) if b < Self::MODULUS => {
+                panic!("RingElementDecomposable requires modulus <= base^len")
+            }
+            _ => (0..LEN).fold(0_u128, |sum, _| {
+                sum.saturating_mul(base).saturating_add(base / 2)
+            }),
+        };
+
+        let coeff_limbs = self.to_coeff_limbs();
+        for coeff_idx in 0..D {
+            let mut a = Self::crt_coeff(&coeff_limbs, coeff_idx);
+            let negate_all = a > max_positive;
+            if negate_all {
+                a = Self::MODULUS - a;
+            }
+            for k in 0..LEN {
+                let mut digit = (a % base) as u64;
+                a /= base;
+                let mut negative = false;
+                if digit > BASE / 2 {
+                    a += 1;
+                    digit = BASE - digit;
+                    negative = true;
+                }
+                negative ^= negate_all;
+                let out = &mut mat[(i + k, j)].limbs;
+                for (l, limb) in out.iter_mut().enumerate() {
+                    let q = B::MODULI[l];
+                    let reduced = digit % q;
+                    limb[coeff_idx] = if negative && reduced != 0 {
+                        q - reduced
+                    } else {
+                        reduced
+                    };
+                }
+            }
+        }
+        for k in 0..LEN {
+            Self::forward(&mut mat[(i + k, j)].limbs);
+        }
+    }
+}
+
+/// A matrix over [`IntModCycloRNSEval`] laid out by evaluation point; see [`PreparedMatrixMul`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntModCycloRNSEvalPrepared<const N: usize, const M: usize, const K: usize> {
+    // `limbs[l][(i * N + r) * M + k]` is evaluation `i` modulo `B::MODULI[l]` of entry `(r, k)`
+    limbs: [Vec<u64>; K],
+}
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>, const N: usize, const M: usize>
+    PreparedMatrixMul<N, M> for IntModCycloRNSEval<D, K, B>
+{
+    type Prepared = IntModCycloRNSEvalPrepared<N, M, K>;
+
+    fn prepare(mat: &Matrix<N, M, Self>) -> Self::Prepared {
+        let limbs = std::array::from_fn(|l| {
+            let mut limb = vec![0_u64; D * N * M];
+            for i in 0..D {
+                for r in 0..N {
+                    for k in 0..M {
+                        limb[(i * N + r) * M + k] = mat[(r, k)].limbs[l][i];
+                    }
+                }
+            }
+            limb
+        });
+        IntModCycloRNSEvalPrepared { limbs }
+    }
+
+    fn add_eq_mul_prepared(
+        mat: &Self::Prepared,
+        outs: &mut [Matrix<N, 1, Self>],
+        vecs: &[Matrix<M, 1, Self>],
+    ) {
+        assert_eq!(outs.len(), vecs.len());
+        for (l, limb) in mat.limbs.iter().enumerate() {
+            let q = B::MODULI[l];
+            for i in 0..D {
+                for (out, vec) in outs.iter_mut().zip(vecs.iter()) {
+                    for r in 0..N {
+                        let row = &limb[(i * N + r) * M..(i * N + r + 1) * M];
+                        let sum = row.iter().enumerate().fold(0_u128, |sum, (k, a)| {
+                            sum + (*a as u128 * vec[(k, 0)].limbs[l][i] as u128) % q as u128
+                        });
+                        let acc = &mut out[(r, 0)].limbs[l][i];
+                        *acc = ((*acc as u128 + sum) % q as u128) as u64;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Random sampling
+
+impl<const D: usize, const K: usize, B: RNSBasis<K>> RandUniformSampled
+    for IntModCycloRNSEval<D, K, B>
+{
+    fn rand_uniform<T: Rng>(rng: &mut T) -> Self {
+        let mut limbs = [[0_u64; D]; K];
+        for (l, limb) in limbs.iter_mut().enumerate() {
+            for x in limb.iter_mut() {
+                *x = rng.gen_range(0..B::MODULI[l]);
+            }
+        }
+        Self::from_limbs(limbs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::gadget::{base_from_len, build_gadget, gadget_inverse};
+    use crate::math::int_mod_poly::IntModPoly;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const D: usize = 16;
+    const P1: u64 = 268369921;
+    const P2: u64 = 249561089;
+    const P3: u64 = 34359709697;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct TwoPrimes;
+    impl RNSBasis<2> for TwoPrimes {
+        const MODULI: [u64; 2] = [P1, P2];
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct ThreePrimes;
+    impl RNSBasis<3> for ThreePrimes {
+        const MODULI: [u64; 3] = [P1, P2, P3];
+    }
+
+    type R2 = IntModCycloRNSEval<D, 2, TwoPrimes>;
+    type R3 = IntModCycloRNSEval<D, 3, ThreePrimes>;
+    type RCRT = IntModCycloCRTEval<D, P1, P2>;
+
+    #[test]
+    fn test_matches_crt_eval() {
+        let mut rng = ChaCha20Rng::from_seed([1; 32]);
+        let a = IntModCyclo::<D, { P1 * P2 }>::rand_uniform(&mut rng);
+        let b = IntModCyclo::<D, { P1 * P2 }>::rand_uniform(&mut rng);
+        let (a_rns, b_rns) = (R2::from(&a), R2::from(&b));
+        let (a_crt, b_crt) = (RCRT::from(&a), RCRT::from(&b));
+
+        assert_eq!(R2::from(&a_crt), a_rns);
+        assert_eq!(RCRT::from(&a_rns), a_crt);
+        assert_eq!(IntModCyclo::<D, { P1 * P2 }>::from(&a_rns), a);
+        assert_eq!(R2::from(&(&a_crt * &b_crt)), &a_rns * &b_rns);
+        assert_eq!(R2::from(&(&a_crt + &b_crt)), &a_rns + &b_rns);
+        assert_eq!(R2::from(&(&a_crt - &b_crt)), &a_rns - &b_rns);
+        assert_eq!(R2::from(&a_crt.auto(5)), a_rns.auto(5));
+        assert_eq!(R2::from(&a_crt.mul_x_pow(7)), a_rns.mul_x_pow(7));
+
+        const Z: u64 = base_from_len(4, P1 * P2);
+        let mut m_crt = Matrix::<1, 1, RCRT>::zero();
+        let mut m_rns = Matrix::<1, 1, R2>::zero();
+        m_crt[(0, 0)] = a_crt;
+        m_rns[(0, 0)] = a_rns;
+        let g_inv_crt = gadget_inverse::<RCRT, 1, 4, 1, Z, 4>(&m_crt);
+        let g_inv_rns = gadget_inverse::<R2, 1, 4, 1, Z, 4>(&m_rns);
+        for k in 0..4 {
+            assert_eq!(R2::from(&g_inv_crt[(k, 0)]), g_inv_rns[(k, 0)]);
+        }
+    }
+
+    #[test]
+    fn test_mul_per_prime() {
+        let mut rng = ChaCha20Rng::from_seed([2; 32]);
+        let a = R3::rand_uniform(&mut rng);
+        let b = R3::rand_uniform(&mut rng);
+        let prod = &a * &b;
+
+        fn limb_poly<const Q: u64>(x: &R3, l: usize) -> IntModCyclo<D, Q> {
+            let evals: [IntMod<Q>; D] = x.limbs[l].map(IntMod::from);
+            IntModCyclo::from(IntModCycloEval::from(evals))
+        }
+        assert_eq!(
+            limb_poly::<P1>(&prod, 0),
+            &limb_poly::<P1>(&a, 0) * &limb_poly::<P1>(&b, 0)
+        );
+        assert_eq!(
+            limb_poly::<P2>(&prod, 1),
+            &limb_poly::<P2>(&a, 1) * &limb_poly::<P2>(&b, 1)
+        );
+        assert_eq!(
+            limb_poly::<P3>(&prod, 2),
+            &limb_poly::<P3>(&a, 2) * &limb_poly::<P3>(&b, 2)
+        );
+    }
+
+    #[test]
+    fn test_crt_and_round_down() {
+        assert!(R3::MODULUS > u64::MAX as u128);
+        let mu = IntModCyclo::<D, 256>::from(vec![1_u64, 255, 17, 0, 128]);
+        let e = R3::from(&IntModCyclo::<D, 256>::from(vec![3_u64, 0, 1]));
+        let encoded = &R3::scale_up_from(&mu) - &e;
+        assert_eq!(encoded.round_down_into::<256>(), mu);
+        assert_eq!(e.norm(), 3);
+
+        let coeffs = (&R3::zero() - &R3::from(5_u64)).crt_reconstruct();
+        assert_eq!(coeffs[0], R3::MODULUS - 5);
+        assert!(coeffs[1..].iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_gadget_inverse_three_primes() {
+        const BASE: u64 = 1 << 23;
+        const LEN: usize = 4;
+        let mut rng = ChaCha20Rng::from_seed([3; 32]);
+        let mut m = Matrix::<1, 1, R3>::zero();
+        m[(0, 0)] = R3::rand_uniform(&mut rng);
+        let g_mat = build_gadget::<R3, 1, LEN, BASE, LEN>();
+        let g_inv = gadget_inverse::<R3, 1, LEN, 1, BASE, LEN>(&m);
+        assert_eq!(&g_mat * &g_inv, m);
+        for k in 0..LEN {
+            assert!(g_inv[(k, 0)].norm() <= (BASE / 2) as u128);
+        }
+    }
+
+    #[test]
+    fn test_prepared_mul_three_primes() {
+        let mut rng = ChaCha20Rng::from_seed([4; 32]);
+        let mat = Matrix::<2, 3, R3>::rand_uniform(&mut rng);
+        let vecs = (0..2)
+            .map(|_| Matrix::<3, 1, R3>::rand_uniform(&mut rng))
+            .collect::<Vec<_>>();
+        let mut outs = (0..2)
+            .map(|_| Matrix::<2, 1, R3>::rand_uniform(&mut rng))
+            .collect::<Vec<_>>();
+        let expected = outs
+            .iter()
+            .zip(vecs.iter())
+            .map(|(out, vec)| out + &(&mat * vec))
+            .collect::<Vec<_>>();
+        let prepared = <R3 as PreparedMatrixMul<2, 3>>::prepare(&mat);
+        R3::add_eq_mul_prepared(&prepared, &mut outs, &vecs);
+        assert_eq!(outs, expected);
+    }
+
+    #[test]
+    fn test_auto_three_primes() {
+        let x = R3::from(&IntModCyclo::<D, 256>::from(IntModPoly::x()));
+        let x_cubed = &(&x * &x) * &x;
+        assert_eq!(x.auto(3), x_cubed);
+        assert_eq!(x.mul_x_pow(2), x_cubed);
+    }
+}
@@ -4,8 +4,9 @@ use crate::math::rand_sampled::*;
 use crate::math::ring_elem::*;
 use rand::Rng;
 use std::cmp::max;
+use std::fmt::Debug;
 use std::mem::ManuallyDrop;
-use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
 
 // TODO
 // * Implement as an array instead of as a `Vec`. The main sticking point is that to move a matrix
@@ -223,12 +224,22 @@ where
     /// Multiplies each element of the matrix by `other`.
     fn mul(self, other: &R) -> Self::Output {
         let mut out = Matrix::zero();
+        Matrix::scalar_mul_into(&mut out, self, other);
+        out
+    }
+}
+
+impl<const N: usize, const M: usize, R: RingElement> MulAssign<&R> for Matrix<N, M, R>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    /// Multiplies each element of the matrix by `other` in place.
+    fn mul_assign(&mut self, other: &R) {
         for r in 0..N {
             for c in 0..M {
-                out[(r, c)] = &self[(r, c)] * other;
+                self[(r, c)] *= other;
             }
         }
-        out
     }
 }
 
@@ -261,9 +272,7 @@ where
     /// Naive matrix multiplication.
     fn mul(self, other: &Matrix<M, K, R>) -> Self::Output {
         let mut out = Matrix::zero();
-        self.mul_iter_do(other, |(r, c), lhs, rhs| {
-            out[(r, c)].add_eq_mul(lhs, rhs);
-        });
+        out.add_eq_mul(self, other);
         out
     }
 }
@@ -292,11 +301,7 @@ where
     /// Element-wise addition.
     fn add(self, other: &Matrix<N, M, R>) -> Self::Output {
         let mut out = Matrix::zero();
-        for r in 0..N {
-            for c in 0..M {
-                out[(r, c)] = &self[(r, c)] + &other[(r, c)];
-            }
-        }
+        Matrix::add_into(&mut out, self, other);
         out
     }
 }
@@ -323,11 +328,7 @@ where
     /// Element-wise subtraction.
     fn sub(self, other: &Matrix<N, M, R>) -> Self::Output {
         let mut out = Matrix::zero();
-        for r in 0..N {
-            for c in 0..M {
-                out[(r, c)] = &self[(r, c)] - &other[(r, c)]
-            }
-        }
+        Matrix::sub_into(&mut out, self, other);
         out
     }
 }
@@ -354,15 +355,88 @@ where
     /// Element-wise negation.
     fn neg(self) -> Self::Output {
         let mut out = Matrix::zero();
+        Matrix::neg_into(&mut out, self);
+        out
+    }
+}
+
+/// Allocation-free arithmetic. Each function overwrites every entry of `out`, so a single output
+/// matrix can be allocated once (e.g. with `Matrix::zero()`) and reused as scratch space across
+/// calls in hot loops. The operator implementations above are thin allocating wrappers of these.
+impl<const N: usize, const M: usize, R: RingElement> Matrix<N, M, R>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    /// Sets `out = a + b`.
+    pub fn add_into(out: &mut Self, a: &Self, b: &Self) {
         for r in 0..N {
             for c in 0..M {
-                out[(r, c)] = -&self[(r, c)];
+                out[(r, c)].clone_from(&a[(r, c)]);
+                out[(r, c)] += &b[(r, c)];
             }
         }
-        out
+    }
+
+    /// Sets `out = a - b`.
+    pub fn sub_into(out: &mut Self, a: &Self, b: &Self) {
+        for r in 0..N {
+            for c in 0..M {
+                out[(r, c)].clone_from(&a[(r, c)]);
+                out[(r, c)] -= &b[(r, c)];
+            }
+        }
+    }
+
+    /// Sets `out = -a`.
+    pub fn neg_into(out: &mut Self, a: &Self) {
+        for r in 0..N {
+            for c in 0..M {
+                out[(r, c)] = -&a[(r, c)];
+            }
+        }
+    }
+
+    /// Sets `out = a * s` for a scalar `s`.
+    pub fn scalar_mul_into(out: &mut Self, a: &Self, s: &R) {
+        for r in 0..N {
+            for c in 0..M {
+                out[(r, c)] = &a[(r, c)] * s;
+            }
+        }
+    }
+
+    /// Sets `out = a * b`.
+    pub fn mul_into<const K: usize>(out: &mut Self, a: &Matrix<N, K, R>, b: &Matrix<K, M, R>) {
+        for r in 0..N {
+            for c in 0..M {
+                out[(r, c)] = R::zero();
+            }
+        }
+        out.add_eq_mul(a, b);
     }
 }
 
+///
+/// Rings with a faster product by a fixed `N x M` matrix, once it is converted to
+/// `Self::Prepared`. This pays off when one matrix multiplies many column vectors, like a GSW
+/// ciphertext of a query does in every selection it is used for.
+///
+pub trait PreparedMatrixMul<const N: usize, const M: usize>: RingElement
+where
+    for<'a> &'a Self: RingElementRef<Self>,
+{
+    type Prepared: Clone + Debug + PartialEq + Eq;
+
+    fn prepare(mat: &Matrix<N, M, Self>) -> Self::Prepared;
+
+    /// Adds `mat * vecs[i]` to `outs[i]` for every `i`, where `mat` is the prepared matrix.
+    fn add_eq_mul_prepared(
+        mat: &Self::Prepared,
+        outs: &mut [Matrix<N, 1, Self>],
+        vecs: &[Matrix<M, 1, Self>],
+    );
+}
+
 /// Norm
 impl<const N: usize, const M: usize, R: RingElement> Matrix<N, M, R>
 where
@@ -566,6 +640,41 @@ mod test {
         );
     }
 
+    #[test]
+    fn into_ops_are_correct() {
+        let mut mat1: Matrix<N, M, IntMod<Q>> = Matrix::zero();
+        let mut mat2: Matrix<N, M, IntMod<Q>> = Matrix::zero();
+        let mut sq: Matrix<N, N, IntMod<Q>> = Matrix::zero();
+        for i in 0..N {
+            for j in 0..M {
+                mat1[(i, j)] = IntMod::from((i * M + j) as u64);
+                mat2[(i, j)] = IntMod::from((3 * i + j * j) as u64);
+            }
+            for j in 0..N {
+                sq[(i, j)] = IntMod::from((i + 2 * j + 1) as u64);
+            }
+        }
+
+        // Start from a dirty buffer to check that every entry is overwritten
+        let mut out: Matrix<N, M, IntMod<Q>> = Matrix::zero();
+        out.iter_do(|x| *x = IntMod::from(7_u64));
+
+        Matrix::add_into(&mut out, &mat1, &mat2);
+        assert_eq!(out, &mat1 + &mat2);
+        Matrix::sub_into(&mut out, &mat1, &mat2);
+        assert_eq!(out, &mat1 - &mat2);
+        Matrix::neg_into(&mut out, &mat1);
+        assert_eq!(out, -&mat1);
+        Matrix::scalar_mul_into(&mut out, &mat1, &IntMod::from(5_u64));
+        assert_eq!(out, &mat1 * &IntMod::from(5_u64));
+        Matrix::mul_into(&mut out, &sq, &mat2);
+        assert_eq!(out, &sq * &mat2);
+
+        let mut scaled = mat1.clone();
+        scaled *= &IntMod::from(5_u64);
+        assert_eq!(scaled, &mat1 * &IntMod::from(5_u64));
+    }
+
     #[test]
     fn scalar_mult_is_correct() {
         let mut mat1: Matrix<N, M, IntMod<Q>> = Matrix::zero();
@@ -7,6 +7,7 @@ pub mod int_mod_cyclo;
 pub mod int_mod_cyclo_crt;
 pub mod int_mod_cyclo_crt_eval;
 pub mod int_mod_cyclo_eval;
+pub mod int_mod_cyclo_rns_eval;
 pub mod int_mod_poly;
 pub mod matrix;
 pub mod ntt;
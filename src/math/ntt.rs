@@ -1,7 +1,7 @@
 use crate::math::int_mod::IntMod;
 use crate::math::int_mod_cyclo_eval::IntModCycloEval;
 use crate::math::simd_utils::Aligned32;
-use crate::math::utils::{floor_log, get_ratio32, mod_inverse, reverse_bits_fast};
+use crate::math::utils::{floor_log, get_ratio32, mod_inverse, reverse_bits, reverse_bits_fast};
 
 /// Compile time lookup table for NTT-related operations
 struct NTTTable<const D: usize, const N: u64> {}
@@ -315,6 +315,89 @@ pub fn ntt_neg_backward<const D: usize, const N: u64>(values: &mut Aligned32<[In
     }
 }
 
+// Runtime-modulus variants of the negacyclic NTT, for rings whose moduli are not const generics
+// (e.g. the limbs of `IntModCycloRNSEval`). These use `u128` products, so the modulus may be as large
+// as `2^62`. The evaluation order is the same bit-reversed order as `ntt_neg_forward`.
+
+///
+/// Computes the bit-reversed powers of `root` modulo `modulus`, in the layout expected by
+/// `ntt_neg_forward_dyn` and `ntt_neg_backward_dyn`.
+///
+pub const fn powers_bit_reversed_dyn<const D: usize>(root: u64, modulus: u64) -> [u64; D] {
+    let mut table = [0_u64; D];
+    let mut cur = 1_u64;
+    let mut idx = 0;
+    while idx < D {
+        table[reverse_bits(D, idx)] = cur;
+        cur = ((cur as u128 * root as u128) % modulus as u128) as u64;
+        idx += 1;
+    }
+    table
+}
+
+pub fn ntt_neg_forward_dyn<const D: usize>(
+    values: &mut [u64; D],
+    modulus: u64,
+    w_powers: &[u64; D],
+) {
+    // Algorithm 2 of https://arxiv.org/pdf/2103.16400.pdf
+    let mul = |a: u64, b: u64| ((a as u128 * b as u128) % modulus as u128) as u64;
+    for round in 0..floor_log(2, D as u64) {
+        let block_count = 1_usize << round;
+        let block_half_stride = D >> (1_usize + round);
+        let block_stride = 2 * block_half_stride;
+        for block_idx in 0..block_count {
+            let w = w_powers[block_count + block_idx];
+            let left_start = block_idx * block_stride;
+            for left_idx in left_start..(left_start + block_half_stride) {
+                let right_idx = left_idx + block_half_stride;
+                let x = values[left_idx];
+                let y = mul(w, values[right_idx]);
+                values[left_idx] = if x + y >= modulus {
+                    x + y - modulus
+                } else {
+                    x + y
+                };
+                values[right_idx] = if x >= y { x - y } else { x + modulus - y };
+            }
+        }
+    }
+}
+
+pub fn ntt_neg_backward_dyn<const D: usize>(
+    values: &mut [u64; D],
+    modulus: u64,
+    w_inv_powers: &[u64; D],
+    inv_d: u64,
+) {
+    // Algorithm 3 of https://arxiv.org/pdf/2103.16400.pdf
+    let mul = |a: u64, b: u64| ((a as u128 * b as u128) % modulus as u128) as u64;
+    for round in 0..floor_log(2, D as u64) {
+        let block_count = D >> (1_usize + round);
+        let block_half_stride = 1 << round;
+        let block_stride = 2 * block_half_stride;
+        for block_idx in 0..block_count {
+            let w = w_inv_powers[block_count + block_idx];
+            let left_start = block_idx * block_stride;
+            for left_idx in left_start..(left_start + block_half_stride) {
+                let right_idx = left_idx + block_half_stride;
+                let x = values[left_idx];
+                let y = values[right_idx];
+                values[left_idx] = if x + y >= modulus {
+                    x + y - modulus
+                } else {
+                    x + y
+                };
+                values[right_idx] = mul(if x >= y { x - y } else { x + modulus - y }, w);
+            }
+        }
+    }
+
+    for value in values.iter_mut() {
+        *value = mul(*value, inv_d);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -12,6 +12,46 @@ pub const fn mod_pow(mut a: u64, mut e: u64, n: u64) -> u64 {
     res
 }
 
+/// Deterministic Miller-Rabin; the first 12 prime witnesses suffice for every `u64`.
+pub const fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    let mut i = 0;
+    while i < WITNESSES.len() {
+        if n.is_multiple_of(WITNESSES[i]) {
+            return n == WITNESSES[i];
+        }
+        i += 1;
+    }
+
+    let mut d = n - 1;
+    let mut r = 0;
+    while d.is_multiple_of(2) {
+        d /= 2;
+        r += 1;
+    }
+    let mut i = 0;
+    'witness: while i < WITNESSES.len() {
+        let mut x = mod_pow(WITNESSES[i], d, n);
+        i += 1;
+        if x == 1 || x == n - 1 {
+            continue 'witness;
+        }
+        let mut j = 1;
+        while j < r {
+            x = mod_pow(x, 2, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+            j += 1;
+        }
+        return false;
+    }
+    true
+}
+
 pub const fn find_sqrt_primitive_root(degree: usize, modulus: u64) -> u64 {
     let double_degree = (2 * degree) as u64;
     if (modulus - 1) % double_degree != 0 {
@@ -53,6 +93,18 @@ mod test {
         assert_eq!(mod_pow(11, 65520, 268369921), 228368554);
     }
 
+    #[test]
+    fn test_is_prime() {
+        for p in [2, 3, 12289, 16760833, 268369921, 249561089, 4294955009] {
+            assert!(is_prime(p), "{p}");
+        }
+        // 3215031751 is a strong pseudoprime to bases 2, 3, 5 and 7
+        for n in [0, 1, 4, 12288, 268369921 * 3, 3215031751, u64::MAX] {
+            assert!(!is_prime(n), "{n}");
+        }
+        assert!(is_prime(18446744073709551557));
+    }
+
     #[test]
     fn test_find_sqrt_primitive_root() {
         let prim1 = find_sqrt_primitive_root(2048, 268369921);
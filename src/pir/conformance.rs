@@ -0,0 +1,120 @@
+//! A conformance suite for implementations of `PIR`.
+//!
+//! `check_conformance` encodes a database of random records, and retrieves random batches, the
+//! first and last indices, and a batch with a duplicate index. Every extracted record must equal
+//! its source record. Queries and responses go through `PIR::encode_query` and
+//! `PIR::encode_response` as they would over the wire, and the serialized lengths must be what
+//! `PIR::summary` reports.
+//! Unlike `run_pir`, which reports failures and keeps going, it panics on the first mismatch, so a
+//! scheme is tested by calling it from a `#[test]`, as the tests below do for the existing ones.
+
+use crate::pir::pir::{PIRRecordBytes, PIR};
+use itertools::Itertools;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::cmp::max;
+
+///
+/// Checks `P` against a random database generated from `seed`, with `num_random_batches` batches
+/// of random indices on top of the edge and duplicate index batches.
+///
+pub fn check_conformance<P: PIR>(seed: u64, num_random_batches: usize) {
+    let summary = P::summary();
+    assert_eq!(summary.num_records, P::NUM_RECORDS);
+    assert_eq!(summary.batch_size, P::BATCH_SIZE);
+    assert_eq!(summary.bytes_per_record, P::BYTES_PER_RECORD);
+    assert_eq!(summary.record_size, P::BATCH_SIZE * P::BYTES_PER_RECORD);
+    assert!(summary.query_size > 0 && summary.response_size > 0);
+
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let records = (0..P::NUM_RECORDS)
+        .map(|_| {
+            let mut bytes = vec![0_u8; P::BYTES_PER_RECORD];
+            rng.fill(bytes.as_mut_slice());
+            P::RecordBytes::from_bytes(&bytes).expect("random bytes are not a valid record")
+        })
+        .collect_vec();
+    let (db, db_hint) = P::encode_db(|idx| records[idx].clone(), None);
+    let (qk, pp) = P::setup(None);
+
+    let mut batches = Vec::new();
+    for _ in 0..num_random_batches {
+        batches.extend(pad_batches::<P>(&[], &mut rng));
+    }
+    batches.extend(pad_batches::<P>(&[0, P::NUM_RECORDS - 1], &mut rng));
+    // With a batch size of one, the duplicate is retrieved by the next query instead
+    let idx = rng.gen_range(0..P::NUM_RECORDS);
+    batches.extend(pad_batches::<P>(&[idx, idx], &mut rng));
+
+    for batch in batches {
+        let (q, st) = P::query(&qk, &batch, &db_hint, None);
+        let q_bytes = P::encode_query(&q);
+        assert_eq!(q_bytes.len(), summary.query_size, "query for {:?}", batch);
+        assert_eq!(P::query_size(&q), q_bytes.len());
+        assert!(P::decode_query(&q_bytes[..q_bytes.len() - 1]).is_err());
+        let q = P::decode_query(&q_bytes).expect("failed to decode the query");
+        assert_eq!(P::encode_query(&q), q_bytes);
+
+        let r = P::answer(&pp, &db, &q, None, None);
+        let r_bytes = P::encode_response(&r);
+        assert_eq!(
+            r_bytes.len(),
+            summary.response_size,
+            "response for {:?}",
+            batch
+        );
+        assert_eq!(P::response_size(&r), r_bytes.len());
+        assert!(P::decode_response(&r_bytes[..r_bytes.len() - 1]).is_err());
+        let r = P::decode_response(&r_bytes).expect("failed to decode the response");
+
+        let extracted = P::extract(&qk, &r, &st, None);
+        assert_eq!(extracted.len(), batch.len());
+        for (&idx, record) in batch.iter().zip(extracted.iter()) {
+            assert_eq!(
+                record.as_bytes(),
+                records[idx].as_bytes(),
+                "record {} in batch {:?}",
+                idx,
+                batch
+            );
+        }
+    }
+}
+
+/// Splits `indices` into full batches, filling the last one (or a single one, if there are no
+/// indices) with random indices.
+fn pad_batches<P: PIR>(indices: &[usize], rng: &mut ChaCha20Rng) -> Vec<Vec<usize>> {
+    let num_batches = max(1, indices.len().div_ceil(P::BATCH_SIZE));
+    let mut padded = indices.to_vec();
+    padded.resize_with(num_batches * P::BATCH_SIZE, || {
+        rng.gen_range(0..P::NUM_RECORDS)
+    });
+    padded
+        .chunks(P::BATCH_SIZE)
+        .map(|batch| batch.to_vec())
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::cuckoo_respire::CuckooRespireImpl;
+    use crate::pir::respire_harness::{RespireBatch as Batch, RespireSmall as Small, RespireTest};
+
+    #[test]
+    fn test_conformance_respire() {
+        check_conformance::<Small>(1, 2);
+        check_conformance::<Batch>(2, 1);
+    }
+
+    #[test]
+    fn test_conformance_cuckoo() {
+        check_conformance::<CuckooRespireImpl<4, 8, 400, Batch>>(3, 1);
+    }
+
+    #[test]
+    #[ignore = "encodes a database of several GiB"]
+    fn test_conformance_respire_test() {
+        check_conformance::<RespireTest>(5, 2);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::pir::pir::{Stats, PIR};
+use crate::pir::pir::{PIRSummary, Stats, PIR};
 use crate::pir::respire::Respire;
 use itertools::Itertools;
 use log::{info, warn};
@@ -6,6 +6,7 @@ use rand::{thread_rng, Rng};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::marker::PhantomData;
 use std::time::{Duration, Instant};
 
@@ -31,7 +32,13 @@ impl<
     > CuckooRespire for CuckooRespireImpl<BATCH_SIZE, NUM_BUCKET, NUM_RECORDS, BaseRespire>
 {
     type BaseRespire = BaseRespire;
-    const NUM_BUCKET: usize = NUM_BUCKET;
+    const NUM_BUCKET: usize = {
+        assert!(
+            NUM_BUCKET == BaseRespire::BATCH_SIZE,
+            "NUM_BUCKET must equal the BATCH_SIZE of the base Respire"
+        );
+        NUM_BUCKET
+    };
 }
 
 impl<
@@ -43,18 +50,19 @@ impl<
 {
     type QueryKey = BaseRespire::QueryKey;
     type PublicParams = BaseRespire::PublicParams;
-    type Query = Vec<BaseRespire::QueryOne>;
+    type Query = Vec<BaseRespire::QueryPacked>;
     type Response = Vec<BaseRespire::AnswerOneCompressed>;
     type Database = Vec<<BaseRespire as PIR>::Database>;
     type DatabaseHint = Vec<Vec<Option<usize>>>;
     type State = Vec<(usize, usize)>;
+    type Params = <BaseRespire as PIR>::Params;
+    type QueryError = <BaseRespire as PIR>::QueryError;
     type RecordBytes = BaseRespire::RecordBytes;
     const BYTES_PER_RECORD: usize = BaseRespire::BYTES_PER_RECORD;
     const NUM_RECORDS: usize = NUM_RECORDS;
     const BATCH_SIZE: usize = BATCH_SIZE;
 
     fn print_summary() {
-        assert_eq!(BaseRespire::BATCH_SIZE, Self::NUM_BUCKET);
         eprintln!(
             "Cuckoo RESPIRE with {} bytes x {} records ({:.3} MiB)",
             BaseRespire::BYTES_PER_RECORD,
@@ -99,7 +107,25 @@ impl<
         eprintln!(
             "Error rate (estimated): 2^({:.3})",
             BaseRespire::params_error_rate_estimate().log2()
-        )
+        );
+
+        for (name, estimate) in BaseRespire::params().security_estimates() {
+            eprintln!("Security (estimated) of {}: {}", name, estimate);
+        }
+    }
+
+    fn summary() -> PIRSummary<Self::Params> {
+        PIRSummary {
+            scheme: "cuckoo respire",
+            bytes_per_record: BaseRespire::BYTES_PER_RECORD,
+            num_records: Self::NUM_RECORDS,
+            batch_size: Self::BATCH_SIZE,
+            query_size: Self::params_query_size(),
+            response_size: Self::params_response_info().0,
+            record_size: Self::params_record_size(),
+            rate: Self::params_rate(),
+            ..BaseRespire::summary()
+        }
     }
 
     fn encode_db<F: Fn(usize) -> Self::RecordBytes>(
@@ -185,9 +211,8 @@ impl<
 
         assert_eq!(actual_idxs.len(), Self::NUM_BUCKET);
         let q = actual_idxs
-            .iter()
-            .copied()
-            .map(|idx| BaseRespire::query_one(qk, idx, time_stats.as_deref_mut()))
+            .chunks(BaseRespire::QUERY_PACK_SIZE)
+            .map(|chunk| BaseRespire::query_packed(qk, chunk, time_stats.as_deref_mut()))
             .collect_vec();
 
         (q, cuckoo_mapping)
@@ -200,12 +225,28 @@ impl<
         qk: Option<&Self::QueryKey>,
         mut time_stats: Option<&mut Stats<Duration>>,
     ) -> Self::Response {
-        assert_eq!(qs.len(), Self::NUM_BUCKET);
-        let answers = qs
-            .iter()
-            .zip(dbs)
-            .map(|(q, db)| BaseRespire::answer_one(pp, db, q, qk, time_stats.as_deref_mut()))
-            .collect_vec();
+        assert_eq!(
+            qs.len(),
+            Self::NUM_BUCKET.div_ceil(BaseRespire::QUERY_PACK_SIZE)
+        );
+        // The expansion does not depend on the database, so each packed query is expanded once and
+        // its indices answered against consecutive buckets.
+        let mut answers = Vec::with_capacity(Self::NUM_BUCKET);
+        let mut dbs_iter = dbs.iter();
+        for q in qs {
+            let expanded =
+                BaseRespire::answer_query_unpack_packed(pp, q, qk, time_stats.as_deref_mut());
+            for (expanded, db) in expanded.into_iter().zip(&mut dbs_iter) {
+                answers.push(BaseRespire::answer_one_expanded(
+                    pp,
+                    db,
+                    &expanded,
+                    qk,
+                    time_stats.as_deref_mut(),
+                ));
+            }
+        }
+        assert_eq!(answers.len(), Self::NUM_BUCKET);
         let answers_compressed = answers
             .chunks(BaseRespire::RESPONSE_CHUNK_SIZE)
             .map(|chunk| {
@@ -244,6 +285,22 @@ impl<
         }
         result
     }
+
+    fn encode_query(qs: &Self::Query) -> Vec<u8> {
+        BaseRespire::encode_query_packed(qs)
+    }
+
+    fn decode_query(bytes: &[u8]) -> io::Result<Self::Query> {
+        BaseRespire::decode_query_packed(bytes)
+    }
+
+    fn encode_response(r: &Self::Response) -> Vec<u8> {
+        BaseRespire::encode_answers_compressed(r)
+    }
+
+    fn decode_response(bytes: &[u8]) -> io::Result<Self::Response> {
+        BaseRespire::decode_answers_compressed(bytes)
+    }
 }
 
 impl<
@@ -309,7 +366,7 @@ impl<
     }
 
     pub fn params_query_size() -> usize {
-        Self::NUM_BUCKET * BaseRespire::params_query_one_size()
+        BaseRespire::params().query_size_for(Self::NUM_BUCKET)
     }
 
     pub fn params_record_size() -> usize {
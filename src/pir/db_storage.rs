@@ -0,0 +1,380 @@
+//! Storage backends for an encoded Respire database.
+//!
+//! The encoded database is a sequence of slabs, one per evaluation slot (per group of
+//! `SIMD_LANES` slots with AVX2), each holding one word per packed record. The first dimension
+//! scan reads the slabs once, in order, so a backend only has to support a sequential scan:
+//! - `InMemoryStorage` keeps the words in a `Vec`, as produced by `encode_db`.
+//! - `MmapStorage` maps a database file and lets the page cache do the reading.
+//! - `ChunkedFileStorage` reads a database file in fixed size chunks on a background thread,
+//!   a bounded number of chunks ahead of the scan, so only those chunks are resident.
+//!
+//! Database files are written by `write_db_file`, or slab segment by slab segment with
+//! `DbFileWriter`, which lets `RespireImpl::encode_db_file` encode a database larger than memory.
+//! They hold the raw words in native byte order after a short header, so they can only be read
+//! back by builds with the same `SIMD_LANES`.
+
+use crate::math::simd_utils::*;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"RSPRDB01");
+
+/// Header words: magic, `SIMD_LANES`, slab length, number of slabs. Its size keeps the data
+/// aligned for `SimdVec` in a mapped file.
+const HEADER_LEN: usize = 4;
+const HEADER_BYTES: usize = HEADER_LEN * size_of::<u64>();
+
+#[cfg(target_feature = "avx2")]
+const ZERO_WORD: SimdVec = Aligned32([0_u64; 4]);
+
+#[cfg(not(target_feature = "avx2"))]
+const ZERO_WORD: SimdVec = 0;
+
+pub trait DbStorage: Send + Sync {
+    fn num_slabs(&self) -> usize;
+    fn slab_len(&self) -> usize;
+
+    ///
+    /// Calls `f` with the index and contents of every slab, in order. Backends may read ahead of
+    /// `f`, but hand out each slab exactly once.
+    ///
+    fn scan(&self, f: &mut dyn FnMut(usize, &[SimdVec])) -> io::Result<()>;
+}
+
+pub struct InMemoryStorage {
+    words: Vec<SimdVec>,
+    slab_len: usize,
+}
+
+impl InMemoryStorage {
+    pub fn new(words: Vec<SimdVec>, slab_len: usize) -> Self {
+        assert!(slab_len > 0);
+        assert!(words.len().is_multiple_of(slab_len));
+        Self { words, slab_len }
+    }
+
+    pub fn words(&self) -> &[SimdVec] {
+        &self.words
+    }
+}
+
+impl DbStorage for InMemoryStorage {
+    fn num_slabs(&self) -> usize {
+        self.words.len() / self.slab_len
+    }
+
+    fn slab_len(&self) -> usize {
+        self.slab_len
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(usize, &[SimdVec])) -> io::Result<()> {
+        for (slab_idx, slab) in self.words.chunks_exact(self.slab_len).enumerate() {
+            f(slab_idx, slab);
+        }
+        Ok(())
+    }
+}
+
+pub struct MmapStorage {
+    mmap: Mmap,
+    num_slabs: usize,
+    slab_len: usize,
+}
+
+impl MmapStorage {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let (num_slabs, slab_len) = read_header(&mut file)?;
+        // SAFETY: the file is only read, and must not be modified while mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        mmap.advise(memmap2::Advice::Sequential)?;
+        Ok(Self {
+            mmap,
+            num_slabs,
+            slab_len,
+        })
+    }
+}
+
+impl DbStorage for MmapStorage {
+    fn num_slabs(&self) -> usize {
+        self.num_slabs
+    }
+
+    fn slab_len(&self) -> usize {
+        self.slab_len
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(usize, &[SimdVec])) -> io::Result<()> {
+        let data = &self.mmap[HEADER_BYTES..];
+        assert_eq!(data.as_ptr().align_offset(align_of::<SimdVec>()), 0);
+        // SAFETY: `read_header` checked the length, the mapping is page aligned, and every bit
+        // pattern is a valid `SimdVec`.
+        let words = unsafe {
+            slice::from_raw_parts(
+                data.as_ptr() as *const SimdVec,
+                self.num_slabs * self.slab_len,
+            )
+        };
+        for (slab_idx, slab) in words.chunks_exact(self.slab_len).enumerate() {
+            f(slab_idx, slab);
+        }
+        Ok(())
+    }
+}
+
+pub struct ChunkedFileStorage {
+    path: PathBuf,
+    num_slabs: usize,
+    slab_len: usize,
+    slabs_per_chunk: usize,
+    read_ahead: usize,
+}
+
+impl ChunkedFileStorage {
+    ///
+    /// Reads are rounded to whole slabs of at least `chunk_bytes`, and at most `read_ahead`
+    /// chunks are buffered ahead of the scan.
+    ///
+    pub fn open(path: &Path, chunk_bytes: usize, read_ahead: usize) -> io::Result<Self> {
+        assert!(read_ahead > 0);
+        let (num_slabs, slab_len) = read_header(&mut File::open(path)?)?;
+        let slab_bytes = slab_len * size_of::<SimdVec>();
+        Ok(Self {
+            path: path.to_path_buf(),
+            num_slabs,
+            slab_len,
+            slabs_per_chunk: chunk_bytes.div_ceil(slab_bytes).max(1),
+            read_ahead,
+        })
+    }
+}
+
+impl DbStorage for ChunkedFileStorage {
+    fn num_slabs(&self) -> usize {
+        self.num_slabs
+    }
+
+    fn slab_len(&self) -> usize {
+        self.slab_len
+    }
+
+    fn scan(&self, f: &mut dyn FnMut(usize, &[SimdVec])) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(HEADER_BYTES as u64))?;
+
+        let (sender, receiver) = sync_channel::<io::Result<Vec<SimdVec>>>(self.read_ahead);
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut slabs_read = 0;
+                while slabs_read < self.num_slabs {
+                    let slabs = self.slabs_per_chunk.min(self.num_slabs - slabs_read);
+                    let mut chunk = vec![ZERO_WORD; slabs * self.slab_len];
+                    let result = file.read_exact(words_as_bytes_mut(&mut chunk));
+                    let failed = result.is_err();
+                    // The scan stops receiving after an error
+                    if sender.send(result.map(|_| chunk)).is_err() || failed {
+                        return;
+                    }
+                    slabs_read += slabs;
+                }
+            });
+
+            let mut slab_idx = 0;
+            for chunk in receiver {
+                for slab in chunk?.chunks_exact(self.slab_len) {
+                    f(slab_idx, slab);
+                    slab_idx += 1;
+                }
+            }
+            assert_eq!(slab_idx, self.num_slabs);
+            Ok(())
+        })
+    }
+}
+
+///
+/// Writes a database file in any order, one contiguous segment of a slab at a time. The file is
+/// allocated up front, and segments that are never written read as zero.
+///
+pub struct DbFileWriter {
+    file: File,
+    slab_len: usize,
+}
+
+impl DbFileWriter {
+    pub fn create(path: &Path, num_slabs: usize, slab_len: usize) -> io::Result<Self> {
+        assert!(slab_len > 0);
+        let mut file = File::create(path)?;
+        file.write_all(&header_bytes(num_slabs, slab_len))?;
+        file.set_len((HEADER_BYTES + num_slabs * slab_len * size_of::<SimdVec>()) as u64)?;
+        Ok(Self { file, slab_len })
+    }
+
+    /// Writes `words` to slab `slab_idx`, starting at word `offset` of the slab.
+    pub fn write_segment(
+        &mut self,
+        slab_idx: usize,
+        offset: usize,
+        words: &[SimdVec],
+    ) -> io::Result<()> {
+        assert!(offset + words.len() <= self.slab_len);
+        let word_idx = slab_idx * self.slab_len + offset;
+        self.file.seek(SeekFrom::Start(
+            (HEADER_BYTES + word_idx * size_of::<SimdVec>()) as u64,
+        ))?;
+        self.file.write_all(words_as_bytes(words))
+    }
+
+    /// Flushes the file to disk.
+    pub fn finish(self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+///
+/// Writes the slabs of `storage` to a database file readable by `MmapStorage` and
+/// `ChunkedFileStorage`.
+///
+pub fn write_db_file(path: &Path, storage: &dyn DbStorage) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&header_bytes(storage.num_slabs(), storage.slab_len()))?;
+    let mut result = Ok(());
+    storage.scan(&mut |_, slab| {
+        if result.is_ok() {
+            result = writer.write_all(words_as_bytes(slab));
+        }
+    })?;
+    result?;
+    writer.flush()
+}
+
+fn header_bytes(num_slabs: usize, slab_len: usize) -> [u8; HEADER_BYTES] {
+    let header = [MAGIC, SIMD_LANES as u64, slab_len as u64, num_slabs as u64];
+    let mut bytes = [0_u8; HEADER_BYTES];
+    for (chunk, word) in bytes.chunks_exact_mut(size_of::<u64>()).zip(header) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+/// Returns `(num_slabs, slab_len)` after checking the header against the file length.
+fn read_header(file: &mut File) -> io::Result<(usize, usize)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut bytes = [0_u8; HEADER_BYTES];
+    file.read_exact(&mut bytes)
+        .map_err(|_| invalid("database file is too short"))?;
+    let mut header = [0_u64; HEADER_LEN];
+    for (word, chunk) in header.iter_mut().zip(bytes.chunks_exact(size_of::<u64>())) {
+        *word = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    let [magic, lanes, slab_len, num_slabs] = header;
+    if magic != MAGIC {
+        return Err(invalid("not a database file"));
+    }
+    if lanes != SIMD_LANES as u64 {
+        return Err(invalid(
+            "database file was written with different SIMD lanes",
+        ));
+    }
+    if slab_len == 0 {
+        return Err(invalid("database file has empty slabs"));
+    }
+    let data_bytes = num_slabs
+        .checked_mul(slab_len)
+        .and_then(|words| words.checked_mul(size_of::<SimdVec>() as u64));
+    if data_bytes != Some(file.metadata()?.len() - HEADER_BYTES as u64) {
+        return Err(invalid("database file length does not match its header"));
+    }
+    Ok((num_slabs as usize, slab_len as usize))
+}
+
+fn words_as_bytes(words: &[SimdVec]) -> &[u8] {
+    // SAFETY: `SimdVec` is plain `u64`s without padding.
+    unsafe { slice::from_raw_parts(words.as_ptr() as *const u8, size_of_val(words)) }
+}
+
+fn words_as_bytes_mut(words: &mut [SimdVec]) -> &mut [u8] {
+    // SAFETY: `SimdVec` is plain `u64`s without padding, and any bytes are a valid `SimdVec`.
+    unsafe { slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, size_of_val(words)) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use std::fs;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("respire-{}-{}", std::process::id(), name))
+    }
+
+    fn collect(storage: &dyn DbStorage) -> Vec<u64> {
+        let mut result = Vec::new();
+        let mut next_slab = 0;
+        storage
+            .scan(&mut |slab_idx, slab| {
+                assert_eq!(slab_idx, next_slab);
+                assert_eq!(slab.len(), storage.slab_len());
+                next_slab += 1;
+                result.extend(
+                    words_as_bytes(slab)
+                        .chunks_exact(8)
+                        .map(|b| u64::from_ne_bytes(b.try_into().unwrap())),
+                );
+            })
+            .unwrap();
+        assert_eq!(next_slab, storage.num_slabs());
+        result
+    }
+
+    #[test]
+    fn test_storage_backends() {
+        let mut rng = ChaCha20Rng::from_seed([7; 32]);
+        let (num_slabs, slab_len) = (13, 100);
+        let mut words = vec![ZERO_WORD; num_slabs * slab_len];
+        rng.fill(words_as_bytes_mut(&mut words));
+        let in_memory = InMemoryStorage::new(words, slab_len);
+        let expected = collect(&in_memory);
+
+        let path = temp_path("test_storage_backends");
+        write_db_file(&path, &in_memory).unwrap();
+        let mmap = MmapStorage::open(&path).unwrap();
+        assert_eq!(collect(&mmap), expected);
+        // 3 slabs per chunk, with a partial last chunk
+        let slab_bytes = slab_len * size_of::<SimdVec>();
+        let chunked = ChunkedFileStorage::open(&path, 2 * slab_bytes + 1, 2).unwrap();
+        assert_eq!(collect(&chunked), expected);
+
+        // Segments written out of order give the same file
+        let segments_path = temp_path("test_storage_backends_segments");
+        let mut writer = DbFileWriter::create(&segments_path, num_slabs, slab_len).unwrap();
+        let words = in_memory.words();
+        for slab_idx in (0..num_slabs).rev() {
+            let slab = &words[slab_idx * slab_len..(slab_idx + 1) * slab_len];
+            writer.write_segment(slab_idx, 60, &slab[60..]).unwrap();
+            writer.write_segment(slab_idx, 0, &slab[..60]).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(fs::read(&segments_path).unwrap(), fs::read(&path).unwrap());
+        fs::remove_file(&segments_path).unwrap();
+
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len((HEADER_BYTES + slab_bytes) as u64).unwrap();
+        let err = MmapStorage::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::write(&path, b"not a database").unwrap();
+        assert!(ChunkedFileStorage::open(&path, 1, 1).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+//! Records authenticated by a Merkle commitment.
+//!
+//! The data owner builds a Merkle tree over the record payloads and publishes its root. Every
+//! stored record is the payload followed by its authentication path (sibling hashes, leaf level
+//! first), so a client can check a record it retrieved against the root without trusting the
+//! server. The tree is over logical record indices, so it works for any `PIR`, including the
+//! Cuckoo bucket layout, whose buckets store the records unchanged.
+//!
+//! Leaves hash their index along with the payload, so a server cannot answer with another valid
+//! record. Hashes are SHA-256 truncated to `HASH_LEN` bytes to keep paths within a record. To pass
+//! a wrong record, the server would need a second preimage of a hash in the path (about 2^128
+//! work). Truncation does not protect against a data owner who finds collisions before publishing
+//! the root.
+//!
+//! A path takes `HASH_LEN` bytes per level, so it leaves no payload in a 256 byte record beyond
+//! 2^15 records. Larger databases can authenticate records with a MAC instead (`encode_mac_db`),
+//! which takes `MAC_LEN` bytes regardless of the size. Only holders of the MAC key can verify, so
+//! the key must be shared between the data owner and the clients, and kept from the server.
+
+use crate::pir::pir::{PIRRecordBytes, Stats, PIR};
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::Duration;
+
+pub const HASH_LEN: usize = 16;
+pub type Hash = [u8; HASH_LEN];
+
+/// Length of the tag `mac_record` appends, HMAC-SHA256 truncated like the hashes.
+pub const MAC_LEN: usize = HASH_LEN;
+pub type MacKey = [u8; 32];
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerifyError {
+    IndexOutOfRange { idx: usize, num_records: usize },
+    RecordLength { expected: usize, actual: usize },
+    RecordCount { expected: usize, actual: usize },
+    RootMismatch { idx: usize },
+    MacMismatch { idx: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            VerifyError::IndexOutOfRange { idx, num_records } => {
+                write!(f, "index {} out of range for {} records", idx, num_records)
+            }
+            VerifyError::RecordLength { expected, actual } => {
+                write!(f, "expected a record of {} bytes, got {}", expected, actual)
+            }
+            VerifyError::RecordCount { expected, actual } => {
+                write!(f, "expected {} records, got {}", expected, actual)
+            }
+            VerifyError::RootMismatch { idx } => {
+                write!(f, "record {} does not match the Merkle root", idx)
+            }
+            VerifyError::MacMismatch { idx } => {
+                write!(f, "record {} does not match its MAC", idx)
+            }
+        }
+    }
+}
+
+pub struct MerkleTree {
+    // Leaves first, padded to a power of two; the last level is the root
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new<F: Fn(usize) -> Vec<u8>>(num_records: usize, payloads: F) -> Self {
+        assert!(num_records > 0);
+        let mut level = (0..num_records.next_power_of_two())
+            .map(|idx| {
+                if idx < num_records {
+                    hash_leaf(idx, &payloads(idx))
+                } else {
+                    [0_u8; HASH_LEN]
+                }
+            })
+            .collect_vec();
+        let mut levels = Vec::with_capacity(depth(num_records) + 1);
+        while level.len() > 1 {
+            let next = level
+                .chunks_exact(2)
+                .map(|pair| hash_node(&pair[0], &pair[1]))
+                .collect_vec();
+            levels.push(level);
+            level = next;
+        }
+        levels.push(level);
+        Self { levels }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// The stored form of record `idx`: its payload followed by its authentication path.
+    pub fn record(&self, idx: usize, payload: &[u8]) -> Vec<u8> {
+        let mut record = payload.to_vec();
+        let mut node_idx = idx;
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            record.extend_from_slice(&level[node_idx ^ 1]);
+            node_idx /= 2;
+        }
+        record
+    }
+}
+
+/// Depth of the tree over `num_records` records, i.e. the number of hashes in a path.
+pub fn depth(num_records: usize) -> usize {
+    num_records.next_power_of_two().trailing_zeros() as usize
+}
+
+/// The payload bytes left in a record of `record_len` bytes, if the path fits at all.
+pub fn payload_len(record_len: usize, num_records: usize) -> Option<usize> {
+    record_len.checked_sub(depth(num_records) * HASH_LEN)
+}
+
+///
+/// Checks a stored record (see `MerkleTree::record`) retrieved for index `idx` against `root`,
+/// and returns its payload.
+///
+pub fn verify_record<'a>(
+    root: &Hash,
+    num_records: usize,
+    idx: usize,
+    record: &'a [u8],
+) -> Result<&'a [u8], VerifyError> {
+    let path_len = depth(num_records) * HASH_LEN;
+    if idx >= num_records {
+        return Err(VerifyError::IndexOutOfRange { idx, num_records });
+    }
+    if record.len() < path_len {
+        return Err(VerifyError::RecordLength {
+            expected: path_len,
+            actual: record.len(),
+        });
+    }
+    let (payload, path) = record.split_at(record.len() - path_len);
+    let mut node = hash_leaf(idx, payload);
+    let mut node_idx = idx;
+    for sibling in path.chunks_exact(HASH_LEN) {
+        let sibling: &Hash = sibling.try_into().unwrap();
+        node = if node_idx.is_multiple_of(2) {
+            hash_node(&node, sibling)
+        } else {
+            hash_node(sibling, &node)
+        };
+        node_idx /= 2;
+    }
+    if node != *root {
+        return Err(VerifyError::RootMismatch { idx });
+    }
+    Ok(payload)
+}
+
+///
+/// Encodes a database of authenticated records, and returns it with the root to publish. Each
+/// payload must be `payload_len(BYTES_PER_RECORD, NUM_RECORDS)` bytes; `payloads` is called twice
+/// per record.
+///
+pub fn encode_authenticated_db<P: PIR, F: Fn(usize) -> Vec<u8>>(
+    payloads: F,
+    time_stats: Option<&mut Stats<Duration>>,
+) -> (P::Database, P::DatabaseHint, Hash) {
+    let len = payload_len(P::BYTES_PER_RECORD, P::NUM_RECORDS)
+        .expect("records are too short for authentication paths");
+    let checked_payloads = |idx: usize| {
+        let payload = payloads(idx);
+        assert_eq!(payload.len(), len);
+        payload
+    };
+    let tree = MerkleTree::new(P::NUM_RECORDS, checked_payloads);
+    let (db, hint) = P::encode_db(
+        |idx| P::RecordBytes::from_bytes(&tree.record(idx, &checked_payloads(idx))).unwrap(),
+        time_stats,
+    );
+    (db, hint, tree.root())
+}
+
+/// Verifies the records extracted for `indices`, and returns their payloads.
+pub fn verify_extracted<'a, P: PIR>(
+    root: &Hash,
+    indices: &[usize],
+    records: &'a [P::RecordBytes],
+) -> Result<Vec<&'a [u8]>, VerifyError> {
+    check_record_count(indices, records.len())?;
+    indices
+        .iter()
+        .zip(records.iter())
+        .map(|(&idx, record)| verify_record(root, P::NUM_RECORDS, idx, record.as_bytes()))
+        .collect()
+}
+
+/// The payload bytes left in a record of `record_len` bytes after its MAC.
+pub fn mac_payload_len(record_len: usize) -> Option<usize> {
+    record_len.checked_sub(MAC_LEN)
+}
+
+/// The stored form of record `idx` in a MAC authenticated database: its payload followed by a MAC
+/// of the index and the payload.
+pub fn mac_record(key: &MacKey, idx: usize, payload: &[u8]) -> Vec<u8> {
+    let mut record = payload.to_vec();
+    record.extend_from_slice(&record_mac(key, idx, payload).finalize().into_bytes()[..MAC_LEN]);
+    record
+}
+
+///
+/// Checks a stored record (see `mac_record`) retrieved for index `idx` against its MAC, and
+/// returns its payload.
+///
+pub fn verify_mac_record<'a>(
+    key: &MacKey,
+    num_records: usize,
+    idx: usize,
+    record: &'a [u8],
+) -> Result<&'a [u8], VerifyError> {
+    if idx >= num_records {
+        return Err(VerifyError::IndexOutOfRange { idx, num_records });
+    }
+    if record.len() < MAC_LEN {
+        return Err(VerifyError::RecordLength {
+            expected: MAC_LEN,
+            actual: record.len(),
+        });
+    }
+    let (payload, tag) = record.split_at(record.len() - MAC_LEN);
+    record_mac(key, idx, payload)
+        .verify_truncated_left(tag)
+        .map_err(|_| VerifyError::MacMismatch { idx })?;
+    Ok(payload)
+}
+
+///
+/// Same as `encode_authenticated_db`, with records authenticated by a MAC under `key`. Each
+/// payload must be `mac_payload_len(BYTES_PER_RECORD)` bytes.
+///
+pub fn encode_mac_db<P: PIR, F: Fn(usize) -> Vec<u8>>(
+    key: &MacKey,
+    payloads: F,
+    time_stats: Option<&mut Stats<Duration>>,
+) -> (P::Database, P::DatabaseHint) {
+    let len = mac_payload_len(P::BYTES_PER_RECORD).expect("records are too short for a MAC");
+    P::encode_db(
+        |idx| {
+            let payload = payloads(idx);
+            assert_eq!(payload.len(), len);
+            P::RecordBytes::from_bytes(&mac_record(key, idx, &payload)).unwrap()
+        },
+        time_stats,
+    )
+}
+
+/// Same as `verify_extracted`, for records of `encode_mac_db`.
+pub fn verify_extracted_mac<'a, P: PIR>(
+    key: &MacKey,
+    indices: &[usize],
+    records: &'a [P::RecordBytes],
+) -> Result<Vec<&'a [u8]>, VerifyError> {
+    check_record_count(indices, records.len())?;
+    indices
+        .iter()
+        .zip(records.iter())
+        .map(|(&idx, record)| verify_mac_record(key, P::NUM_RECORDS, idx, record.as_bytes()))
+        .collect()
+}
+
+fn check_record_count(indices: &[usize], num_records: usize) -> Result<(), VerifyError> {
+    if num_records != indices.len() {
+        return Err(VerifyError::RecordCount {
+            expected: indices.len(),
+            actual: num_records,
+        });
+    }
+    Ok(())
+}
+
+fn record_mac(key: &MacKey, idx: usize, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(&(idx as u64).to_le_bytes());
+    mac.update(payload);
+    mac
+}
+
+fn hash_leaf(idx: usize, payload: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_TAG]);
+    hasher.update((idx as u64).to_le_bytes());
+    hasher.update(payload);
+    truncate(hasher.finalize().as_slice())
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_TAG]);
+    hasher.update(left);
+    hasher.update(right);
+    truncate(hasher.finalize().as_slice())
+}
+
+fn truncate(digest: &[u8]) -> Hash {
+    digest[..HASH_LEN].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merkle_paths() {
+        let payload = |idx: usize| vec![idx as u8; 10];
+        for num_records in [1, 2, 5, 8] {
+            let tree = MerkleTree::new(num_records, payload);
+            for idx in 0..num_records {
+                let record = tree.record(idx, &payload(idx));
+                assert_eq!(record.len(), 10 + depth(num_records) * HASH_LEN);
+                assert_eq!(
+                    verify_record(&tree.root(), num_records, idx, &record),
+                    Ok(payload(idx).as_slice())
+                );
+            }
+        }
+
+        let tree = MerkleTree::new(5, payload);
+        let root = tree.root();
+        let mut record = tree.record(3, &payload(3));
+        assert_eq!(
+            verify_record(&root, 5, 2, &record),
+            Err(VerifyError::RootMismatch { idx: 2 })
+        );
+        for byte_idx in [0, 10, record.len() - 1] {
+            record[byte_idx] ^= 1;
+            assert!(verify_record(&root, 5, 3, &record).is_err());
+            record[byte_idx] ^= 1;
+        }
+        assert!(verify_record(&root, 5, 3, &record[..20]).is_err());
+        assert_eq!(payload_len(256, 400), Some(256 - 9 * HASH_LEN));
+        assert_eq!(payload_len(100, 1 << 12), None);
+    }
+
+    #[test]
+    fn test_record_macs() {
+        let key = [7_u8; 32];
+        let payload = vec![3_u8; 20];
+        let mut record = mac_record(&key, 5, &payload);
+        assert_eq!(record.len(), payload.len() + MAC_LEN);
+        assert_eq!(
+            verify_mac_record(&key, 8, 5, &record),
+            Ok(payload.as_slice())
+        );
+        assert_eq!(
+            verify_mac_record(&key, 8, 4, &record),
+            Err(VerifyError::MacMismatch { idx: 4 })
+        );
+        assert_eq!(
+            verify_mac_record(&[8_u8; 32], 8, 5, &record),
+            Err(VerifyError::MacMismatch { idx: 5 })
+        );
+        for byte_idx in [0, record.len() - 1] {
+            record[byte_idx] ^= 1;
+            assert!(verify_mac_record(&key, 8, 5, &record).is_err());
+            record[byte_idx] ^= 1;
+        }
+        assert!(verify_mac_record(&key, 8, 5, &record[..MAC_LEN - 1]).is_err());
+        assert_eq!(mac_payload_len(256), Some(256 - MAC_LEN));
+    }
+}
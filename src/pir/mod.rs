@@ -1,6 +1,14 @@
 //! PIR (Private Information Retrieval) constructs.
+pub mod conformance;
 pub mod cuckoo_respire;
+pub mod db_storage;
+pub mod merkle;
 pub mod noise;
+pub mod noise_profile;
+pub mod param_search;
 pub mod pir;
+pub mod pp_store;
 pub mod respire;
 pub mod respire_harness;
+pub mod security;
+pub mod shard;
@@ -1,3 +1,10 @@
+//! A small algebra for tracking noise through RLWE/GSW operations.
+//!
+//! Subgaussian noise is tracked by the square of its subgaussian width (`variance`), for a
+//! `rows x cols` matrix of ring elements of the given degree. Bounded noise (e.g. secrets and
+//! rounding errors) is tracked by a coefficient bound, and multiplying by it is treated
+//! heuristically as multiplying by a subgaussian of that width.
+
 use std::ops::{Add, Mul};
 
 #[derive(Clone, Copy, Debug)]
@@ -38,6 +45,57 @@ impl SubGaussianNoise {
     pub fn variance(self) -> f64 {
         self.variance
     }
+
+    pub fn zero(degree: u64) -> Self {
+        Self::new(0_f64, degree)
+    }
+
+    /// `log2` of the subgaussian width.
+    pub fn width_bits(self) -> f64 {
+        self.variance.log2() / 2_f64
+    }
+
+    ///
+    /// Noise of `k * g^-1(c)`, where `k` has `rows * gadget.len` columns, each with noise `self`,
+    /// and `g^-1(c)` is the (approximate) gadget decomposition of some `c`.
+    ///
+    pub fn gadget_product(self, gadget: Gadget, rows: usize) -> Self {
+        self * Independent(rows as f64 * self.degree as f64 * gadget.factor())
+    }
+
+    ///
+    /// Key switching (e.g. after an automorphism) with a key of noise `key`, for a secret bounded
+    /// by `secret`. Dropped gadget digits leave a rounding error that gets multiplied by the secret.
+    ///
+    pub fn key_switch(self, key: SubGaussianNoise, gadget: Gadget, secret: BoundedNoise) -> Self {
+        self + key.gadget_product(gadget, 1) + gadget.rounding(self.degree) * secret
+    }
+
+    ///
+    /// `depth` levels of automorphism based projection. The noise added at each level is doubled
+    /// (i.e. its variance quadrupled) by every subsequent level.
+    ///
+    pub fn project(
+        self,
+        key: SubGaussianNoise,
+        gadget: Gadget,
+        secret: BoundedNoise,
+        depth: usize,
+    ) -> Self {
+        let ct = (4usize.pow(depth as u32) - 1) / 3;
+        let new = Self::zero(self.degree).key_switch(key, gadget, secret);
+        self + new * Independent(ct as f64)
+    }
+
+    /// External product with a GSW encoding (of `2 * gadget.len` columns) with noise `gsw`.
+    pub fn external_product(self, gsw: SubGaussianNoise, gadget: Gadget) -> Self {
+        self + gsw.gadget_product(gadget, 2)
+    }
+
+    /// Modulus switching from `q_from` to `q_to`, excluding the rounding error.
+    pub fn modulus_switch(self, q_from: u64, q_to: u64) -> Self {
+        self * Scale(q_to as f64 / q_from as f64)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -67,6 +125,89 @@ impl BoundedNoise {
         copy.cols = cols;
         copy
     }
+
+    /// The error of rounding to the nearest integer.
+    pub fn rounding(degree: u64) -> Self {
+        Self::new(0.5_f64, degree)
+    }
+
+    pub fn bound(self) -> f64 {
+        self.bound
+    }
+
+    /// Modulus switching from `q_from` to `q_to`, excluding the rounding error.
+    pub fn modulus_switch(self, q_from: u64, q_to: u64) -> Self {
+        self * (q_to as f64 / q_from as f64)
+    }
+}
+
+/// A base `base` gadget decomposition keeping `len` digits, after dropping the lowest `drop`.
+#[derive(Clone, Copy, Debug)]
+pub struct Gadget {
+    pub len: usize,
+    pub base: u64,
+    pub drop: usize,
+}
+
+impl Gadget {
+    pub fn new(len: usize, base: u64) -> Self {
+        Self::approx(len, base, 0)
+    }
+
+    pub fn approx(len: usize, base: u64, drop: usize) -> Self {
+        assert!(base >= 2);
+        Self { len, base, drop }
+    }
+
+    /// Square subgaussian width of a decomposed coefficient, summed over the digits.
+    pub fn factor(self) -> f64 {
+        let z_factor = match self.base {
+            2 => {
+                // With probability <= 2^(-48.421), a random binary gadget will have <= 1200 equal 1
+                // N(log(1 - sum(binomial(2048, x) * (1/2)^2048, x, 0, 1200), 2)) = -48.4216972197488
+                const BINARY_FACTOR: f64 = 1200_f64 / 2048_f64;
+                BINARY_FACTOR
+            }
+            // 3 => {
+            //     // With probability <= 2^(-48.506), a random ternary gadget will have <= 1528 coefficients equal +- 1
+            //     // N(log(1 - sum(binomial(2048, x) * (2/3)^x * (1/3)^(2048-x), x, 0, 1528), 2)) = -48.5067228501629
+            //     const TERNARY_FACTOR: f64 = 1528_f64 / 2048_f64;
+            //     TERNARY_FACTOR
+            // }
+            z => {
+                // TODO noise: verify this factor is right
+                // const CHERNOFF_FACTOR: f64 = 0.6_f64;
+                const CHERNOFF_FACTOR: f64 = 1.0_f64;
+                ((z / 2) as f64).powi(2) * CHERNOFF_FACTOR
+            }
+        };
+        (self.len as f64) * z_factor
+    }
+
+    /// The error left by the dropped digits; each coefficient is off by at most `base^drop / 2`.
+    pub fn rounding(self, degree: u64) -> SubGaussianNoise {
+        let bound = if self.drop == 0 {
+            0_f64
+        } else {
+            (self.base as f64).powi(self.drop as i32) / 2_f64
+        };
+        SubGaussianNoise::new(bound.powi(2), degree)
+    }
+}
+
+impl Add<BoundedNoise> for BoundedNoise {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        assert_eq!(self.rows, rhs.rows);
+        assert_eq!(self.cols, rhs.cols);
+        assert_eq!(self.degree, rhs.degree);
+        Self {
+            bound: self.bound + rhs.bound,
+            degree: self.degree,
+            rows: self.rows,
+            cols: self.cols,
+        }
+    }
 }
 
 impl Mul<f64> for BoundedNoise {
@@ -147,3 +288,51 @@ impl Mul<SubGaussianNoise> for BoundedNoise {
         } * Independent(self.cols as f64)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noise_algebra() {
+        let degree = 2048_u64;
+        let error = SubGaussianNoise::new(100_f64, degree);
+        let secret = BoundedNoise::new(7_f64, degree);
+        let gadget = Gadget::new(4, 1 << 14);
+        let factor = 4_f64 * ((1_u64 << 13) as f64).powi(2);
+        assert_eq!(gadget.factor(), factor);
+        assert_eq!(
+            error.gadget_product(gadget, 2).variance(),
+            2_f64 * 2048_f64 * factor * 100_f64
+        );
+
+        // Without dropped digits, key switching only adds the gadget product
+        let switched = error.key_switch(error, gadget, secret);
+        assert_eq!(
+            switched.variance(),
+            100_f64 + error.gadget_product(gadget, 1).variance()
+        );
+        let dropped = error.key_switch(error, Gadget::approx(3, 1 << 14, 1), secret);
+        assert_eq!(
+            dropped.variance()
+                - error
+                    .key_switch(error, Gadget::new(3, 1 << 14), secret)
+                    .variance(),
+            ((1_u64 << 13) as f64).powi(2) * 2048_f64 * 49_f64
+        );
+
+        // Two levels of projection: the first level's noise is doubled by the second
+        let projected = SubGaussianNoise::zero(degree).project(error, gadget, secret, 2);
+        assert_eq!(
+            projected.variance(),
+            5_f64 * switched.variance() - 5_f64 * 100_f64
+        );
+
+        assert_eq!(
+            error.modulus_switch(1 << 20, 1 << 10).variance(),
+            100_f64 / (1 << 20) as f64
+        );
+        let rounding = BoundedNoise::rounding(degree) + BoundedNoise::rounding(degree);
+        assert_eq!(rounding.bound(), 1_f64);
+    }
+}
@@ -0,0 +1,280 @@
+//! Empirical noise profiling for Respire.
+//!
+//! `RespireImpl::noise_profile` runs query/answer cycles on a small database, measuring the noise
+//! after each stage with the query key. The report built here compares those measurements with
+//! [`RespireParamsExpanded::noise_breakdown`], and fits a subgaussian tail to the noise
+//! coefficients before compression, so that stages where the analysis is loose (wastes margin) or
+//! optimistic (underestimates the noise) stand out.
+
+use crate::pir::respire::{NoiseBreakdown, RespireParamsExpanded};
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Slack (bits) above which an estimate is reported as loose.
+pub const LOOSE_SLACK_BITS: f64 = 2_f64;
+
+/// Fewest samples at or above a threshold for its empirical tail probability to be used.
+const MIN_TAIL_SAMPLES: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NoiseStage {
+    QueryExpandRlwe,
+    FirstDim,
+    Fold,
+    Rotate,
+    /// Note that the projection noise is lower on the coefficients that are projected away, so
+    /// the measured average slightly underestimates the noise on the remaining ones.
+    Project,
+    /// Scalar to vector packing, i.e. the noise right before compression.
+    VectorPack,
+}
+
+impl NoiseStage {
+    pub const ALL: [NoiseStage; 6] = [
+        NoiseStage::QueryExpandRlwe,
+        NoiseStage::FirstDim,
+        NoiseStage::Fold,
+        NoiseStage::Rotate,
+        NoiseStage::Project,
+        NoiseStage::VectorPack,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseStage::QueryExpandRlwe => "query expanded rlwe",
+            NoiseStage::FirstDim => "first dim",
+            NoiseStage::Fold => "fold",
+            NoiseStage::Rotate => "rotate",
+            NoiseStage::Project => "project*",
+            NoiseStage::VectorPack => "pre compression",
+        }
+    }
+
+    /// The analytic estimate for this stage, as `log2` of the subgaussian width.
+    pub fn estimate_bits(&self, breakdown: &NoiseBreakdown) -> f64 {
+        let noise = match self {
+            NoiseStage::QueryExpandRlwe => breakdown.query_expand_rlwe,
+            NoiseStage::FirstDim => breakdown.first_dim,
+            NoiseStage::Fold => breakdown.fold,
+            NoiseStage::Rotate => breakdown.rotate,
+            NoiseStage::Project => breakdown.project,
+            NoiseStage::VectorPack => breakdown.vector_pack,
+        };
+        noise.width_bits()
+    }
+}
+
+/// Measured widths (`log2`) of one stage over all trials, against its analytic estimate.
+#[derive(Clone, Copy, Debug)]
+pub struct StageProfile {
+    pub stage: NoiseStage,
+    pub estimate_bits: f64,
+    pub mean_bits: f64,
+    pub max_bits: f64,
+    pub samples: usize,
+}
+
+impl StageProfile {
+    /// How far the estimate is above the worst measurement; negative if it is below.
+    pub fn slack_bits(&self) -> f64 {
+        self.estimate_bits - self.max_bits
+    }
+
+    pub fn is_optimistic(&self) -> bool {
+        self.slack_bits() < 0_f64
+    }
+
+    pub fn is_loose(&self) -> bool {
+        self.slack_bits() > LOOSE_SLACK_BITS
+    }
+
+    fn verdict(&self) -> &'static str {
+        if self.is_optimistic() {
+            "OPTIMISTIC"
+        } else if self.is_loose() {
+            "loose"
+        } else {
+            "ok"
+        }
+    }
+}
+
+///
+/// A subgaussian tail `Pr[|e| >= t] <= 2 exp(-pi t^2 / s^2)` fitted to the noise coefficients before
+/// compression: `s` is the smallest width consistent with every empirical tail probability that is
+/// backed by enough samples.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct TailFit {
+    pub width_bits: f64,
+    pub estimate_bits: f64,
+    pub max_abs: u64,
+    pub samples: usize,
+    /// `log2` of the fitted probability that a coefficient exceeds `Q1 / (2P)`, i.e. decodes wrong
+    /// before compression.
+    pub error_rate_log2: f64,
+}
+
+impl TailFit {
+    pub fn fit(coefficients: &[u64], estimate_bits: f64, threshold: f64) -> Self {
+        let width = fit_subgaussian_width(coefficients);
+        let error_rate_log2 = (1_f64 - PI * (threshold / width).powi(2) / 2_f64.ln()).min(0_f64);
+        TailFit {
+            width_bits: width.log2(),
+            estimate_bits,
+            max_abs: coefficients.iter().copied().max().unwrap_or(0),
+            samples: coefficients.len(),
+            error_rate_log2,
+        }
+    }
+
+    pub fn is_optimistic(&self) -> bool {
+        self.width_bits > self.estimate_bits
+    }
+}
+
+/// The smallest subgaussian width `s` with `Pr[|e| >= t] <= 2 exp(-pi t^2 / s^2)` at every sample.
+pub fn fit_subgaussian_width(coefficients: &[u64]) -> f64 {
+    let mut sorted = coefficients.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let n = sorted.len() as f64;
+    let mut width = 0_f64;
+    for (i, &t) in sorted.iter().enumerate().skip(MIN_TAIL_SAMPLES - 1) {
+        // `i + 1` samples are at least `t`; only the last of a run of ties counts
+        if sorted.get(i + 1) == Some(&t) {
+            continue;
+        }
+        let tail = (i + 1) as f64 / n;
+        width = width.max(t as f64 * (PI / (2_f64 / tail).ln()).sqrt());
+    }
+    width
+}
+
+#[derive(Clone, Debug)]
+pub struct NoiseProfile {
+    pub params: RespireParamsExpanded,
+    pub answers: usize,
+    pub failures: usize,
+    pub stages: Vec<StageProfile>,
+    pub tail: TailFit,
+}
+
+impl NoiseProfile {
+    ///
+    /// Builds the report from the measured `(stage, log2 width)` pairs and the absolute noise
+    /// coefficients before compression.
+    ///
+    pub fn new(
+        params: RespireParamsExpanded,
+        answers: usize,
+        failures: usize,
+        measured: &[(NoiseStage, f64)],
+        coefficients: &[u64],
+    ) -> Self {
+        let breakdown = params.noise_breakdown();
+        let stages = NoiseStage::ALL
+            .iter()
+            .filter_map(|&stage| {
+                let bits = measured
+                    .iter()
+                    .filter(|(s, _)| *s == stage)
+                    .map(|(_, bits)| *bits)
+                    .collect::<Vec<_>>();
+                if bits.is_empty() {
+                    return None;
+                }
+                Some(StageProfile {
+                    stage,
+                    estimate_bits: stage.estimate_bits(&breakdown),
+                    mean_bits: bits.iter().sum::<f64>() / bits.len() as f64,
+                    max_bits: bits.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    samples: bits.len(),
+                })
+            })
+            .collect();
+        let threshold = params.Q1 as f64 / (2 * params.P) as f64;
+        let tail = TailFit::fit(
+            coefficients,
+            NoiseStage::VectorPack.estimate_bits(&breakdown),
+            threshold,
+        );
+        NoiseProfile {
+            params,
+            answers,
+            failures,
+            stages,
+            tail,
+        }
+    }
+
+    pub fn optimistic_stages(&self) -> impl Iterator<Item = &StageProfile> {
+        self.stages.iter().filter(|s| s.is_optimistic())
+    }
+}
+
+impl fmt::Display for NoiseProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Noise profile over {} answer(s), {} decoding failure(s)",
+            self.answers, self.failures
+        )?;
+        writeln!(
+            f,
+            "{:<22} {:>9} {:>9} {:>9} {:>9}  verdict",
+            "stage (log2 width)", "estimate", "mean", "max", "slack"
+        )?;
+        for s in self.stages.iter() {
+            writeln!(
+                f,
+                "{:<22} {:>9.3} {:>9.3} {:>9.3} {:>9.3}  {}",
+                s.stage.name(),
+                s.estimate_bits,
+                s.mean_bits,
+                s.max_bits,
+                s.slack_bits(),
+                s.verdict()
+            )?;
+        }
+        write!(
+            f,
+            "tail fit over {} coefficient(s): width 2^({:.3}) vs estimate 2^({:.3}){}, max |e| = {}, \
+             error rate before compression 2^({:.1})",
+            self.tail.samples,
+            self.tail.width_bits,
+            self.tail.estimate_bits,
+            if self.tail.is_optimistic() {
+                " OPTIMISTIC"
+            } else {
+                ""
+            },
+            self.tail.max_abs,
+            self.tail.error_rate_log2,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use rand_distr::{Distribution, Normal};
+
+    #[test]
+    fn test_fit_subgaussian_width() {
+        // A Gaussian of width s satisfies the bound with width s, and the bound is not tight for
+        // it, so the fit lands somewhat below s
+        let width = 1000_f64;
+        let normal = Normal::new(0_f64, width / (2_f64 * PI).sqrt()).unwrap();
+        let mut rng = ChaCha20Rng::from_seed([1; 32]);
+        let samples = (0..100_000)
+            .map(|_| normal.sample(&mut rng).abs().round() as u64)
+            .collect::<Vec<_>>();
+        let fitted = fit_subgaussian_width(&samples);
+        assert!((0.8 * width..1.05 * width).contains(&fitted), "{fitted}");
+
+        // Too few samples to say anything
+        assert_eq!(fit_subgaussian_width(&[5; MIN_TAIL_SAMPLES - 1]), 0_f64);
+    }
+}
@@ -0,0 +1,466 @@
+//! Search for Respire parameters that fit a target database.
+//!
+//! Candidates are enumerated over the structural parameters (`NU1`, `NU2`, `N_VEC`, `D2`, `Q2`,
+//! `Q3`, `T_GSW`), scored with the analytic error estimate and the size functions of
+//! [`RespireParamsExpanded`], and reduced to the Pareto frontier of query size, response size and
+//! rate. The first dimension ring (`D1`, `Q1A`, `Q1B`) and its error distributions are fixed.
+//! First dimensions wider than `D1` take several RLWE query ciphertexts, which the query size and
+//! noise estimate account for.
+
+use crate::math::utils::{ceil_log, floor_log};
+use crate::pir::respire::RespireParams;
+use crate::pir::respire_harness::FactoryParams;
+use crate::pir::security::{LWEInstance, SecurityEstimate};
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub struct SearchTarget {
+    pub num_records: usize,
+    pub record_bytes: usize,
+    pub batch_size: usize,
+    /// Largest acceptable `log2` of the estimated per-record failure probability.
+    pub max_error_rate_log2: f64,
+    /// Smallest acceptable estimated security (bits) of every RLWE instance.
+    pub min_security_bits: f64,
+}
+
+///
+/// The values tried for each parameter. `T_PROJ_SHORT`, `T_PROJ_LONG` and `T_RLWE_TO_GSW` only
+/// affect the public parameter size and the noise, so for each structural candidate the smallest
+/// public parameters that meet the error target are kept.
+///
+#[derive(Clone, Debug)]
+pub struct SearchSpace {
+    pub p: Vec<u64>,
+    pub d2: Vec<usize>,
+    pub q2: Vec<u64>,
+    /// `Q3` is taken to be each of these multiples of `P`.
+    pub q3_over_p: Vec<u64>,
+    pub n_vec_max: usize,
+    pub t_gsw: Vec<usize>,
+    pub t_proj_short: Vec<usize>,
+    pub t_proj_long: Vec<usize>,
+    pub t_rlwe_to_gsw: Vec<usize>,
+    /// Candidate widths (millionths) for the compression error and secret, smallest first. The
+    /// smallest one meeting the security floor is used.
+    pub width_compress_millionths: Vec<u64>,
+}
+
+impl Default for SearchSpace {
+    fn default() -> Self {
+        SearchSpace {
+            p: vec![2, 4, 16],
+            d2: vec![512, 1024, 2048],
+            // NTT friendly primes for D1 = 2048, from 14 to 32 bits
+            q2: vec![
+                12289, 61441, 114689, 249857, 520193, 1032193, 2056193, 4169729, 8380417, 16760833,
+                33550337, 67104769, 134176769, 268369921, 536813569, 1073692673, 2147389441,
+                4294955009,
+            ],
+            q3_over_p: vec![2, 4, 6, 8, 12, 16, 24, 32],
+            n_vec_max: 8,
+            t_gsw: vec![6, 8, 10],
+            t_proj_short: vec![3, 4, 5, 6],
+            t_proj_long: vec![12, 16, 20, 24],
+            t_rlwe_to_gsw: vec![3, 4, 5, 6],
+            width_compress_millionths: (0..12).map(|i| 1_000_000 << i).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub params: RespireParams,
+    pub query_size: usize,
+    pub response_size: usize,
+    pub public_param_size: usize,
+    pub rate: f64,
+    pub error_rate_log2: f64,
+}
+
+impl Candidate {
+    /// Whether `self` is at least as good as `other` in every objective and better in one.
+    pub fn dominates(&self, other: &Candidate) -> bool {
+        let no_worse = self.query_size <= other.query_size
+            && self.response_size <= other.response_size
+            && self.rate >= other.rate;
+        let better = self.query_size < other.query_size
+            || self.response_size < other.response_size
+            || self.rate > other.rate;
+        no_worse && better
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "// query {:.3} KiB, response {:.3} KiB, rate {:.4}, public params {:.3} KiB, error 2^({:.1})",
+            self.query_size as f64 / 1024_f64,
+            self.response_size as f64 / 1024_f64,
+            self.rate,
+            self.public_param_size as f64 / 1024_f64,
+            self.error_rate_log2,
+        )?;
+        // An expression that can be passed to `respire!` as is
+        let p = &self.params;
+        let fields = [
+            ("Q1A", p.Q1A),
+            ("Q1B", p.Q1B),
+            ("D1", p.D1 as u64),
+            ("T_GSW", p.T_GSW as u64),
+            ("T_PROJ_SHORT", p.T_PROJ_SHORT as u64),
+            ("DROP_PROJ_SHORT", p.DROP_PROJ_SHORT as u64),
+            ("T_PROJ_LONG", p.T_PROJ_LONG as u64),
+            ("DROP_PROJ_LONG", p.DROP_PROJ_LONG as u64),
+            ("T_RLWE_TO_GSW", p.T_RLWE_TO_GSW as u64),
+            ("DROP_RLWE_TO_GSW", p.DROP_RLWE_TO_GSW as u64),
+            ("T_VECTORIZE", p.T_VECTORIZE as u64),
+            ("BATCH_SIZE", p.BATCH_SIZE as u64),
+            ("N_VEC", p.N_VEC as u64),
+            ("ERROR_WIDTH_MILLIONTHS", p.ERROR_WIDTH_MILLIONTHS),
+            ("ERROR_WIDTH_VEC_MILLIONTHS", p.ERROR_WIDTH_VEC_MILLIONTHS),
+            (
+                "ERROR_WIDTH_COMPRESS_MILLIONTHS",
+                p.ERROR_WIDTH_COMPRESS_MILLIONTHS,
+            ),
+            ("SECRET_BOUND", p.SECRET_BOUND),
+            ("SECRET_WIDTH_VEC_MILLIONTHS", p.SECRET_WIDTH_VEC_MILLIONTHS),
+            (
+                "SECRET_WIDTH_COMPRESS_MILLIONTHS",
+                p.SECRET_WIDTH_COMPRESS_MILLIONTHS,
+            ),
+            ("P", p.P),
+            ("D3", p.D3 as u64),
+            ("NU1", p.NU1 as u64),
+            ("NU2", p.NU2 as u64),
+            ("Q3", p.Q3),
+            ("Q2", p.Q2),
+            ("D2", p.D2 as u64),
+        ];
+        writeln!(f, "RespireParams {{")?;
+        for (name, value) in fields {
+            writeln!(f, "    {}: {},", name, value)?;
+        }
+        write!(f, "}}\n.expand()")
+    }
+}
+
+/// The first dimension ring, error distributions and gadgets shared by every factory preset.
+/// The structural parameters of the preset are overwritten by each candidate.
+const fn base_params() -> RespireParams {
+    FactoryParams::single_record_256(0, 0).expand()
+}
+
+///
+/// Enumerates the candidates in `space` that store `target`, meet its error and security
+/// requirements, and returns the Pareto frontier, sorted by query size.
+///
+pub fn search(target: &SearchTarget, space: &SearchSpace) -> Vec<Candidate> {
+    pareto_frontier(candidates(target, space))
+}
+
+/// All candidates in `space` that store `target` and meet its error and security requirements.
+pub fn candidates(target: &SearchTarget, space: &SearchSpace) -> Vec<Candidate> {
+    let base = base_params();
+    let width = |millionths: u64| millionths as f64 / 1_000_000_f64;
+
+    // The first dimension ring has a uniform secret in [-SECRET_BOUND, SECRET_BOUND)
+    let q1_lwe = LWEInstance {
+        stddev_s: base.SECRET_BOUND as f64 / 3_f64.sqrt(),
+        ..LWEInstance::gaussian(
+            base.D1,
+            (base.Q1A as f64) * (base.Q1B as f64),
+            width(base.ERROR_WIDTH_MILLIONTHS),
+            0_f64,
+        )
+    };
+    if SecurityEstimate::estimate(&q1_lwe).bits() < target.min_security_bits {
+        return Vec::new();
+    }
+
+    // Smallest compression width per (D2, Q2) that meets the security floor
+    let width_compress = |d2: usize, q2: u64| {
+        space.width_compress_millionths.iter().copied().find(|&w| {
+            let lwe = LWEInstance::gaussian(d2, q2 as f64, width(w), width(w));
+            SecurityEstimate::estimate(&lwe).bits() >= target.min_security_bits
+        })
+    };
+
+    let mut t_aux = Vec::new();
+    for &t_proj_short in space.t_proj_short.iter() {
+        for &t_proj_long in space.t_proj_long.iter() {
+            for &t_rlwe_to_gsw in space.t_rlwe_to_gsw.iter() {
+                t_aux.push((t_proj_short, t_proj_long, t_rlwe_to_gsw));
+            }
+        }
+    }
+    let log_d1 = floor_log(2, base.D1 as u64);
+    t_aux.sort_by_key(|(short, long, rlwe_to_gsw)| log_d1 * (short + long) + 2 * rlwe_to_gsw);
+
+    let mut feasible = Vec::new();
+    for &d2 in space.d2.iter().filter(|&&d2| d2 <= base.D1) {
+        for &q2 in space.q2.iter() {
+            let Some(width) = width_compress(d2, q2) else {
+                continue;
+            };
+            for &p in space.p.iter() {
+                let log_p = floor_log(2, p);
+                if !(target.record_bytes * 8).is_multiple_of(log_p) {
+                    continue;
+                }
+                let d3 = target.record_bytes * 8 / log_p;
+                if !d3.is_power_of_two() || d3 > d2 {
+                    continue;
+                }
+                let pack_ratio_db = base.D1 / d3;
+                let nu_total = ceil_log(2, target.num_records.div_ceil(pack_ratio_db) as u64);
+                let num_ring_elems = target.batch_size.div_ceil(d2 / d3);
+
+                for &q3_over_p in space.q3_over_p.iter() {
+                    let q3 = q3_over_p * p;
+                    if q3 >= q2 {
+                        continue;
+                    }
+                    for nu1 in 1..=nu_total {
+                        for &t_gsw in space.t_gsw.iter() {
+                            for n_vec in 1..=space.n_vec_max.min(num_ring_elems) {
+                                let structural = RespireParams {
+                                    T_GSW: t_gsw,
+                                    BATCH_SIZE: target.batch_size,
+                                    N_VEC: n_vec,
+                                    ERROR_WIDTH_COMPRESS_MILLIONTHS: width,
+                                    SECRET_WIDTH_COMPRESS_MILLIONTHS: width,
+                                    P: p,
+                                    D3: d3,
+                                    NU1: nu1,
+                                    NU2: nu_total - nu1,
+                                    Q3: q3,
+                                    Q2: q2,
+                                    D2: d2,
+                                    ..base
+                                };
+                                if let Some(c) = best_aux(structural, &t_aux, target) {
+                                    feasible.push(c);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    feasible
+}
+
+/// Picks the first (i.e. smallest public params) auxiliary gadget lengths that meet the target.
+fn best_aux(
+    structural: RespireParams,
+    t_aux: &[(usize, usize, usize)],
+    target: &SearchTarget,
+) -> Option<Candidate> {
+    if structural.validate().is_err() {
+        return None;
+    }
+
+    t_aux
+        .iter()
+        .find_map(|&(t_proj_short, t_proj_long, t_rlwe_to_gsw)| {
+            let params = RespireParams {
+                T_PROJ_SHORT: t_proj_short,
+                T_PROJ_LONG: t_proj_long,
+                T_RLWE_TO_GSW: t_rlwe_to_gsw,
+                ..structural
+            };
+            let expanded = params.expand();
+            let error_rate_log2 = expanded.error_rate_estimate().log2();
+            if error_rate_log2 > target.max_error_rate_log2 {
+                return None;
+            }
+            Some(Candidate {
+                params,
+                query_size: expanded.query_size(),
+                response_size: expanded.response_info().0,
+                public_param_size: expanded.public_param_size(),
+                rate: expanded.rate(),
+                error_rate_log2,
+            })
+        })
+}
+
+/// Keeps the candidates not dominated by any other; among equal objectives the first one wins.
+pub fn pareto_frontier(candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let mut frontier: Vec<Candidate> = Vec::new();
+    for c in candidates {
+        let covered = frontier.iter().any(|f| {
+            f.dominates(&c)
+                || (f.query_size == c.query_size
+                    && f.response_size == c.response_size
+                    && f.rate == c.rate)
+        });
+        if !covered {
+            frontier.retain(|f| !c.dominates(f));
+            frontier.push(c);
+        }
+    }
+    frontier.sort_by_key(|c| (c.query_size, c.response_size));
+    frontier
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire::Respire;
+
+    #[test]
+    fn test_base_matches_presets() {
+        let base = base_params();
+        let batch = FactoryParams::batch_256(8, 2, 3, 3).expand();
+        assert_eq!(
+            (base.Q1A, base.Q1B, base.D1),
+            (batch.Q1A, batch.Q1B, batch.D1)
+        );
+        assert_eq!(base.ERROR_WIDTH_MILLIONTHS, batch.ERROR_WIDTH_MILLIONTHS);
+        assert_eq!(base.SECRET_BOUND, batch.SECRET_BOUND);
+        assert_eq!(base.T_GSW, batch.T_GSW);
+        assert_eq!(base.T_VECTORIZE, batch.T_VECTORIZE);
+    }
+
+    #[test]
+    fn test_candidate_display() {
+        const PRINTED: &str = "RespireParams {
+    Q1A: 268369921,
+    Q1B: 249561089,
+    D1: 2048,
+    T_GSW: 8,
+    T_PROJ_SHORT: 4,
+    DROP_PROJ_SHORT: 0,
+    T_PROJ_LONG: 20,
+    DROP_PROJ_LONG: 0,
+    T_RLWE_TO_GSW: 4,
+    DROP_RLWE_TO_GSW: 0,
+    T_VECTORIZE: 2,
+    BATCH_SIZE: 1,
+    N_VEC: 1,
+    ERROR_WIDTH_MILLIONTHS: 9900000,
+    ERROR_WIDTH_VEC_MILLIONTHS: 9900000,
+    ERROR_WIDTH_COMPRESS_MILLIONTHS: 253600000,
+    SECRET_BOUND: 7,
+    SECRET_WIDTH_VEC_MILLIONTHS: 9900000,
+    SECRET_WIDTH_COMPRESS_MILLIONTHS: 253600000,
+    P: 16,
+    D3: 512,
+    NU1: 3,
+    NU2: 3,
+    Q3: 256,
+    Q2: 16760833,
+    D2: 512,
+}
+.expand()";
+        // The same text, pasted as code
+        type Pasted = crate::respire!(RespireParams {
+            Q1A: 268369921,
+            Q1B: 249561089,
+            D1: 2048,
+            T_GSW: 8,
+            T_PROJ_SHORT: 4,
+            DROP_PROJ_SHORT: 0,
+            T_PROJ_LONG: 20,
+            DROP_PROJ_LONG: 0,
+            T_RLWE_TO_GSW: 4,
+            DROP_RLWE_TO_GSW: 0,
+            T_VECTORIZE: 2,
+            BATCH_SIZE: 1,
+            N_VEC: 1,
+            ERROR_WIDTH_MILLIONTHS: 9900000,
+            ERROR_WIDTH_VEC_MILLIONTHS: 9900000,
+            ERROR_WIDTH_COMPRESS_MILLIONTHS: 253600000,
+            SECRET_BOUND: 7,
+            SECRET_WIDTH_VEC_MILLIONTHS: 9900000,
+            SECRET_WIDTH_COMPRESS_MILLIONTHS: 253600000,
+            P: 16,
+            D3: 512,
+            NU1: 3,
+            NU2: 3,
+            Q3: 256,
+            Q2: 16760833,
+            D2: 512,
+        }
+        .expand());
+
+        let params = FactoryParams::single_record_256(3, 3).expand();
+        let expanded = params.expand();
+        let candidate = Candidate {
+            params,
+            query_size: expanded.query_size(),
+            response_size: expanded.response_info().0,
+            public_param_size: expanded.public_param_size(),
+            rate: expanded.rate(),
+            error_rate_log2: expanded.error_rate_estimate().log2(),
+        };
+        let printed = candidate.to_string();
+        assert!(printed.starts_with("// query "));
+        assert!(printed.ends_with(PRINTED), "{printed}");
+        assert_eq!(format!("{:?}", Pasted::params()), format!("{:?}", expanded));
+    }
+
+    #[test]
+    fn test_search_frontier() {
+        let target = SearchTarget {
+            num_records: 1 << 16,
+            record_bytes: 256,
+            batch_size: 1,
+            max_error_rate_log2: -40_f64,
+            min_security_bits: 128_f64,
+        };
+        let space = SearchSpace {
+            p: vec![16],
+            d2: vec![512],
+            q2: vec![16760833],
+            t_gsw: vec![8],
+            ..SearchSpace::default()
+        };
+        let frontier = search(&target, &space);
+        assert!(!frontier.is_empty());
+        for a in frontier.iter() {
+            let expanded = a.params.expand();
+            assert!(expanded.db_size() >= target.num_records);
+            assert_eq!(expanded.BYTES_PER_RECORD, target.record_bytes);
+            assert!(a.error_rate_log2 <= target.max_error_rate_log2);
+            assert!(frontier.iter().all(|b| !b.dominates(a)));
+        }
+    }
+
+    #[test]
+    fn test_search_wide_first_dim() {
+        let target = SearchTarget {
+            num_records: 1 << 20,
+            record_bytes: 256,
+            batch_size: 1,
+            max_error_rate_log2: -40_f64,
+            min_security_bits: 128_f64,
+        };
+        let space = SearchSpace {
+            p: vec![16],
+            d2: vec![512],
+            q2: vec![16760833],
+            t_gsw: vec![8],
+            ..SearchSpace::default()
+        };
+        let candidates = candidates(&target, &space);
+        let wide = candidates
+            .iter()
+            .filter(|c| c.params.expand().rlwe_count() > c.params.D1)
+            .collect::<Vec<_>>();
+        assert!(!wide.is_empty());
+        for c in wide {
+            let expanded = c.params.expand();
+            assert!(expanded.db_size() >= target.num_records);
+            assert_eq!(c.query_size, expanded.query_size());
+            // Two or more RLWE seeds, and a Q1 coefficient of at least 7 bytes per row
+            assert!(c.query_size > 2 * 32 + expanded.rlwe_count() * 7);
+            assert!(c.error_rate_log2 <= target.max_error_rate_log2);
+        }
+    }
+}
@@ -1,4 +1,9 @@
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fmt;
+use std::io;
 use std::ops::AddAssign;
 use std::time::Duration;
 
@@ -7,6 +12,27 @@ pub trait PIRRecordBytes: Clone + Default {
     fn as_bytes(&self) -> &[u8];
 }
 
+/// Length of the checksum `record_with_checksum` appends.
+pub const RECORD_CHECKSUM_LEN: usize = 8;
+
+///
+/// Appends a truncated SHA-256 of `payload`, so a client can detect a record that decrypted
+/// wrongly. The payload must be `RECORD_CHECKSUM_LEN` bytes shorter than a record.
+///
+pub fn record_with_checksum(payload: &[u8]) -> Vec<u8> {
+    let mut record = payload.to_vec();
+    record.extend_from_slice(&Sha256::digest(payload)[..RECORD_CHECKSUM_LEN]);
+    record
+}
+
+/// Whether `record` ends in the checksum of the rest, as written by `record_with_checksum`.
+pub fn record_checksum_ok(record: &[u8]) -> bool {
+    match record.len().checked_sub(RECORD_CHECKSUM_LEN) {
+        Some(len) => Sha256::digest(&record[..len])[..RECORD_CHECKSUM_LEN] == record[len..],
+        None => false,
+    }
+}
+
 pub struct Stats<T: AddAssign<T> + Copy + Default> {
     order: Vec<&'static str>,
     stats: HashMap<&'static str, T>,
@@ -38,6 +64,42 @@ impl<T: AddAssign<T> + Copy + Default> Stats<T> {
         }
         result
     }
+
+    pub fn map<U: AddAssign<U> + Copy + Default, F: Fn(T) -> U>(&self, f: F) -> Stats<U> {
+        let mut result = Stats::new();
+        for (name, value) in self.as_vec() {
+            result.add(name, f(value));
+        }
+        result
+    }
+}
+
+/// Serialized as a map, in insertion order.
+impl<T: AddAssign<T> + Copy + Default + Serialize> Serialize for Stats<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.order.len()))?;
+        for (name, value) in self.as_vec() {
+            map.serialize_entry(name, &value)?;
+        }
+        map.end()
+    }
+}
+
+/// The parameters and estimated costs of a PIR scheme, for machine readable reports.
+#[derive(Clone, Debug, Serialize)]
+pub struct PIRSummary<Params> {
+    pub scheme: &'static str,
+    pub bytes_per_record: usize,
+    pub num_records: usize,
+    pub batch_size: usize,
+    pub params: Params,
+    pub public_param_size: usize,
+    pub query_size: usize,
+    pub response_size: usize,
+    pub record_size: usize,
+    pub rate: f64,
+    pub error_rate_log2: f64,
+    pub security_bits: f64,
 }
 
 pub trait PIR {
@@ -49,6 +111,10 @@ pub trait PIR {
     type Database;
     type DatabaseHint;
     type State;
+    /// The scheme specific parameters reported by `summary`.
+    type Params: Serialize;
+    /// Why `try_answer` rejects a query.
+    type QueryError: fmt::Debug + fmt::Display;
 
     // A single raw record
     type RecordBytes: PIRRecordBytes;
@@ -57,6 +123,7 @@ pub trait PIR {
     const BATCH_SIZE: usize;
 
     fn print_summary();
+    fn summary() -> PIRSummary<Self::Params>;
 
     fn encode_db<F: Fn(usize) -> Self::RecordBytes>(
         records_generator: F,
@@ -76,10 +143,39 @@ pub trait PIR {
         qk: Option<&Self::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> Self::Response;
+    /// Same as `answer`, but returns an error for a malformed query instead of panicking.
+    /// Schemes that do not check queries answer every query.
+    fn try_answer(
+        pp: &Self::PublicParams,
+        db: &Self::Database,
+        q: &Self::Query,
+        qk: Option<&Self::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<Self::Response, Self::QueryError> {
+        Ok(Self::answer(pp, db, q, qk, time_stats))
+    }
     fn extract(
         qk: &Self::QueryKey,
         r: &Self::Response,
         st: &Self::State,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> Vec<Self::RecordBytes>;
+
+    /// Serializes `q` as sent to the server.
+    fn encode_query(q: &Self::Query) -> Vec<u8>;
+    /// Inverse of `encode_query`. Fails on malformed input.
+    fn decode_query(bytes: &[u8]) -> io::Result<Self::Query>;
+    /// Serializes `r` as sent to the client.
+    fn encode_response(r: &Self::Response) -> Vec<u8>;
+    /// Inverse of `encode_response`. Fails on malformed input.
+    fn decode_response(bytes: &[u8]) -> io::Result<Self::Response>;
+
+    /// Size in bytes of `q` as sent to the server; `summary().query_size` for a full batch.
+    fn query_size(q: &Self::Query) -> usize {
+        Self::encode_query(q).len()
+    }
+    /// Size in bytes of `r` as sent to the client; `summary().response_size` for a full batch.
+    fn response_size(r: &Self::Response) -> usize {
+        Self::encode_response(r).len()
+    }
 }
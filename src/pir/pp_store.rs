@@ -0,0 +1,370 @@
+//! A server-side store of the public parameters of many clients.
+//!
+//! Public parameters are large (see `RespireParamsExpanded::public_param_size`) and uploaded once
+//! per client, so a server answering many clients keeps them in a `PublicParamsStore` keyed by a
+//! client (or session) id. Resident parameters are kept within a memory budget, measured in the
+//! bytes they take in memory (`Respire::public_params_memory_size`), about twice their serialized
+//! size. When inserting would exceed the budget, the least recently used parameters are evicted:
+//! spilled to a file in the spill directory if there is one, and dropped otherwise, in which case
+//! the client has to upload them again.
+//!
+//! The store keeps a SHA-256 digest of every client's serialized parameters, and checks spilled
+//! parameters against it when loading them back. `PublicParams` are reference counted, so `get`
+//! returns a handle that `answer` takes directly, and evicting parameters does not free them while
+//! an answer is still using them.
+//!
+//! The store is internally synchronized: every method takes `&self`, so threads answering
+//! different clients can share one store (e.g. in an `Arc`). Loading spilled parameters and
+//! spilling evicted ones happen under the lock.
+
+use crate::pir::pir::PIR;
+use crate::pir::respire::Respire;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+pub type ClientId = u64;
+
+/// Public parameters held by the store, as taken by `R::answer(&handle, ...)`.
+pub type PublicParamsHandle<R> = <R as PIR>::PublicParams;
+
+struct Entry<R: Respire> {
+    // `None` if evicted to the spill directory
+    params: Option<PublicParamsHandle<R>>,
+    digest: [u8; 32],
+    encoded_len: usize,
+    memory_size: usize,
+    last_used: u64,
+    spilled: bool,
+}
+
+struct State<R: Respire> {
+    entries: HashMap<ClientId, Entry<R>>,
+    // Last use of each resident entry, oldest first
+    lru: BTreeMap<u64, ClientId>,
+    tick: u64,
+    memory_used: usize,
+}
+
+pub struct PublicParamsStore<R: Respire> {
+    memory_budget: usize,
+    spill_dir: Option<PathBuf>,
+    state: Mutex<State<R>>,
+}
+
+impl<R: Respire> PublicParamsStore<R> {
+    ///
+    /// Creates an empty store holding at most `memory_budget` bytes of resident parameters.
+    /// Evicted parameters are spilled to files in `spill_dir` if given; the store removes the
+    /// files it wrote when it is dropped.
+    ///
+    pub fn new(memory_budget: usize, spill_dir: Option<PathBuf>) -> Self {
+        Self {
+            memory_budget,
+            spill_dir,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                memory_used: 0,
+            }),
+        }
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.lock().memory_used
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    pub fn contains(&self, client: ClientId) -> bool {
+        self.lock().entries.contains_key(&client)
+    }
+
+    pub fn is_resident(&self, client: ClientId) -> bool {
+        self.lock()
+            .entries
+            .get(&client)
+            .is_some_and(|entry| entry.params.is_some())
+    }
+
+    /// Inserts (or replaces) the parameters of `client`, evicting others if needed.
+    pub fn insert(&self, client: ClientId, params: <R as PIR>::PublicParams) -> io::Result<()> {
+        let bytes = R::encode_public_params(&params);
+        self.insert_encoded(client, params, &bytes)
+    }
+
+    /// Same as `insert`, for parameters in serialized form, e.g. as uploaded by the client.
+    pub fn insert_bytes(&self, client: ClientId, bytes: &[u8]) -> io::Result<()> {
+        let params = R::decode_public_params(bytes)?;
+        self.insert_encoded(client, params, bytes)
+    }
+
+    ///
+    /// Returns the parameters of `client`, loading them from the spill directory if they were
+    /// evicted there, or `None` if the store does not have them.
+    ///
+    pub fn get(&self, client: ClientId) -> io::Result<Option<PublicParamsHandle<R>>> {
+        let mut state = self.lock();
+        let Some(entry) = state.entries.get(&client) else {
+            return Ok(None);
+        };
+        if let Some(params) = entry.params.clone() {
+            state.touch(client);
+            return Ok(Some(params));
+        }
+
+        let (digest, encoded_len) = (entry.digest, entry.encoded_len);
+        let bytes = fs::read(self.spill_path(client))?;
+        if bytes.len() != encoded_len || <[u8; 32]>::from(Sha256::digest(&bytes)) != digest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("spilled public params of client {} are corrupted", client),
+            ));
+        }
+        let params = R::decode_public_params(&bytes)?;
+        let memory_size = state.entries[&client].memory_size;
+        self.make_room(&mut state, memory_size)?;
+        let entry = state.entries.get_mut(&client).unwrap();
+        entry.params = Some(params.clone());
+        state.memory_used += memory_size;
+        state.touch(client);
+        Ok(Some(params))
+    }
+
+    /// Removes the parameters of `client`, including any spilled copy.
+    pub fn remove(&self, client: ClientId) -> io::Result<()> {
+        self.remove_locked(&mut self.lock(), client)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<R>> {
+        // The state is consistent between statements that can panic, so a poisoned lock is fine
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn remove_locked(&self, state: &mut State<R>, client: ClientId) -> io::Result<()> {
+        if let Some(entry) = state.entries.remove(&client) {
+            if entry.params.is_some() {
+                state.lru.remove(&entry.last_used);
+                state.memory_used -= entry.memory_size;
+            }
+            if entry.spilled {
+                fs::remove_file(self.spill_path(client))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_encoded(
+        &self,
+        client: ClientId,
+        params: PublicParamsHandle<R>,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let memory_size = R::public_params_memory_size(&params);
+        if memory_size > self.memory_budget {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "public params exceed the memory budget",
+            ));
+        }
+        let mut state = self.lock();
+        self.remove_locked(&mut state, client)?;
+        self.make_room(&mut state, memory_size)?;
+        state.tick += 1;
+        let tick = state.tick;
+        state.lru.insert(tick, client);
+        state.entries.insert(
+            client,
+            Entry {
+                params: Some(params),
+                digest: Sha256::digest(bytes).into(),
+                encoded_len: bytes.len(),
+                memory_size,
+                last_used: tick,
+                spilled: false,
+            },
+        );
+        state.memory_used += memory_size;
+        Ok(())
+    }
+
+    /// Evicts least recently used parameters until `size` more bytes fit in the budget.
+    fn make_room(&self, state: &mut State<R>, size: usize) -> io::Result<()> {
+        while state.memory_used + size > self.memory_budget {
+            let (_, &client) = state.lru.first_key_value().unwrap();
+            let entry = state.entries.get_mut(&client).unwrap();
+            // Parameters never change once inserted, so an earlier spill is still valid. The entry
+            // is only evicted once the spill is written, so a failed write leaves it resident.
+            if self.spill_dir.is_some() && !entry.spilled {
+                let path = self.spill_path(client);
+                let bytes = R::encode_public_params(entry.params.as_ref().unwrap());
+                if let Err(e) = fs::write(&path, bytes) {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                entry.spilled = true;
+            }
+            entry.params = None;
+            state.memory_used -= entry.memory_size;
+            state.lru.pop_first();
+            if self.spill_dir.is_none() {
+                state.entries.remove(&client);
+            }
+        }
+        Ok(())
+    }
+
+    fn spill_path(&self, client: ClientId) -> PathBuf {
+        spill_path(self.spill_dir.as_ref().unwrap(), client)
+    }
+}
+
+impl<R: Respire> State<R> {
+    fn touch(&mut self, client: ClientId) {
+        let entry = self.entries.get_mut(&client).unwrap();
+        self.lru.remove(&entry.last_used);
+        self.tick += 1;
+        entry.last_used = self.tick;
+        self.lru.insert(self.tick, client);
+    }
+}
+
+impl<R: Respire> Drop for PublicParamsStore<R> {
+    fn drop(&mut self) {
+        let Some(spill_dir) = self.spill_dir.as_ref() else {
+            return;
+        };
+        let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (&client, entry) in state.entries.iter() {
+            if entry.spilled {
+                let _ = fs::remove_file(spill_path(spill_dir, client));
+            }
+        }
+    }
+}
+
+fn spill_path(spill_dir: &Path, client: ClientId) -> PathBuf {
+    spill_dir.join(format!("{:016x}.pp", client))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pir::respire_harness::{filled_record, RespireSmall as Small};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_public_params_store() {
+        let spill_dir = std::env::temp_dir().join(format!(
+            "respire-{}-test_public_params_store",
+            std::process::id()
+        ));
+        fs::create_dir_all(&spill_dir).unwrap();
+
+        let clients = (0..3).map(|_| Small::setup(None)).collect::<Vec<_>>();
+        let encoded = clients
+            .iter()
+            .map(|(_, pp)| Small::encode_public_params(pp))
+            .collect::<Vec<_>>();
+        let encoded_len = encoded[0].len();
+        let decoded = Small::decode_public_params(&encoded[0]).unwrap();
+        assert_eq!(Small::encode_public_params(&decoded), encoded[0]);
+        assert!(Small::decode_public_params(&encoded[0][..encoded_len - 1]).is_err());
+
+        // Keys for an automorphism other than the one `setup` uses at a level are rejected
+        let mut tampered = (*clients[0].1).clone();
+        tampered.0 .0[1].1 += 2;
+        let bytes = Small::encode_public_params(&Arc::new(tampered));
+        assert!(Small::decode_public_params(&bytes).is_err());
+        let mut tampered = (*clients[0].1).clone();
+        tampered.0 .1[0].1 += 2;
+        let bytes = Small::encode_public_params(&Arc::new(tampered));
+        assert!(Small::decode_public_params(&bytes).is_err());
+
+        // The budget counts the in-memory size, which is larger than the serialized one
+        let size = Small::public_params_memory_size(&clients[0].1);
+        assert!(size > encoded_len);
+
+        // Room for two clients: using client 0 makes client 1 the one evicted
+        let store = PublicParamsStore::<Small>::new(2 * size, Some(spill_dir.clone()));
+        store.insert(0, clients[0].1.clone()).unwrap();
+        store.insert_bytes(1, &encoded[1]).unwrap();
+        store.get(0).unwrap().unwrap();
+        store.insert(2, clients[2].1.clone()).unwrap();
+        assert!(store.is_resident(0) && !store.is_resident(1) && store.is_resident(2));
+        assert_eq!(store.memory_used(), 2 * size);
+
+        // Client 1 comes back from disk, evicting client 0
+        let records_generator = filled_record::<Small>;
+        let (db, _) = Small::encode_db(records_generator, None);
+        let (q, st) = Small::query(&clients[1].0, &[42], &(), None);
+        let pp = store.get(1).unwrap().unwrap();
+        assert_eq!(Small::encode_public_params(&pp), encoded[1]);
+        let r = Small::answer(&pp, &db, &q, None, None);
+        assert_eq!(
+            Small::extract(&clients[1].0, &r, &st, None),
+            [records_generator(42)]
+        );
+        assert!(!store.is_resident(0) && store.is_resident(1));
+
+        // Threads share the store, and answer with the handles it hands out
+        std::thread::scope(|s| {
+            for client in [1, 2] {
+                let (store, db, clients) = (&store, &db, &clients);
+                s.spawn(move || {
+                    let (q, st) = Small::query(&clients[client].0, &[7], &(), None);
+                    let pp = store.get(client as ClientId).unwrap().unwrap();
+                    let r = Small::answer(&pp, db, &q, None, None);
+                    assert_eq!(
+                        Small::extract(&clients[client].0, &r, &st, None),
+                        [records_generator(7)]
+                    );
+                });
+            }
+        });
+        assert_eq!(store.memory_used(), 2 * size);
+
+        // A corrupted spill is detected
+        let path = spill_dir.join(format!("{:016x}.pp", 0));
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[encoded_len / 2] ^= 1;
+        fs::write(&path, bytes).unwrap();
+        let err = store.get(0).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        store.remove(0).unwrap();
+        assert!(!path.exists());
+        assert_eq!(store.len(), 2);
+        drop(store);
+
+        // Without a spill directory, evicted parameters are gone
+        let store = PublicParamsStore::<Small>::new(size, None);
+        store.insert(0, clients[0].1.clone()).unwrap();
+        store.insert(1, clients[1].1.clone()).unwrap();
+        assert!(store.get(0).unwrap().is_none());
+        assert!(store.get(1).unwrap().is_some());
+
+        // A spill that cannot be written leaves the evicted parameters resident
+        let missing_dir = spill_dir.join("missing");
+        let store = PublicParamsStore::<Small>::new(size, Some(missing_dir));
+        store.insert(0, clients[0].1.clone()).unwrap();
+        assert!(store.insert(1, clients[1].1.clone()).is_err());
+        assert!(store.is_resident(0) && !store.contains(1));
+        assert_eq!(store.memory_used(), size);
+        let pp = store.get(0).unwrap().unwrap();
+        assert_eq!(Small::encode_public_params(&pp), encoded[0]);
+        drop(store);
+
+        fs::remove_dir_all(&spill_dir).unwrap();
+    }
+}
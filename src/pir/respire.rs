@@ -4,27 +4,45 @@ use log::Level::Info;
 use log::{info, log_enabled};
 use std::cmp::{max, min};
 use std::f64::consts::PI;
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::iter;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::path::Path;
+use std::slice;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rand_distr::num_traits::clamp;
+use serde::Serialize;
 
 use crate::math::gadget::{
-    base_from_len, build_gadget, gadget_inverse, gadget_inverse_scalar, RingElementDecomposable,
+    base_from_len, build_gadget, build_gadget_approx, gadget_inverse, gadget_inverse_approx,
+    gadget_inverse_into, gadget_inverse_scalar, RingElementDecomposable,
 };
 use crate::math::int_mod::IntMod;
 use crate::math::int_mod_cyclo::IntModCyclo;
 use crate::math::int_mod_cyclo_crt_eval::IntModCycloCRTEval;
 use crate::math::int_mod_cyclo_eval::IntModCycloEval;
-use crate::math::matrix::Matrix;
+use crate::math::matrix::{Matrix, PreparedMatrixMul};
+use crate::math::number_theory::is_prime;
 
 use crate::math::rand_sampled::{RandDiscreteGaussianSampled, RandUniformSampled};
-use crate::math::ring_elem::{NormedRingElement, RingElement};
+use crate::math::ring_elem::{NormedRingElement, RingElement, RingElementRef};
 use crate::math::utils::{ceil_log, floor_log, mod_inverse, reverse_bits_fast};
 
 use crate::math::simd_utils::*;
-use crate::pir::pir::{PIRRecordBytes, Stats, PIR};
+use crate::pir::db_storage::{DbFileWriter, DbStorage, InMemoryStorage};
+use crate::pir::noise::{BoundedNoise, Gadget, Independent, SubGaussianNoise};
+use crate::pir::noise_profile::{NoiseProfile, NoiseStage};
+use crate::pir::pir::{record_checksum_ok, PIRRecordBytes, PIRSummary, Stats, PIR};
+use crate::pir::security;
+use crate::pir::security::{width_to_stddev, LWEInstance, SecurityEstimate};
+use crate::pir::shard::ShardRespire;
 
 pub struct RespireImpl<
     const Q1: u64,
@@ -36,10 +54,13 @@ pub struct RespireImpl<
     const M_GSW: usize,
     const Z_PROJ_SHORT: u64,
     const T_PROJ_SHORT: usize,
+    const DROP_PROJ_SHORT: usize,
     const Z_PROJ_LONG: u64,
     const T_PROJ_LONG: usize,
+    const DROP_PROJ_LONG: usize,
     const Z_RLWE_TO_GSW: u64,
     const T_RLWE_TO_GSW: usize,
+    const DROP_RLWE_TO_GSW: usize,
     const M_RLWE_TO_GSW: usize,
     const Z_VECTORIZE: u64,
     const T_VECTORIZE: usize,
@@ -61,17 +82,95 @@ pub struct RespireImpl<
     const T_COMPRESS: usize,
     const Z_COMPRESS: u64,
     const BYTES_PER_RECORD: usize,
-> {}
+    RingQ1Fast = IntModCycloCRTEval<D1, Q1A, Q1B>,
+> {
+    phantom: PhantomData<RingQ1Fast>,
+}
+
+///
+/// A representation of `Z_Q[x]/(x^D + 1)`, for `Q = QA * QB` a product of two NTT-friendly primes
+/// below `2^32`, that `RespireImpl` can compute in modulo `Q1`. Elements are stored as their
+/// evaluations modulo `QA` and modulo `QB`, which the database encoding and the first dimension
+/// read and write directly, packing both residues into one `u64`. `Q1` is therefore limited to
+/// two such primes; the multi-prime `IntModCycloRNSEval` does not implement this trait.
+///
+pub trait RingQ1Eval<const D: usize, const Q: u64, const QA: u64, const QB: u64>:
+    RingElement + RandUniformSampled + for<'a> From<&'a IntModCyclo<D, Q>> + Send + Sync + 'static
+where
+    for<'a> &'a Self: RingElementRef<Self>,
+{
+    /// The evaluations modulo `QA` and `QB`, each in `[0, QA)` and `[0, QB)` respectively.
+    fn residues(&self) -> (&[u64; D], &[u64; D]);
+
+    /// Mutable access to the evaluations. Values written must be reduced.
+    fn residues_mut(&mut self) -> (&mut [u64; D], &mut [u64; D]);
+
+    /// Converts to coefficient form; the inverse of `From<&IntModCyclo<D, Q>>`.
+    fn to_coeff(&self) -> IntModCyclo<D, Q>;
+
+    fn auto(&self, k: usize) -> Self;
+
+    fn mul_x_pow(&self, k: usize) -> Self;
+
+    fn mul_x_pow_into(&self, k: usize, out: &mut Self);
+}
+
+impl<const D: usize, const Q: u64, const QA: u64, const QB: u64> RingQ1Eval<D, Q, QA, QB>
+    for IntModCycloCRTEval<D, QA, QB>
+{
+    fn residues(&self) -> (&[u64; D], &[u64; D]) {
+        // Safety: `IntMod` is `repr(transparent)` over a reduced `u64`
+        unsafe {
+            (
+                &*(&self.proj1.evals as *const [IntMod<QA>; D] as *const [u64; D]),
+                &*(&self.proj2.evals as *const [IntMod<QB>; D] as *const [u64; D]),
+            )
+        }
+    }
+
+    fn residues_mut(&mut self) -> (&mut [u64; D], &mut [u64; D]) {
+        // Safety: as above; callers keep the values reduced
+        unsafe {
+            (
+                &mut *(&mut self.proj1.evals as *mut [IntMod<QA>; D] as *mut [u64; D]),
+                &mut *(&mut self.proj2.evals as *mut [IntMod<QB>; D] as *mut [u64; D]),
+            )
+        }
+    }
+
+    fn to_coeff(&self) -> IntModCyclo<D, Q> {
+        self.into()
+    }
+
+    fn auto(&self, k: usize) -> Self {
+        self.auto(k)
+    }
+
+    fn mul_x_pow(&self, k: usize) -> Self {
+        self.mul_x_pow(k)
+    }
+
+    fn mul_x_pow_into(&self, k: usize, out: &mut Self) {
+        self.mul_x_pow_into(k, out)
+    }
+}
 
 #[allow(non_snake_case)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct RespireParams {
     pub Q1A: u64,
     pub Q1B: u64,
     pub D1: usize,
     pub T_GSW: usize,
     pub T_PROJ_SHORT: usize,
+    /// Number of low digits dropped from the `T_PROJ_SHORT`-digit decomposition (0 for exact).
+    pub DROP_PROJ_SHORT: usize,
     pub T_PROJ_LONG: usize,
+    /// Number of low digits dropped from the `T_PROJ_LONG`-digit decomposition (0 for exact).
+    pub DROP_PROJ_LONG: usize,
     pub T_RLWE_TO_GSW: usize,
+    /// Number of low digits dropped from the `T_RLWE_TO_GSW`-digit decomposition (0 for exact).
+    pub DROP_RLWE_TO_GSW: usize,
     pub T_VECTORIZE: usize,
     pub BATCH_SIZE: usize,
     pub N_VEC: usize,
@@ -90,8 +189,316 @@ pub struct RespireParams {
     pub D2: usize,
 }
 
+/// A violated constraint on the Respire parameters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParamsError {
+    DimensionNotPowerOfTwo,
+    D2DoesNotDivideD1,
+    D3DoesNotDivideD2,
+    Q1ATooLarge,
+    Q1BTooLarge,
+    Q1ANotNttPrime,
+    Q1BNotNttPrime,
+    Q2NotNttPrime,
+    PNotPowerOfTwo,
+    PDoesNotDivideQ3,
+    DropNotBelowLength,
+    GswCountTooLarge,
+    BatchSizeZero,
+    NVecZero,
+}
+
+impl ParamsError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            ParamsError::DimensionNotPowerOfTwo => "D1, D2 and D3 must be powers of two",
+            ParamsError::D2DoesNotDivideD1 => "D2 must divide D1",
+            ParamsError::D3DoesNotDivideD2 => "D3 must divide D2",
+            ParamsError::Q1ATooLarge => "Q1A must fit in a u32",
+            ParamsError::Q1BTooLarge => "Q1B must fit in a u32",
+            ParamsError::Q1ANotNttPrime => "Q1A must be a prime congruent to 1 mod 2 * D1",
+            ParamsError::Q1BNotNttPrime => "Q1B must be a prime congruent to 1 mod 2 * D1",
+            ParamsError::Q2NotNttPrime => "Q2 must be a prime congruent to 1 mod 2 * D1",
+            ParamsError::PNotPowerOfTwo => "P must be a power of two",
+            ParamsError::PDoesNotDivideQ3 => "P must divide Q3",
+            ParamsError::DropNotBelowLength => {
+                "each DROP_* must be smaller than the corresponding T_*"
+            }
+            ParamsError::GswCountTooLarge => "(NU2 + log2(D1 / D3)) * T_GSW must be at most D1",
+            ParamsError::BatchSizeZero => "BATCH_SIZE must be positive",
+            ParamsError::NVecZero => "N_VEC must be positive",
+        }
+    }
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+const MAX_PARAMS_ERRORS: usize = 15;
+
+/// The list of constraints violated by a parameter set, usable in `const` contexts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParamsErrors {
+    errors: [Option<ParamsError>; MAX_PARAMS_ERRORS],
+    len: usize,
+}
+
+impl ParamsErrors {
+    pub const fn new() -> Self {
+        Self {
+            errors: [None; MAX_PARAMS_ERRORS],
+            len: 0,
+        }
+    }
+
+    pub const fn push(&mut self, error: ParamsError) {
+        self.errors[self.len] = Some(error);
+        self.len += 1;
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn get(&self, i: usize) -> Option<ParamsError> {
+        if i < self.len {
+            self.errors[i]
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ParamsError> + '_ {
+        self.errors[..self.len].iter().flatten().copied()
+    }
+
+    pub const fn into_result(self) -> Result<(), ParamsErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl Default for ParamsErrors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for ParamsErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.iter().map(|e| e.message()).join("; "))
+    }
+}
+
+/// An invalid set of records to sum, see `Respire::query_sum_one`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AggregateError {
+    Empty,
+    IndexOutOfRange {
+        index: usize,
+    },
+    /// `index` is not in `column`, the column of the first index.
+    ColumnMismatch {
+        index: usize,
+        column: usize,
+    },
+    DuplicateIndex {
+        index: usize,
+    },
+}
+
+impl fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AggregateError::Empty => write!(f, "no records to sum"),
+            AggregateError::IndexOutOfRange { index } => {
+                write!(f, "record {} is out of range", index)
+            }
+            AggregateError::ColumnMismatch { index, column } => {
+                write!(f, "record {} is not in column {}", index, column)
+            }
+            AggregateError::DuplicateIndex { index } => {
+                write!(f, "record {} is selected twice", index)
+            }
+        }
+    }
+}
+
+/// A query whose shape does not match the parameters, e.g. one sent by a misbehaving client.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QueryError {
+    BatchSize { expected: usize, actual: usize },
+    RlweCiphertexts { expected: usize, actual: usize },
+    RlweCount { expected: usize, actual: usize },
+    GswCount { expected: usize, actual: usize },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (what, expected, actual) = match *self {
+            QueryError::BatchSize { expected, actual } => ("queries", expected, actual),
+            QueryError::RlweCiphertexts { expected, actual } => {
+                ("first dimension ciphertexts", expected, actual)
+            }
+            QueryError::RlweCount { expected, actual } => {
+                ("first dimension coefficients", expected, actual)
+            }
+            QueryError::GswCount { expected, actual } => ("GSW coefficients", expected, actual),
+        };
+        write!(f, "expected {} {}, got {}", expected, what, actual)
+    }
+}
+
+///
+/// How `extract_one_checked` flags records as likely corrupted. The default threshold does not
+/// depend on the parameters; `for_params` derives one from their noise estimate instead.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExtractConfig {
+    /// Records with a coefficient margin below this are flagged
+    pub margin_threshold: f64,
+    /// Whether records end in a checksum (see `record_with_checksum`)
+    pub checksum: bool,
+}
+
+impl Default for ExtractConfig {
+    fn default() -> Self {
+        Self {
+            margin_threshold: 0.25,
+            checksum: false,
+        }
+    }
+}
+
+impl ExtractConfig {
+    /// Log2 of the probability that a coefficient of a correct response gets flagged.
+    pub const FALSE_POSITIVE_LOG2: f64 = -40_f64;
+
+    ///
+    /// Flags a record once one of its coefficients has a margin that the estimated noise of
+    /// `params` reaches with probability at most `2^FALSE_POSITIVE_LOG2`. Parameters whose noise
+    /// can reach a rounding boundary at that probability get a threshold of 0, which flags
+    /// nothing.
+    ///
+    pub fn for_params(params: &RespireParamsExpanded) -> Self {
+        let breakdown = params.noise_breakdown();
+        // Same tail bound as the error rate: Pr[|e| > t] <= 2 exp(-pi t^2 / variance)
+        let tail = (breakdown.switch_total.variance() * -Self::FALSE_POSITIVE_LOG2 * 2_f64.ln()
+            / PI)
+            .sqrt();
+        let noise_bound = breakdown.switch_rounding.bound() + tail;
+        let ratio = (params.Q3 / params.P) as f64;
+        Self {
+            margin_threshold: clamp(1_f64 - 2_f64 * noise_bound / ratio, 0_f64, 1_f64),
+            checksum: false,
+        }
+    }
+}
+
+///
+/// How close the coefficients of an extracted record came to a rounding boundary. A margin is 1
+/// for a coefficient without noise, and 0 for one at a boundary, which noise could have pushed
+/// either way. Correct parameters keep margins close to 1, so a small margin points to a
+/// decryption failure or a tampered response.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordConfidence {
+    pub min_margin: f64,
+    pub mean_margin: f64,
+    /// `None` unless `ExtractConfig::checksum` is set
+    pub checksum_ok: Option<bool>,
+    pub likely_corrupted: bool,
+}
+
+const fn is_ntt_prime(q: u64, d: usize) -> bool {
+    is_prime(q) && (q - 1).is_multiple_of(2 * d as u64)
+}
+
 impl RespireParams {
+    /// Like `expand`, but refuses parameter sets whose estimated security is below
+    /// `min_security_bits`.
+    pub fn expand_secure(&self, min_security_bits: f64) -> Result<RespireParamsExpanded, String> {
+        let expanded = self.expand();
+        expanded.check_security(min_security_bits)?;
+        Ok(expanded)
+    }
+
+    ///
+    /// Checks the constraints the protocol relies on. This is a `const fn`, and `expand` panics
+    /// on the first violation, so an invalid `respire!(...)` parameter set fails to compile.
+    ///
+    pub const fn validate(&self) -> Result<(), ParamsErrors> {
+        let mut errors = ParamsErrors::new();
+        let dims_pow2 =
+            self.D1.is_power_of_two() && self.D2.is_power_of_two() && self.D3.is_power_of_two();
+        if !dims_pow2 {
+            errors.push(ParamsError::DimensionNotPowerOfTwo);
+        }
+        if !self.D1.is_multiple_of(self.D2) {
+            errors.push(ParamsError::D2DoesNotDivideD1);
+        }
+        if !self.D2.is_multiple_of(self.D3) {
+            errors.push(ParamsError::D3DoesNotDivideD2);
+        }
+        if self.Q1A > u32::MAX as u64 {
+            errors.push(ParamsError::Q1ATooLarge);
+        }
+        if self.Q1B > u32::MAX as u64 {
+            errors.push(ParamsError::Q1BTooLarge);
+        }
+        if !is_ntt_prime(self.Q1A, self.D1) {
+            errors.push(ParamsError::Q1ANotNttPrime);
+        }
+        if !is_ntt_prime(self.Q1B, self.D1) {
+            errors.push(ParamsError::Q1BNotNttPrime);
+        }
+        if !is_ntt_prime(self.Q2, self.D1) {
+            errors.push(ParamsError::Q2NotNttPrime);
+        }
+        if !self.P.is_power_of_two() || self.P < 2 {
+            errors.push(ParamsError::PNotPowerOfTwo);
+        }
+        if !self.Q3.is_multiple_of(self.P) {
+            errors.push(ParamsError::PDoesNotDivideQ3);
+        }
+        if self.DROP_PROJ_SHORT >= self.T_PROJ_SHORT
+            || self.DROP_PROJ_LONG >= self.T_PROJ_LONG
+            || self.DROP_RLWE_TO_GSW >= self.T_RLWE_TO_GSW
+        {
+            errors.push(ParamsError::DropNotBelowLength);
+        }
+        if dims_pow2 && self.D3 <= self.D1 {
+            let nu3 = floor_log(2, (self.D1 / self.D3) as u64);
+            if (self.NU2 + nu3) * self.T_GSW > self.D1 {
+                errors.push(ParamsError::GswCountTooLarge);
+            }
+        }
+        if self.BATCH_SIZE == 0 {
+            errors.push(ParamsError::BatchSizeZero);
+        }
+        if self.N_VEC == 0 {
+            errors.push(ParamsError::NVecZero);
+        }
+        errors.into_result()
+    }
+
     pub const fn expand(&self) -> RespireParamsExpanded {
+        if let Err(errors) = self.validate() {
+            match errors.get(0) {
+                Some(error) => panic!("{}", error.message()),
+                None => unreachable!(),
+            }
+        }
         let q1 = self.Q1A * self.Q1B;
         let z_gsw = base_from_len(self.T_GSW, q1);
         let z_proj_short = base_from_len(self.T_PROJ_SHORT, q1);
@@ -109,16 +516,19 @@ impl RespireParams {
             T_GSW: self.T_GSW,
             M_GSW: 2 * self.T_GSW,
             Z_PROJ_SHORT: z_proj_short,
-            T_PROJ_SHORT: self.T_PROJ_SHORT,
+            T_PROJ_SHORT: self.T_PROJ_SHORT - self.DROP_PROJ_SHORT,
+            DROP_PROJ_SHORT: self.DROP_PROJ_SHORT,
             Z_PROJ_LONG: z_proj_long,
-            T_PROJ_LONG: self.T_PROJ_LONG,
+            T_PROJ_LONG: self.T_PROJ_LONG - self.DROP_PROJ_LONG,
+            DROP_PROJ_LONG: self.DROP_PROJ_LONG,
             Z_RLWE_TO_GSW: z_rlwe_to_gsw,
-            T_RLWE_TO_GSW: self.T_RLWE_TO_GSW,
+            T_RLWE_TO_GSW: self.T_RLWE_TO_GSW - self.DROP_RLWE_TO_GSW,
+            DROP_RLWE_TO_GSW: self.DROP_RLWE_TO_GSW,
             T_VECTORIZE: self.T_VECTORIZE,
             Z_VECTORIZE: z_vectorize,
             BATCH_SIZE: self.BATCH_SIZE,
             N_VEC: self.N_VEC,
-            M_RLWE_TO_GSW: 2 * self.T_RLWE_TO_GSW,
+            M_RLWE_TO_GSW: 2 * (self.T_RLWE_TO_GSW - self.DROP_RLWE_TO_GSW),
             ERROR_WIDTH_MILLIONTHS: self.ERROR_WIDTH_MILLIONTHS,
             ERROR_WIDTH_VEC_MILLIONTHS: self.ERROR_WIDTH_VEC_MILLIONTHS,
             ERROR_WIDTH_COMPRESS_MILLIONTHS: self.ERROR_WIDTH_COMPRESS_MILLIONTHS,
@@ -140,7 +550,7 @@ impl RespireParams {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub struct RespireParamsExpanded {
     pub Q1: u64,
     pub Q1A: u64,
@@ -150,11 +560,16 @@ pub struct RespireParamsExpanded {
     pub T_GSW: usize,
     pub M_GSW: usize,
     pub Z_PROJ_SHORT: u64,
+    /// Number of digits actually kept, i.e. the requested length minus the dropped digits. The
+    /// base is still derived from the full length. The same holds for the other `T_*`/`DROP_*` pairs.
     pub T_PROJ_SHORT: usize,
+    pub DROP_PROJ_SHORT: usize,
     pub Z_PROJ_LONG: u64,
     pub T_PROJ_LONG: usize,
+    pub DROP_PROJ_LONG: usize,
     pub Z_RLWE_TO_GSW: u64,
     pub T_RLWE_TO_GSW: usize,
+    pub DROP_RLWE_TO_GSW: usize,
     pub M_RLWE_TO_GSW: usize,
     pub Z_VECTORIZE: u64,
     pub T_VECTORIZE: usize,
@@ -178,11 +593,560 @@ pub struct RespireParamsExpanded {
     pub BYTES_PER_RECORD: usize,
 }
 
+///
+/// The analytic noise of each step of the protocol, for a single query. Steps up to `vector_pack`
+/// mirror the stages of `answer_one` and `answer_compress_chunk` (see `noise_subgaussian_bits` for
+/// the measured counterparts); `*_new` fields are the noise a step adds on top of its input.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseBreakdown {
+    pub initial: SubGaussianNoise,
+    pub query_expand_rlwe: SubGaussianNoise,
+    pub query_expand_gsw_raw: SubGaussianNoise,
+    pub rlwe_to_gsw_initial: SubGaussianNoise,
+    pub rlwe_to_gsw_gadget: SubGaussianNoise,
+    pub rlwe_to_gsw_dropped: SubGaussianNoise,
+    pub query_expand_gsw: SubGaussianNoise,
+    pub first_dim: SubGaussianNoise,
+    pub fold: SubGaussianNoise,
+    pub rotate: SubGaussianNoise,
+    /// Noise added by projecting a single record.
+    pub project_new: SubGaussianNoise,
+    /// A single projected answer, i.e. ring packing of one record.
+    pub project: SubGaussianNoise,
+    pub ring_pack_records: usize,
+    pub ring_pack: SubGaussianNoise,
+    pub vector_pack_elems: usize,
+    pub vector_pack_new: SubGaussianNoise,
+    pub vector_pack: SubGaussianNoise,
+    /// Preswitch noise carried to `Q3`.
+    pub switch_preswitch: SubGaussianNoise,
+    /// Noise of the compression key and of rounding the first component to `Q2`, at `Q3`.
+    pub switch_gadget: SubGaussianNoise,
+    pub switch_total: SubGaussianNoise,
+    /// Rounding to `Q3`, plus the offset from `Q1` not being a multiple of `P`.
+    pub switch_rounding: BoundedNoise,
+    /// A coefficient decodes correctly if its final noise is below this.
+    pub threshold: u64,
+    pub margin_bits: f64,
+    pub error_rate: f64,
+}
+
+impl NoiseBreakdown {
+    pub fn log(&self) {
+        info!("*** Error estimates (bits) ***");
+        info!("Initial: {}", self.initial.width_bits());
+        info!("Query expand rlwe: {}", self.query_expand_rlwe.width_bits());
+        info!(
+            "Query expand GSW (raw): {}",
+            self.query_expand_gsw_raw.width_bits()
+        );
+        info!(
+            "    RLWE to GSW initial component: {}",
+            self.rlwe_to_gsw_initial.width_bits(),
+        );
+        info!(
+            "    RLWE to GSW gadget component: {}",
+            self.rlwe_to_gsw_gadget.width_bits(),
+        );
+        if self.rlwe_to_gsw_dropped.variance() > 0_f64 {
+            info!(
+                "    RLWE to GSW dropped digits component: {}",
+                self.rlwe_to_gsw_dropped.width_bits(),
+            );
+        }
+        info!(
+            "Query expand GSW (converted): {}",
+            self.query_expand_gsw.width_bits()
+        );
+        info!("First dimension: {}", self.first_dim.width_bits());
+        info!("Fold: {}", self.fold.width_bits());
+        info!("Rotate select: {}", self.rotate.width_bits());
+        info!(
+            "    Projection *new* error component: {}",
+            self.project_new.width_bits()
+        );
+        info!(
+            "Ring packing ({} record(s)): {}",
+            self.ring_pack_records,
+            self.ring_pack.width_bits()
+        );
+        info!(
+            "    Vector packing *new* error component ({} ring elem(s)): {}",
+            self.vector_pack_elems,
+            self.vector_pack_new.width_bits(),
+        );
+        info!("Vector packing: {}", self.vector_pack.width_bits());
+        info!("***");
+        info!(
+            "Preswitch noise: {:.3} total bits; approx {:.3} of margin",
+            self.vector_pack.width_bits(),
+            self.margin_bits
+        );
+
+        let threshold = self.threshold;
+        info!(
+            "Switch rounding term noise bound (absolute / threshold): {} / {}",
+            self.switch_rounding.bound(),
+            threshold
+        );
+        info!("Switch subgaussian term noise widths (absolute / threshold):");
+        info!(
+            "    preswitch: {:.3} / {}",
+            self.switch_preswitch.variance().sqrt(),
+            threshold
+        );
+        info!(
+            "    gadget: {:.3} / {}",
+            self.switch_gadget.variance().sqrt(),
+            threshold
+        );
+        info!(
+            "    total: {:.3} / {}",
+            self.switch_total.variance().sqrt(),
+            threshold
+        );
+        info!("Error rate: 2^({})", self.error_rate.log2());
+        info!("***");
+    }
+}
+
+/// Modulus switching and compression, see `RespireParamsExpanded::switch_noise`.
+struct SwitchNoise {
+    preswitch: SubGaussianNoise,
+    gadget: SubGaussianNoise,
+    total: SubGaussianNoise,
+    rounding: BoundedNoise,
+    error_rate: f64,
+}
+
+impl RespireParamsExpanded {
+    pub const fn packed_dim1_size(&self) -> usize {
+        1 << self.NU1
+    }
+
+    pub const fn packed_dim2_size(&self) -> usize {
+        1 << self.NU2
+    }
+
+    pub const fn pack_ratio_db(&self) -> usize {
+        self.D1 / self.D3
+    }
+
+    pub const fn pack_ratio_response(&self) -> usize {
+        self.D2 / self.D3
+    }
+
+    pub const fn db_size(&self) -> usize {
+        self.packed_dim1_size() * self.packed_dim2_size() * self.pack_ratio_db()
+    }
+
+    pub const fn nu3(&self) -> usize {
+        ceil_log(2, (self.D1 / self.D3) as u64)
+    }
+
+    pub const fn rlwe_count(&self) -> usize {
+        1 << self.NU1
+    }
+
+    pub const fn gsw_count(&self) -> usize {
+        (self.NU2 + self.nu3()) * self.T_GSW
+    }
+
+    ///
+    /// The RLWE instances whose hardness the scheme relies on: the first dimension ring, with a
+    /// uniform secret in `[-SECRET_BOUND, SECRET_BOUND)`, and the compression ring.
+    ///
+    pub const fn lwe_instances(&self) -> [(&'static str, LWEInstance); 2] {
+        const fn width(millionths: u64) -> f64 {
+            millionths as f64 / 1_000_000_f64
+        }
+        [
+            (
+                "D1/Q1",
+                LWEInstance {
+                    n: self.D1,
+                    q: self.Q1 as f64,
+                    stddev_e: width_to_stddev(width(self.ERROR_WIDTH_MILLIONTHS)),
+                    stddev_s: self.SECRET_BOUND as f64 / security::sqrt(3_f64),
+                },
+            ),
+            (
+                "D2/Q2",
+                LWEInstance::gaussian(
+                    self.D2,
+                    self.Q2 as f64,
+                    width(self.ERROR_WIDTH_COMPRESS_MILLIONTHS),
+                    width(self.SECRET_WIDTH_COMPRESS_MILLIONTHS),
+                ),
+            ),
+        ]
+    }
+
+    pub fn security_estimates(&self) -> [(&'static str, SecurityEstimate); 2] {
+        self.lwe_instances()
+            .map(|(name, lwe)| (name, SecurityEstimate::estimate(&lwe)))
+    }
+
+    /// The estimated security of the weakest instance.
+    pub const fn security_bits(&self) -> f64 {
+        let instances = self.lwe_instances();
+        let mut bits = f64::INFINITY;
+        let mut i = 0;
+        while i < instances.len() {
+            bits = bits.min(SecurityEstimate::estimate(&instances[i].1).bits());
+            i += 1;
+        }
+        bits
+    }
+
+    ///
+    /// Returns `self`, or panics if the estimated security is below `min_security_bits`. `respire!`
+    /// calls this in a constant, so that a weak parameter set fails to compile.
+    ///
+    pub const fn assert_secure(self, min_security_bits: f64) -> Self {
+        if self.security_bits() < min_security_bits {
+            panic!("the estimated security of the parameters is below the floor");
+        }
+        self
+    }
+
+    pub fn check_security(&self, min_security_bits: f64) -> Result<(), String> {
+        for (name, estimate) in self.security_estimates() {
+            if estimate.bits() < min_security_bits {
+                return Err(format!(
+                    "{} instance has estimated security {}, below the floor of {} bits",
+                    name, estimate, min_security_bits
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Tracks the noise through each step of answering a query, down to the error rate of a
+    /// decoded coefficient.
+    ///
+    pub fn noise_breakdown(&self) -> NoiseBreakdown {
+        let width = |millionths: u64| millionths as f64 / 1_000_000_f64;
+        let degree = self.D1 as u64;
+        let error = SubGaussianNoise::new(width(self.ERROR_WIDTH_MILLIONTHS).powi(2), degree);
+        let error_vec =
+            SubGaussianNoise::new(width(self.ERROR_WIDTH_VEC_MILLIONTHS).powi(2), degree);
+        let secret = BoundedNoise::new(self.SECRET_BOUND as f64, degree);
+
+        let gadget_gsw = Gadget::new(self.T_GSW, self.Z_GSW);
+        let gadget_proj_short =
+            Gadget::approx(self.T_PROJ_SHORT, self.Z_PROJ_SHORT, self.DROP_PROJ_SHORT);
+        let gadget_proj_long =
+            Gadget::approx(self.T_PROJ_LONG, self.Z_PROJ_LONG, self.DROP_PROJ_LONG);
+        let gadget_rlwe_to_gsw = Gadget::approx(
+            self.T_RLWE_TO_GSW,
+            self.Z_RLWE_TO_GSW,
+            self.DROP_RLWE_TO_GSW,
+        );
+        let gadget_vectorize = Gadget::new(self.T_VECTORIZE, self.Z_VECTORIZE);
+
+        let log_d: usize = ceil_log(2, self.D1 as u64);
+        let select = |e_reg: SubGaussianNoise, e_gsw: SubGaussianNoise, depth: usize| {
+            (0..depth).fold(e_reg, |e, _| e.external_product(e_gsw, gadget_gsw))
+        };
+
+        // Query expansion
+        let query_expand_rlwe = error.project(error, gadget_proj_short, secret, log_d);
+        let query_expand_gsw_raw = error.project(error, gadget_proj_long, secret, log_d);
+
+        // RLWE to GSW: the raw encoding times s, the key times the gadget decomposition, and the
+        // rounding error of the dropped digits times the s and s^2 gadget rows
+        let secret_sq = BoundedNoise::new(
+            (self.D1 as f64).sqrt() * (self.SECRET_BOUND as f64).powi(2),
+            degree,
+        );
+        let rlwe_to_gsw_initial = query_expand_gsw_raw * secret;
+        let rlwe_to_gsw_gadget = error.gadget_product(gadget_rlwe_to_gsw, 2);
+        let rlwe_to_gsw_dropped = gadget_rlwe_to_gsw.rounding(degree) * secret
+            + gadget_rlwe_to_gsw.rounding(degree) * secret_sq;
+        let query_expand_gsw = rlwe_to_gsw_initial + rlwe_to_gsw_gadget + rlwe_to_gsw_dropped;
+
+        // First dimension (NU1). Past D1 rows, the selections come from several query
+        // ciphertexts, each expanded to the same depth.
+        let dim1 = self.packed_dim1_size();
+        let record = BoundedNoise::new_matrix((self.P / 2) as f64, degree, dim1, 1);
+        let first_dim = query_expand_rlwe.with_dimension(1, dim1) * record;
+
+        // Folding (NU2), rotating (NU3)
+        let fold = select(first_dim, query_expand_gsw, self.NU2);
+        let rotate = select(fold, query_expand_gsw, self.nu3());
+
+        // Proj/select (NU3) + ring packing
+        let project_new =
+            SubGaussianNoise::zero(degree).project(error, gadget_proj_long, secret, self.nu3());
+        let ring_pack_records = min(self.BATCH_SIZE, self.pack_ratio_response());
+        let ring_pack = rotate + project_new * Independent(ring_pack_records as f64);
+
+        // Scalar to vector packing
+        let vector_pack_elems = min(
+            self.BATCH_SIZE.div_ceil(self.pack_ratio_response()),
+            self.N_VEC,
+        );
+        let vector_pack_new =
+            error_vec.gadget_product(gadget_vectorize, 1) * Independent(vector_pack_elems as f64);
+        let vector_pack = ring_pack + vector_pack_new;
+
+        // Compression
+        let switch = self.switch_noise(vector_pack);
+        let threshold = self.Q3 / (2 * self.P);
+
+        // 3 bits = 8 widths
+        let margin_bits =
+            (self.Q1 as f64).log2() - (self.P as f64).log2() - vector_pack.width_bits() - 3_f64;
+
+        NoiseBreakdown {
+            initial: error,
+            query_expand_rlwe,
+            query_expand_gsw_raw,
+            rlwe_to_gsw_initial,
+            rlwe_to_gsw_gadget,
+            rlwe_to_gsw_dropped,
+            query_expand_gsw,
+            first_dim,
+            fold,
+            rotate,
+            project_new,
+            project: rotate + project_new,
+            ring_pack_records,
+            ring_pack,
+            vector_pack_elems,
+            vector_pack_new,
+            vector_pack,
+            switch_preswitch: switch.preswitch,
+            switch_gadget: switch.gadget,
+            switch_total: switch.total,
+            switch_rounding: switch.rounding,
+            threshold,
+            margin_bits,
+            error_rate: switch.error_rate,
+        }
+    }
+
+    ///
+    /// Modulus switching to `Q3` and compression of a vector encoding with noise `vector_pack`,
+    /// down to the error rate of a decoded coefficient.
+    ///
+    fn switch_noise(&self, vector_pack: SubGaussianNoise) -> SwitchNoise {
+        let degree = self.D1 as u64;
+        let error_switch = SubGaussianNoise::new(
+            (self.ERROR_WIDTH_COMPRESS_MILLIONTHS as f64 / 1_000_000_f64).powi(2),
+            degree,
+        );
+        let secret_vec = SubGaussianNoise::new(
+            (self.SECRET_WIDTH_VEC_MILLIONTHS as f64 / 1_000_000_f64).powi(2),
+            degree,
+        );
+        let gadget_compress = Gadget::new(self.T_COMPRESS, self.Z_COMPRESS);
+
+        assert_eq!(self.Z_COMPRESS, 2);
+        let preswitch = (vector_pack * Independent(8_f64)).modulus_switch(self.Q1, self.Q3);
+        let gadget = (secret_vec * BoundedNoise::rounding(degree)
+            + error_switch.gadget_product(gadget_compress, 1))
+        .modulus_switch(self.Q2, self.Q3);
+        let total = preswitch + gadget;
+        let rounding = BoundedNoise::rounding(degree)
+            + BoundedNoise::rounding(degree)
+            + BoundedNoise::new((self.Q1 % self.P) as f64 / 2_f64, degree)
+                .modulus_switch(self.Q1, self.Q3);
+        // Rounding past half the plaintext spacing decodes wrong regardless of the noise
+        let margin = 0.5_f64 * (self.Q3 / self.P) as f64 - rounding.bound();
+        let error_rate = if margin <= 0_f64 {
+            1_f64
+        } else {
+            2_f64 * (self.D3 as f64) * f64::exp(-PI * margin.powi(2) / total.variance())
+        };
+        SwitchNoise {
+            preswitch,
+            gadget,
+            total,
+            rounding,
+            error_rate,
+        }
+    }
+
+    pub fn error_rate_estimate(&self) -> f64 {
+        let breakdown = self.noise_breakdown();
+        breakdown.log();
+        clamp(breakdown.error_rate, 0_f64, 1_f64)
+    }
+
+    pub fn public_param_size(&self) -> usize {
+        let automorph_elems = floor_log(2, self.D1 as u64) * (self.T_PROJ_SHORT + self.T_PROJ_LONG);
+        let reg_to_gsw_elems = 2 * self.T_RLWE_TO_GSW;
+        let scal_to_vec_elems = self.N_VEC * self.T_VECTORIZE;
+        let q_elem_size = self.D1 * ceil_log(2, self.Q1) / 8;
+
+        let compress_elems = self.N_VEC * self.T_COMPRESS;
+        let q2_elem_size = self.D1 * ceil_log(2, self.Q2) / 8;
+
+        // This code assumes we implement use PRG trick to compress the randomness components of the
+        // public params. Technically though, this hasn't been implemented.
+
+        info!(
+            "automorph pp: {:.3} KiB",
+            (automorph_elems * q_elem_size) as f64 / 1024_f64
+        );
+        info!(
+            "rlwe to GSW pp: {:.3} KiB",
+            (reg_to_gsw_elems * q_elem_size) as f64 / 1024_f64
+        );
+        info!(
+            "scal to vec pp: {:.3} KiB",
+            (scal_to_vec_elems * q_elem_size) as f64 / 1024_f64
+        );
+        info!(
+            "compress pp: {:.3} KiB",
+            (compress_elems * q2_elem_size) as f64 / 1024_f64
+        );
+        return (automorph_elems + reg_to_gsw_elems + scal_to_vec_elems) * q_elem_size
+            + compress_elems * q2_elem_size;
+    }
+
+    ///
+    /// Number of indices whose GSW coefficients fit in one query ciphertext. Their selection
+    /// coefficients take `ceil(n * rlwe_count() / D1)` more ciphertexts.
+    ///
+    pub fn query_pack_size(&self) -> usize {
+        self.D1.checked_div(self.gsw_count()).unwrap_or(self.D1)
+    }
+
+    ///
+    /// Size of a query for `num_indices` indices, packed `query_pack_size()` at a time. Each
+    /// ciphertext costs its 32 byte seed, on top of one `Q1` coefficient per selection and GSW
+    /// bit.
+    ///
+    pub fn query_size_for(&self, num_indices: usize) -> usize {
+        let pack_size = self.query_pack_size();
+        let num_seeds = (0..num_indices.div_ceil(pack_size))
+            .map(|i| {
+                let count = min(pack_size, num_indices - i * pack_size);
+                1 + (count * self.rlwe_count()).div_ceil(self.D1)
+            })
+            .sum::<usize>();
+        num_seeds * 32
+            + (num_indices * (self.rlwe_count() + self.gsw_count()) * ceil_log(2, self.Q1))
+                .div_ceil(8)
+    }
+
+    pub fn query_one_size(&self) -> usize {
+        self.query_size_for(1)
+    }
+
+    pub fn record_one_size(&self) -> usize {
+        let log_p = floor_log(2, self.P);
+        self.D3 * log_p / 8
+    }
+
+    pub fn response_one_size(&self, trunc_len: usize) -> usize {
+        // Technically we can do ceil(d * (log(q2) + len * log(q1)) by packing into a single large integer.
+        // But for simplicity assume each IntMod<self.Q1> / IntMod<self.Q2> is serialized individually.
+        let log_q1 = ceil_log(2, self.Q3);
+        let log_q2 = ceil_log(2, self.Q2);
+        ((self.D2 as f64) * (log_q2 as f64 + (trunc_len as f64) * log_q1 as f64) / 8_f64).ceil()
+            as usize
+    }
+
+    pub fn query_size(&self) -> usize {
+        self.query_size_for(self.BATCH_SIZE)
+    }
+
+    pub fn record_size(&self) -> usize {
+        self.BATCH_SIZE * self.record_one_size()
+    }
+
+    ///
+    /// size, number of full vectors, remainder size
+    ///
+    pub fn response_info(&self) -> (usize, usize, usize) {
+        let num_ring_elem = self.BATCH_SIZE.div_ceil(self.pack_ratio_response());
+        let num_full_vecs = num_ring_elem / self.N_VEC;
+        let num_rem = num_ring_elem % self.N_VEC;
+
+        let full_vec_size = self.response_one_size(self.N_VEC);
+        let rem_vec_size = if num_rem > 0 {
+            self.response_one_size(num_rem)
+        } else {
+            0
+        };
+        (
+            num_full_vecs * full_vec_size + rem_vec_size,
+            num_full_vecs,
+            num_rem,
+        )
+    }
+
+    pub fn rate(&self) -> f64 {
+        (self.record_size() as f64) / (self.response_info().0 as f64)
+    }
+}
+
+///
+/// The `RespireImpl` for a `RespireParamsExpanded` constant. Arithmetic modulo `Q1` uses
+/// `IntModCycloCRTEval` unless another `RingQ1Eval` ring is given as a second argument.
+///
+/// Parameter sets with an estimated security below `DEFAULT_MIN_SECURITY_BITS` fail to compile.
+/// A different floor can be given as a trailing `min_security_bits = ...` argument:
+///
+/// ```
+/// use respire::pir::respire::{Respire, RespireParams, RespireParamsExpanded};
+/// use respire::pir::respire_harness::FactoryParams;
+/// use respire::pir::security::DEFAULT_MIN_SECURITY_BITS;
+///
+/// // A narrow compression error over a large modulus
+/// const WEAK: RespireParamsExpanded = RespireParams {
+///     Q2: 4294955009,
+///     ERROR_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+///     SECRET_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+///     ..FactoryParams::single_record_256(3, 3).expand()
+/// }
+/// .expand();
+/// type Weak = respire::respire!(WEAK, min_security_bits = 32_f64);
+/// assert!(Weak::params().security_bits() < DEFAULT_MIN_SECURITY_BITS);
+/// ```
+///
+/// while the default floor rejects it:
+///
+/// ```compile_fail
+/// # use respire::pir::respire::{Respire, RespireParams, RespireParamsExpanded};
+/// # use respire::pir::respire_harness::FactoryParams;
+/// # const WEAK: RespireParamsExpanded = RespireParams {
+/// #     Q2: 4294955009,
+/// #     ERROR_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+/// #     SECRET_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+/// #     ..FactoryParams::single_record_256(3, 3).expand()
+/// # }
+/// # .expand();
+/// type Weak = respire::respire!(WEAK);
+/// assert!(Weak::params().security_bits() > 0_f64);
+/// ```
+///
+/// Likewise for a parameter set that `RespireParams::validate` rejects, e.g. one where `P` does not
+/// divide `Q3`:
+///
+/// ```compile_fail
+/// # use respire::pir::respire::{Respire, RespireParams, RespireParamsExpanded};
+/// # use respire::pir::respire_harness::FactoryParams;
+/// const INVALID: RespireParamsExpanded = RespireParams {
+///     Q3: 250,
+///     ..FactoryParams::single_record_256(3, 3).expand()
+/// }
+/// .expand();
+/// type Invalid = respire::respire!(INVALID);
+/// assert_eq!(Invalid::params().Q3, 250);
+/// ```
+///
 #[macro_export]
 macro_rules! respire {
-    ($params: expr) => {
+    (@impl $params: expr, $min_security_bits: expr $(, $ring: ty)?) => {
         $crate::pir::respire::RespireImpl<
-            {$params.Q1},
+            {$params.assert_secure($min_security_bits).Q1},
             {$params.Q1A},
             {$params.Q1B},
             {$params.D1},
@@ -191,10 +1155,13 @@ macro_rules! respire {
             {$params.M_GSW},
             {$params.Z_PROJ_SHORT},
             {$params.T_PROJ_SHORT},
+            {$params.DROP_PROJ_SHORT},
             {$params.Z_PROJ_LONG},
             {$params.T_PROJ_LONG},
+            {$params.DROP_PROJ_LONG},
             {$params.Z_RLWE_TO_GSW},
             {$params.T_RLWE_TO_GSW},
+            {$params.DROP_RLWE_TO_GSW},
             {$params.M_RLWE_TO_GSW},
             {$params.Z_VECTORIZE},
             {$params.T_VECTORIZE},
@@ -216,8 +1183,21 @@ macro_rules! respire {
             {$params.T_COMPRESS},
             {$params.Z_COMPRESS},
             {$params.BYTES_PER_RECORD},
+            $($ring,)?
         >
-    }
+    };
+    ($params: expr, min_security_bits = $min_security_bits: expr) => {
+        $crate::respire!(@impl $params, $min_security_bits)
+    };
+    ($params: expr, $ring: ty, min_security_bits = $min_security_bits: expr) => {
+        $crate::respire!(@impl $params, $min_security_bits, $ring)
+    };
+    ($params: expr) => {
+        $crate::respire!(@impl $params, $crate::pir::security::DEFAULT_MIN_SECURITY_BITS)
+    };
+    ($params: expr, $ring: ty) => {
+        $crate::respire!(@impl $params, $crate::pir::security::DEFAULT_MIN_SECURITY_BITS, $ring)
+    };
 }
 
 macro_rules! respire_impl {
@@ -232,10 +1212,13 @@ macro_rules! respire_impl {
                 const M_GSW: usize,
                 const Z_PROJ_SHORT: u64,
                 const T_PROJ_SHORT: usize,
+                const DROP_PROJ_SHORT: usize,
                 const Z_PROJ_LONG: u64,
                 const T_PROJ_LONG: usize,
+                const DROP_PROJ_LONG: usize,
                 const Z_RLWE_TO_GSW: u64,
                 const T_RLWE_TO_GSW: usize,
+                const DROP_RLWE_TO_GSW: usize,
                 const M_RLWE_TO_GSW: usize,
                 const Z_VECTORIZE: u64,
                 const T_VECTORIZE: usize,
@@ -257,6 +1240,7 @@ macro_rules! respire_impl {
                 const T_COMPRESS: usize,
                 const Z_COMPRESS: u64,
                 const BYTES_PER_RECORD: usize,
+                RingQ1Fast,
             > $impl_for
             for RespireImpl<
                 Q1,
@@ -268,10 +1252,13 @@ macro_rules! respire_impl {
                 M_GSW,
                 Z_PROJ_SHORT,
                 T_PROJ_SHORT,
+                DROP_PROJ_SHORT,
                 Z_PROJ_LONG,
                 T_PROJ_LONG,
+                DROP_PROJ_LONG,
                 Z_RLWE_TO_GSW,
                 T_RLWE_TO_GSW,
+                DROP_RLWE_TO_GSW,
                 M_RLWE_TO_GSW,
                 Z_VECTORIZE,
                 T_VECTORIZE,
@@ -293,7 +1280,17 @@ macro_rules! respire_impl {
                 T_COMPRESS,
                 Z_COMPRESS,
                 BYTES_PER_RECORD,
+                RingQ1Fast,
             >
+        where
+            RingQ1Fast: RingQ1Eval<D1, Q1, Q1A, Q1B>
+                + PreparedMatrixMul<2, M_GSW>
+                + RingElementDecomposable<Z_GSW, T_GSW>
+                + RingElementDecomposable<Z_PROJ_SHORT, T_PROJ_SHORT>
+                + RingElementDecomposable<Z_PROJ_LONG, T_PROJ_LONG>
+                + RingElementDecomposable<Z_RLWE_TO_GSW, T_RLWE_TO_GSW>
+                + RingElementDecomposable<Z_VECTORIZE, T_VECTORIZE>,
+            for<'a> &'a RingQ1Fast: RingElementRef<RingQ1Fast>,
         $body
     };
     ($body: tt) => {
@@ -307,10 +1304,13 @@ macro_rules! respire_impl {
                 const M_GSW: usize,
                 const Z_PROJ_SHORT: u64,
                 const T_PROJ_SHORT: usize,
+                const DROP_PROJ_SHORT: usize,
                 const Z_PROJ_LONG: u64,
                 const T_PROJ_LONG: usize,
+                const DROP_PROJ_LONG: usize,
                 const Z_RLWE_TO_GSW: u64,
                 const T_RLWE_TO_GSW: usize,
+                const DROP_RLWE_TO_GSW: usize,
                 const M_RLWE_TO_GSW: usize,
                 const Z_VECTORIZE: u64,
                 const T_VECTORIZE: usize,
@@ -332,6 +1332,7 @@ macro_rules! respire_impl {
                 const T_COMPRESS: usize,
                 const Z_COMPRESS: u64,
                 const BYTES_PER_RECORD: usize,
+                RingQ1Fast,
             > RespireImpl<
                 Q1,
                 Q1A,
@@ -342,10 +1343,13 @@ macro_rules! respire_impl {
                 M_GSW,
                 Z_PROJ_SHORT,
                 T_PROJ_SHORT,
+                DROP_PROJ_SHORT,
                 Z_PROJ_LONG,
                 T_PROJ_LONG,
+                DROP_PROJ_LONG,
                 Z_RLWE_TO_GSW,
                 T_RLWE_TO_GSW,
+                DROP_RLWE_TO_GSW,
                 M_RLWE_TO_GSW,
                 Z_VECTORIZE,
                 T_VECTORIZE,
@@ -367,12 +1371,22 @@ macro_rules! respire_impl {
                 T_COMPRESS,
                 Z_COMPRESS,
                 BYTES_PER_RECORD,
+                RingQ1Fast,
             >
+        where
+            RingQ1Fast: RingQ1Eval<D1, Q1, Q1A, Q1B>
+                + PreparedMatrixMul<2, M_GSW>
+                + RingElementDecomposable<Z_GSW, T_GSW>
+                + RingElementDecomposable<Z_PROJ_SHORT, T_PROJ_SHORT>
+                + RingElementDecomposable<Z_PROJ_LONG, T_PROJ_LONG>
+                + RingElementDecomposable<Z_RLWE_TO_GSW, T_RLWE_TO_GSW>
+                + RingElementDecomposable<Z_VECTORIZE, T_VECTORIZE>,
+            for<'a> &'a RingQ1Fast: RingElementRef<RingQ1Fast>,
         $body
     };
 }
 
-pub trait Respire: PIR {
+pub trait Respire: PIR<PublicParams: Clone> {
     // Type aliases
     type RingP;
     type RingQ1;
@@ -381,6 +1395,8 @@ pub trait Respire: PIR {
     type RLWEEncodingSeeded;
     type RLWEEncodingCompressed;
     type GSWEncoding;
+    /// A GSW encoding prepared for repeated selections (see `PreparedMatrixMul`).
+    type GSWPrepared;
     type EncodingSecret;
     type VecEncodingSecret;
     type VecEncodingSecretQ2;
@@ -400,6 +1416,10 @@ pub trait Respire: PIR {
     type RecordPackedSmall;
     // Packed records from a single response, before compression
     type RecordPacked;
+    // Up to QUERY_PACK_SIZE indices sharing one set of query ciphertexts
+    type QueryPacked;
+    // A seeded encoding of zero, precomputed offline for one query ciphertext
+    type QueryMask;
     type QueryOne;
     type QueryOneExpanded;
     type AnswerOne;
@@ -423,12 +1443,50 @@ pub trait Respire: PIR {
     const GSW_ROT_COUNT: usize;
     const GSW_COUNT: usize;
     const GSW_EXPAND_ITERS: usize;
+    const QUERY_PACK_SIZE: usize;
 
     fn query_one(
         qk: &<Self as PIR>::QueryKey,
         idx: usize,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::QueryOne;
+    fn query_select_one(
+        qk: &<Self as PIR>::QueryKey,
+        rows: &[bool],
+        column: usize,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryOne;
+    fn query_sum_one(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::QueryOne, AggregateError>;
+    fn query_packed(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked;
+    fn query_select_packed(
+        qk: &<Self as PIR>::QueryKey,
+        selections: &[(&[bool], usize)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked;
+    fn query_mask_count(num_indices: usize) -> usize;
+    fn query_masks(
+        qk: &<Self as PIR>::QueryKey,
+        count: usize,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::QueryMask>;
+    fn query_packed_online(
+        masks: &mut Vec<<Self as Respire>::QueryMask>,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked;
+    fn query_select_packed_online(
+        masks: &mut Vec<<Self as Respire>::QueryMask>,
+        selections: &[(&[bool], usize)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked;
     fn answer_one(
         pp: &<Self as PIR>::PublicParams,
         db: &<Self as PIR>::Database,
@@ -436,12 +1494,43 @@ pub trait Respire: PIR {
         qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOne;
+    fn answer_one_planes(
+        pp: &<Self as PIR>::PublicParams,
+        planes: &[<Self as PIR>::Database],
+        q: &<Self as Respire>::QueryOne,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::AnswerOneCompressed>;
+    fn answer_planes(
+        pp: &<Self as PIR>::PublicParams,
+        planes: &[<Self as PIR>::Database],
+        qs: &<Self as PIR>::Query,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as PIR>::Response>;
+    fn answer_query_unpack_packed(
+        pp: &<Self as PIR>::PublicParams,
+        q: &<Self as Respire>::QueryPacked,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::QueryOneExpanded>;
+    fn answer_one_expanded(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        expanded: &<Self as Respire>::QueryOneExpanded,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::AnswerOne;
     fn answer_compress_chunk(
         pp: &<Self as PIR>::PublicParams,
         chunk: &[<Self as Respire>::AnswerOne],
         qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOneCompressed;
+    fn answer_pack_chunk(
+        pp: &<Self as PIR>::PublicParams,
+        chunk: &[<Self as Respire>::AnswerOne],
+    ) -> <Self as Respire>::VecRLWEEncoding;
     fn answer_compress_vec(
         pp: &<Self as PIR>::PublicParams,
         vec: &<Self as Respire>::VecRLWEEncoding,
@@ -452,6 +1541,36 @@ pub trait Respire: PIR {
         r: &<Self as Respire>::AnswerOneCompressed,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> Vec<<Self as PIR>::RecordBytes>;
+    fn extract_sum_one(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::Record>;
+    fn extract_one_checked(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+        config: &ExtractConfig,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<(<Self as PIR>::RecordBytes, RecordConfidence)>;
+    fn extract_checked(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as PIR>::Response,
+        config: &ExtractConfig,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<(<Self as PIR>::RecordBytes, RecordConfidence)>;
+    fn encode_public_params(pp: &<Self as PIR>::PublicParams) -> Vec<u8>;
+    fn decode_public_params(bytes: &[u8]) -> io::Result<<Self as PIR>::PublicParams>;
+    fn public_params_memory_size(pp: &<Self as PIR>::PublicParams) -> usize;
+    fn validate_query_packed(
+        q: &<Self as Respire>::QueryPacked,
+        num_indices: usize,
+    ) -> Result<(), QueryError>;
+    fn encode_query_packed(qs: &[<Self as Respire>::QueryPacked]) -> Vec<u8>;
+    fn decode_query_packed(bytes: &[u8]) -> io::Result<Vec<<Self as Respire>::QueryPacked>>;
+    fn encode_answers_compressed(r: &[<Self as Respire>::AnswerOneCompressed]) -> Vec<u8>;
+    fn decode_answers_compressed(
+        bytes: &[u8],
+    ) -> io::Result<Vec<<Self as Respire>::AnswerOneCompressed>>;
 
     fn params() -> RespireParamsExpanded;
     fn params_error_rate_estimate() -> f64;
@@ -493,7 +1612,9 @@ respire_impl!(PIR, {
         <Self as Respire>::VecEncodingSecret,
         <Self as Respire>::VecEncodingSecretQ2Small,
     );
-    type PublicParams = (
+    /// Reference counted, so a `PublicParamsStore` hands out handles that `answer` takes as they
+    /// are, and evicting parameters does not free them while an answer is using them.
+    type PublicParams = Arc<(
         (
             Vec<<Self as Respire>::AutoParamsShort>,
             Vec<<Self as Respire>::AutoParamsLong>,
@@ -501,17 +1622,19 @@ respire_impl!(PIR, {
         <Self as Respire>::RLWEToGSWParams,
         <Self as Respire>::CompressParams,
         <Self as Respire>::VectorizeParams,
-    );
+    )>;
 
-    type Query = Vec<<Self as Respire>::QueryOne>;
+    type Query = Vec<<Self as Respire>::QueryPacked>;
     type Response = Vec<<Self as Respire>::AnswerOneCompressed>;
 
     /// We structure the database as `[2] x [D1 / S] x [DIM2_SIZE] x [DIM1_SIZE] x [S]` for optimal first dimension
     /// processing. The outermost pair is the first resp. second CRT projections, packed as two u32 into one u64;
     /// `S` is the SIMD lane count that we can use, i.e. 4 for AVX2.
-    type Database = Vec<SimdVec>;
+    type Database = Box<dyn DbStorage>;
     type DatabaseHint = ();
     type State = ();
+    type Params = RespireParamsExpanded;
+    type QueryError = QueryError;
 
     // Public types & constants
     type RecordBytes = RecordBytesImpl<BYTES_PER_RECORD>;
@@ -559,7 +1682,29 @@ respire_impl!(PIR, {
         eprintln!(
             "Error rate (estimated): 2^({:.3})",
             Self::params_error_rate_estimate().log2()
-        )
+        );
+
+        for (name, estimate) in Self::params().security_estimates() {
+            eprintln!("Security (estimated) of {}: {}", name, estimate);
+        }
+    }
+
+    fn summary() -> PIRSummary<Self::Params> {
+        let params = Self::params();
+        PIRSummary {
+            scheme: "respire",
+            bytes_per_record: Self::BYTES_PER_RECORD,
+            num_records: Self::NUM_RECORDS,
+            batch_size: Self::BATCH_SIZE,
+            params,
+            public_param_size: params.public_param_size(),
+            query_size: params.query_size(),
+            response_size: params.response_info().0,
+            record_size: params.record_size(),
+            rate: params.rate(),
+            error_rate_log2: params.error_rate_estimate().log2(),
+            security_bits: params.security_bits(),
+        }
     }
 
     fn encode_db<F: Fn(usize) -> Self::RecordBytes>(
@@ -567,32 +1712,13 @@ respire_impl!(PIR, {
         time_stats: Option<&mut Stats<Duration>>,
     ) -> (Self::Database, Self::DatabaseHint) {
         let begin = Instant::now();
-        let records_encoded_generator = |idx: usize| Self::encode_record(&records_generator(idx));
 
         assert!(Q1A <= u32::MAX as u64);
         assert!(Q1B <= u32::MAX as u64);
         assert_eq!(Self::DB_SIZE % Self::PACK_RATIO_DB, 0);
 
-        let records_packed_generator = |chunk_idx: usize| {
-            let chunk = (Self::PACK_RATIO_DB * chunk_idx..Self::PACK_RATIO_DB * (chunk_idx + 1))
-                .map(|idx| records_encoded_generator(idx));
-            let mut record_packed = IntModCyclo::<D1, P>::zero();
-            for (record_in_chunk, record) in chunk.enumerate() {
-                for (coeff_idx, coeff) in record.coeff.iter().enumerate() {
-                    record_packed.coeff[Self::PACK_RATIO_DB * coeff_idx + record_in_chunk] = *coeff;
-                }
-            }
-            let value = <Self as Respire>::RingQ1Fast::from(&record_packed.include_into::<Q1>());
-            let mut packed_value = [0u64; D1];
-            for i in 0..D1 {
-                packed_value[i] = {
-                    let lo = u64::from(value.proj1.evals[i]);
-                    let hi = u64::from(value.proj2.evals[i]);
-                    (hi << 32) | lo
-                };
-            }
-            packed_value
-        };
+        let records_packed_generator =
+            |chunk_idx: usize| Self::encode_records_packed(&records_generator, chunk_idx);
 
         // Quick and dirty buffered iterator implementation
         struct BufferedRecordsIterator<T: Clone, F: Fn(usize) -> T, const N: usize> {
@@ -713,7 +1839,7 @@ respire_impl!(PIR, {
         }
 
         info!("Done processing DB");
-        (db, ())
+        (Box::new(InMemoryStorage::new(db, Self::PACKED_DB_SIZE)), ())
     }
 
     fn setup(
@@ -729,7 +1855,7 @@ respire_impl!(PIR, {
         // Compression target
         let s_small: <Self as Respire>::VecEncodingSecretQ2Small = {
             let mut rng = ChaCha20Rng::from_entropy();
-            let mut result = Matrix::zero();
+            let mut result = Matrix::<N_VEC, 1, IntModCycloEval<D2, Q2>>::zero();
             for i in 0..N_VEC {
                 result[(i, 0)] = IntModCycloEval::rand_discrete_gaussian::<
                     _,
@@ -740,32 +1866,33 @@ respire_impl!(PIR, {
         };
 
         // Vector secret mod Q2 (compression source secret)
-        let s_vec_q2 = s_vec.map_ring(|r| {
+        let s_vec_q2 = s_vec.map_ring::<IntModCycloEval<D1, Q2>, _>(|r| {
             IntModCycloEval::from(IntModCyclo::from(
-                IntModCyclo::<D1, Q1>::from(r)
-                    .coeff
-                    .map(|x| IntMod::from(i64::from(x))),
+                r.to_coeff().coeff.map(|x| IntMod::from(i64::from(x))),
             ))
         });
-        let s_small_q2 =
-            s_small.map_ring(|r| IntModCycloEval::from(IntModCyclo::from(r).include_dim()));
+        let s_small_q2 = s_small.map_ring::<IntModCycloEval<D1, Q2>, _>(|r| {
+            IntModCycloEval::from(IntModCyclo::from(r).include_dim())
+        });
         let compress_params = Self::compress_setup(&s_vec_q2, &s_small_q2);
 
         let mut auto_params_short: Vec<<Self as Respire>::AutoParamsShort> =
             Vec::with_capacity(Self::RLWE_EXPAND_ITERS);
         for i in 0..floor_log(2, D1 as u64) {
             let tau_power = (D1 >> i) + 1;
-            auto_params_short.push(Self::auto_setup::<T_PROJ_SHORT, Z_PROJ_SHORT>(
-                tau_power, &s_encode,
-            ));
+            auto_params_short.push(Self::auto_setup::<
+                T_PROJ_SHORT,
+                Z_PROJ_SHORT,
+                DROP_PROJ_SHORT,
+            >(tau_power, &s_encode));
         }
         let mut auto_params_long: Vec<<Self as Respire>::AutoParamsLong> =
             Vec::with_capacity(Self::GSW_EXPAND_ITERS);
         for i in 0..floor_log(2, D1 as u64) {
             let tau_power = (D1 >> i) + 1;
-            auto_params_long.push(Self::auto_setup::<T_PROJ_LONG, Z_PROJ_LONG>(
-                tau_power, &s_encode,
-            ));
+            auto_params_long.push(
+                Self::auto_setup::<T_PROJ_LONG, Z_PROJ_LONG, DROP_PROJ_LONG>(tau_power, &s_encode),
+            );
         }
 
         let rlwe_to_gsw_params = Self::rlwe_to_gsw_setup(&s_encode);
@@ -778,12 +1905,12 @@ respire_impl!(PIR, {
 
         (
             (s_encode, s_vec, s_small),
-            (
+            Arc::new((
                 (auto_params_short, auto_params_long),
                 rlwe_to_gsw_params,
                 compress_params,
                 vectorize_params,
-            ),
+            )),
         )
     }
 
@@ -795,9 +1922,8 @@ respire_impl!(PIR, {
     ) -> (<Self as PIR>::Query, <Self as PIR>::State) {
         assert_eq!(indices.len(), Self::BATCH_SIZE);
         let q = indices
-            .iter()
-            .copied()
-            .map(|idx| Self::query_one(qk, idx, time_stats.as_deref_mut()))
+            .chunks(Self::QUERY_PACK_SIZE)
+            .map(|chunk| Self::query_packed(qk, chunk, time_stats.as_deref_mut()))
             .collect_vec();
         (q, ())
     }
@@ -807,18 +1933,21 @@ respire_impl!(PIR, {
         db: &<Self as PIR>::Database,
         qs: &<Self as PIR>::Query,
         qk: Option<&<Self as PIR>::QueryKey>,
-        mut time_stats: Option<&mut Stats<Duration>>,
+        time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as PIR>::Response {
-        assert_eq!(qs.len(), Self::BATCH_SIZE);
-        let answers = qs
-            .iter()
-            .map(|q| Self::answer_one(pp, db, q, qk, time_stats.as_deref_mut()))
-            .collect_vec();
-        let answers_compressed = answers
-            .chunks(N_VEC * Self::PACK_RATIO_RESPONSE)
-            .map(|chunk| Self::answer_compress_chunk(pp, chunk, qk, time_stats.as_deref_mut()))
-            .collect_vec();
-        answers_compressed
+        let mut responses = Self::answer_planes(pp, slice::from_ref(db), qs, qk, time_stats);
+        responses.pop().unwrap()
+    }
+
+    fn try_answer(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        qs: &<Self as PIR>::Query,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as PIR>::Response, <Self as PIR>::QueryError> {
+        Self::validate_query(qs)?;
+        Ok(Self::answer(pp, db, qs, qk, time_stats))
     }
 
     fn extract(
@@ -838,16 +1967,33 @@ respire_impl!(PIR, {
         }
         result
     }
+
+    fn encode_query(qs: &Self::Query) -> Vec<u8> {
+        Self::encode_query_packed(qs)
+    }
+
+    fn decode_query(bytes: &[u8]) -> io::Result<Self::Query> {
+        Self::decode_query_packed(bytes)
+    }
+
+    fn encode_response(r: &Self::Response) -> Vec<u8> {
+        Self::encode_answers_compressed(r)
+    }
+
+    fn decode_response(bytes: &[u8]) -> io::Result<Self::Response> {
+        Self::decode_answers_compressed(bytes)
+    }
 });
 
 respire_impl!(Respire, {
     type RingP = IntModCyclo<D1, P>;
     type RingQ1 = IntModCyclo<D1, Q1>;
-    type RingQ1Fast = IntModCycloCRTEval<D1, Q1A, Q1B>;
+    type RingQ1Fast = RingQ1Fast;
     type RLWEEncoding = Matrix<2, 1, Self::RingQ1Fast>;
     type RLWEEncodingSeeded = ([u8; 32], Self::RingQ1Fast);
     type RLWEEncodingCompressed = ([u8; 32], Vec<IntMod<Q1>>);
     type GSWEncoding = Matrix<2, M_GSW, Self::RingQ1Fast>;
+    type GSWPrepared = <Self::RingQ1Fast as PreparedMatrixMul<2, M_GSW>>::Prepared;
 
     type EncodingSecret = Self::RingQ1Fast;
     type VecEncodingSecret = Matrix<N_VEC, 1, Self::RingQ1Fast>;
@@ -875,14 +2021,17 @@ respire_impl!(Respire, {
     type Record = IntModCyclo<D3, P>;
     type RecordPackedSmall = Matrix<N_VEC, 1, IntModCyclo<D2, P>>;
     type RecordPacked = IntModCyclo<D1, P>;
-    type QueryOne = (
-        <Self as Respire>::RLWEEncodingCompressed,
+    type QueryPacked = (
+        // D1 selection coefficients per ciphertext, the last one possibly fewer
+        Vec<<Self as Respire>::RLWEEncodingCompressed>,
         <Self as Respire>::RLWEEncodingCompressed,
     );
+    type QueryMask = ([u8; 32], <Self as Respire>::RingQ1);
+    type QueryOne = <Self as Respire>::QueryPacked;
     type QueryOneExpanded = (
         Vec<<Self as Respire>::RLWEEncoding>, // first dim
-        Vec<<Self as Respire>::GSWEncoding>,  // fold
-        Vec<<Self as Respire>::GSWEncoding>,  // rotate
+        Vec<<Self as Respire>::GSWPrepared>,  // fold
+        Vec<<Self as Respire>::GSWPrepared>,  // rotate
     );
     type AnswerOne = <Self as Respire>::RLWEEncoding;
     type AnswerOneCompressed = <Self as Respire>::VecRLWEEncodingSmallTruncated;
@@ -906,64 +2055,234 @@ respire_impl!(Respire, {
 
     const GSW_COUNT: usize = (Self::GSW_FOLD_COUNT + Self::GSW_ROT_COUNT) * T_GSW;
     const GSW_EXPAND_ITERS: usize = ceil_log(2, Self::GSW_COUNT as u64);
+    const QUERY_PACK_SIZE: usize = match D1.checked_div(Self::GSW_COUNT) {
+        Some(pack_size) => pack_size,
+        None => D1,
+    };
 
     fn query_one(
-        (s_encode, _, _): &<Self as PIR>::QueryKey,
+        qk: &<Self as PIR>::QueryKey,
         idx: usize,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::QueryOne {
-        let begin = Instant::now();
-        assert!(idx < Self::DB_SIZE);
-        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
-        let (idx_i, idx_j) = (idx / last_dims_size, idx % last_dims_size);
+        Self::query_packed(qk, &[idx], time_stats)
+    }
+
+    ///
+    /// Encodes a 0/1 selection `rows` over the first dimension, and the index `column` in the
+    /// remaining dimensions. The answer is the sum mod `P` of the selected records in that column.
+    /// The noise does not depend on the selection, except for the sum wrapping around `P`, which
+    /// adds at most `Q1 mod P` per selected record.
+    ///
+    fn query_select_one(
+        qk: &<Self as PIR>::QueryKey,
+        rows: &[bool],
+        column: usize,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryOne {
+        Self::query_select_packed(qk, &[(rows, column)], time_stats)
+    }
 
-        let mut mu_rlwe = <Self as Respire>::RingQ1::zero();
-        for i in 0..Self::RLWE_COUNT {
-            mu_rlwe.coeff[reverse_bits_fast::<D1>(i)] =
-                IntMod::<P>::from((i == idx_i) as u64).scale_up_into();
+    ///
+    /// A query for the sum mod `P` of the given records, which must all lie in the same column,
+    /// i.e. agree mod `DB_SIZE / RLWE_COUNT`.
+    ///
+    /// Sums only get query privacy, not database privacy: the answer is computed and compressed
+    /// like any other, without rerandomization or noise flooding, so its noise may reveal more
+    /// about the database than the requested sum.
+    ///
+    fn query_sum_one(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Result<<Self as Respire>::QueryOne, AggregateError> {
+        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
+        let column = match indices.first() {
+            Some(idx) => idx % last_dims_size,
+            None => return Err(AggregateError::Empty),
+        };
+        let mut rows = vec![false; Self::RLWE_COUNT];
+        for &index in indices {
+            if index >= Self::DB_SIZE {
+                return Err(AggregateError::IndexOutOfRange { index });
+            }
+            if index % last_dims_size != column {
+                return Err(AggregateError::ColumnMismatch { index, column });
+            }
+            let row = &mut rows[index / last_dims_size];
+            if *row {
+                return Err(AggregateError::DuplicateIndex { index });
+            }
+            *row = true;
         }
+        Ok(Self::query_select_one(qk, &rows, column, time_stats))
+    }
 
-        // [NU2 + NU3] x [T_GSW]
-        let mut mu_gsw = <Self as Respire>::RingQ1::zero();
+    fn query_packed(
+        qk: &<Self as PIR>::QueryKey,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked {
+        let selections = indices
+            .iter()
+            .map(|&idx| Self::index_selection(idx))
+            .collect_vec();
+        let selections = selections
+            .iter()
+            .map(|(rows, column)| (rows.as_slice(), *column))
+            .collect_vec();
+        Self::query_select_packed(qk, &selections, time_stats)
+    }
 
-        let mut bits = Vec::with_capacity(NU2);
-        let mut idx_j_curr = idx_j;
-        for _ in 0..(Self::NU2 + Self::NU3) {
-            bits.push(idx_j_curr % 2);
-            idx_j_curr /= 2;
+    ///
+    /// Same as `query_select_packed_online`, with freshly sampled masks.
+    ///
+    fn query_select_packed(
+        qk: &<Self as PIR>::QueryKey,
+        selections: &[(&[bool], usize)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked {
+        let begin = Instant::now();
+        let mut masks = Self::query_masks(qk, Self::query_mask_count(selections.len()), None);
+        let result = Self::query_select_packed_online(&mut masks, selections, None);
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("query", end - begin);
         }
+        result
+    }
 
-        for (bit_idx, bit) in bits.into_iter().rev().enumerate() {
-            let mut msg = IntMod::from(bit as u64);
-            for gsw_pow in 0..T_GSW {
-                let pack_idx = T_GSW * bit_idx + gsw_pow;
-                mu_gsw.coeff[reverse_bits_fast::<D1>(pack_idx)] = msg;
-                msg *= IntMod::from(Z_GSW);
-            }
+    ///
+    /// The number of query masks used by a packed query of `num_indices` indices: one per RLWE
+    /// ciphertext, and one for the GSW ciphertext.
+    ///
+    fn query_mask_count(num_indices: usize) -> usize {
+        assert!((1..=Self::QUERY_PACK_SIZE).contains(&num_indices));
+        (num_indices * Self::RLWE_COUNT).div_ceil(D1) + 1
+    }
+
+    ///
+    /// The offline phase of a query: samples `count` seeded encodings of zero, in coefficient
+    /// form. This is everything a query ciphertext needs except its message, so the online phase
+    /// only adds the selection coefficients. Each mask must be used for at most one ciphertext;
+    /// the online functions remove the masks they use from the pool.
+    ///
+    fn query_masks(
+        (s_encode, _, _): &<Self as PIR>::QueryKey,
+        count: usize,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::QueryMask> {
+        let begin = Instant::now();
+        let zero = <Self as Respire>::RingQ1::zero();
+        let masks = (0..count)
+            .map(|_| {
+                let (seed, c1) = Self::encode_rlwe_seeded(s_encode, &zero);
+                (seed, c1.to_coeff())
+            })
+            .collect_vec();
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("query_offline", end - begin);
         }
+        masks
+    }
 
-        let (seed_rlwe, ct1_rlwe) = Self::encode_rlwe_seeded(s_encode, &mu_rlwe);
-        let ct1_rlwe_coeff = <Self as Respire>::RingQ1::from(&ct1_rlwe).coeff;
-        let (seed_gsw, ct1_gsw) = Self::encode_rlwe_seeded(s_encode, &mu_gsw);
-        let ct1_gsw_coeff = <Self as Respire>::RingQ1::from(&ct1_gsw).coeff;
-        let compressed_rlwe = (
-            seed_rlwe,
-            (0..Self::RLWE_COUNT)
-                .map(|i| ct1_rlwe_coeff[reverse_bits_fast::<D1>(i)])
-                .collect_vec(),
+    ///
+    /// Same as `query_packed`, using masks from `masks` (see `query_masks`).
+    ///
+    fn query_packed_online(
+        masks: &mut Vec<<Self as Respire>::QueryMask>,
+        indices: &[usize],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked {
+        let selections = indices
+            .iter()
+            .map(|&idx| Self::index_selection(idx))
+            .collect_vec();
+        let selections = selections
+            .iter()
+            .map(|(rows, column)| (rows.as_slice(), *column))
+            .collect_vec();
+        Self::query_select_packed_online(masks, &selections, time_stats)
+    }
+
+    ///
+    /// Packs up to `QUERY_PACK_SIZE` selections (see `query_select_one`) into shared ciphertexts,
+    /// taking `query_mask_count(selections.len())` masks from the end of `masks`.
+    /// Selection `k` occupies coefficients `k * RLWE_COUNT..(k + 1) * RLWE_COUNT` of the
+    /// concatenated RLWE ciphertexts, `D1` per ciphertext, and `k * GSW_COUNT..(k + 1) * GSW_COUNT`
+    /// of the GSW ciphertext (in expansion order), so the server expands each ciphertext in one
+    /// tree. The expansion depth, and hence the noise, is the same as for a single index.
+    ///
+    fn query_select_packed_online(
+        masks: &mut Vec<<Self as Respire>::QueryMask>,
+        selections: &[(&[bool], usize)],
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::QueryPacked {
+        let begin = Instant::now();
+        let count = selections.len();
+        assert!(
+            masks.len() >= Self::query_mask_count(count),
+            "not enough query masks"
         );
-        let compressed_gsw = (
+        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
+
+        // The ciphertexts are the masks, restricted to the coefficients the server expands
+        let rlwe_total = count * Self::RLWE_COUNT;
+        let mut compressed_rlwes = (0..rlwe_total.div_ceil(D1))
+            .map(|ct_idx| {
+                let (seed, mask) = masks.pop().unwrap();
+                let len = min(D1, rlwe_total - ct_idx * D1);
+                (
+                    seed,
+                    (0..len)
+                        .map(|i| mask.coeff[reverse_bits_fast::<D1>(i)])
+                        .collect_vec(),
+                )
+            })
+            .collect_vec();
+        // [count] x [NU2 + NU3] x [T_GSW]
+        let (seed_gsw, mask_gsw) = masks.pop().unwrap();
+        let mut compressed_gsw = (
             seed_gsw,
-            (0..Self::GSW_COUNT)
-                .map(|i| ct1_gsw_coeff[reverse_bits_fast::<D1>(i)])
+            (0..count * Self::GSW_COUNT)
+                .map(|i| mask_gsw.coeff[reverse_bits_fast::<D1>(i)])
                 .collect_vec(),
         );
 
+        for (k, &(rows, column)) in selections.iter().enumerate() {
+            assert_eq!(rows.len(), Self::RLWE_COUNT);
+            assert!(column < last_dims_size);
+
+            let rlwe_offset = k * Self::RLWE_COUNT;
+            for (i, &selected) in rows.iter().enumerate() {
+                let pack_idx = rlwe_offset + i;
+                compressed_rlwes[pack_idx / D1].1[pack_idx % D1] +=
+                    IntMod::<P>::from(selected as u64).scale_up_into();
+            }
+
+            let mut bits = Vec::with_capacity(NU2);
+            let mut idx_j_curr = column;
+            for _ in 0..(Self::NU2 + Self::NU3) {
+                bits.push(idx_j_curr % 2);
+                idx_j_curr /= 2;
+            }
+
+            let gsw_offset = k * Self::GSW_COUNT;
+            for (bit_idx, bit) in bits.into_iter().rev().enumerate() {
+                let mut msg = IntMod::from(bit as u64);
+                for gsw_pow in 0..T_GSW {
+                    compressed_gsw.1[gsw_offset + T_GSW * bit_idx + gsw_pow] += msg;
+                    msg *= IntMod::from(Z_GSW);
+                }
+            }
+        }
+
         let end = Instant::now();
         if let Some(time_stats) = time_stats {
-            time_stats.add("query", end - begin);
+            time_stats.add("query_online", end - begin);
         }
-        (compressed_rlwe, compressed_gsw)
+        (compressed_rlwes, compressed_gsw)
     }
 
     fn answer_one(
@@ -973,54 +2292,155 @@ respire_impl!(Respire, {
         qk: Option<&<Self as PIR>::QueryKey>,
         mut time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOne {
-        // Query expansion
-        let (rlwes, gsws_fold, gsws_rot) =
-            Self::answer_query_unpack(pp, q, qk, time_stats.as_deref_mut());
-        let rlwe_saved = rlwes[0].clone();
+        let expanded = Self::answer_query_unpack(pp, q, qk, time_stats.as_deref_mut());
+        Self::answer_one_expanded(pp, db, &expanded, qk, time_stats)
+    }
+
+    ///
+    /// Answers one query against several databases encoded with the same parameters ("planes"),
+    /// e.g. the columns of a table sharing one index space. The query is expanded once, and its
+    /// first dimension ciphertexts and fold/rotate GSW encodings are reused for every plane.
+    /// Returns one compressed answer per plane, in order.
+    ///
+    fn answer_one_planes(
+        pp: &<Self as PIR>::PublicParams,
+        planes: &[<Self as PIR>::Database],
+        q: &<Self as Respire>::QueryOne,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::AnswerOneCompressed> {
+        let expanded = Self::answer_query_unpack(pp, q, qk, time_stats.as_deref_mut());
+        planes
+            .iter()
+            .map(|plane| {
+                let answer =
+                    Self::answer_one_expanded(pp, plane, &expanded, qk, time_stats.as_deref_mut());
+                Self::answer_compress_chunk(pp, &[answer], qk, time_stats.as_deref_mut())
+            })
+            .collect_vec()
+    }
+
+    ///
+    /// Same as `answer_one_planes`, for a full (batched) query. Returns one response per plane,
+    /// each of which extracts like a response from `answer`.
+    ///
+    fn answer_planes(
+        pp: &<Self as PIR>::PublicParams,
+        planes: &[<Self as PIR>::Database],
+        qs: &<Self as PIR>::Query,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as PIR>::Response> {
+        assert_eq!(qs.len(), Self::BATCH_SIZE.div_ceil(Self::QUERY_PACK_SIZE));
+        let mut answers = (0..planes.len())
+            .map(|_| Vec::with_capacity(Self::BATCH_SIZE))
+            .collect_vec();
+        for q in qs {
+            for expanded in Self::answer_query_unpack_packed(pp, q, qk, time_stats.as_deref_mut()) {
+                for (plane, plane_answers) in planes.iter().zip(answers.iter_mut()) {
+                    plane_answers.push(Self::answer_one_expanded(
+                        pp,
+                        plane,
+                        &expanded,
+                        qk,
+                        time_stats.as_deref_mut(),
+                    ));
+                }
+            }
+        }
+        answers
+            .iter()
+            .map(|plane_answers| {
+                assert_eq!(plane_answers.len(), Self::BATCH_SIZE);
+                plane_answers
+                    .chunks(Self::RESPONSE_CHUNK_SIZE)
+                    .map(|chunk| {
+                        Self::answer_compress_chunk(pp, chunk, qk, time_stats.as_deref_mut())
+                    })
+                    .collect_vec()
+            })
+            .collect_vec()
+    }
+
+    ///
+    /// Expands a packed query in one tree, and splits the result into one expanded query per
+    /// index. The number of indices is read off the length of the query.
+    ///
+    fn answer_query_unpack_packed(
+        pp: &<Self as PIR>::PublicParams,
+        (cs_reg, c_gsw): &<Self as Respire>::QueryPacked,
+        _: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::QueryOneExpanded> {
+        let ((auto_params_short, auto_params_long), rlwe_to_gsw_params, _, _) = &**pp;
+        let rlwe_total = cs_reg
+            .iter()
+            .map(|(_, vec_reg)| vec_reg.len())
+            .sum::<usize>();
+        let count = rlwe_total / Self::RLWE_COUNT;
+        assert!((1..=Self::QUERY_PACK_SIZE).contains(&count));
+        assert_eq!(rlwe_total, count * Self::RLWE_COUNT);
+        assert_eq!(c_gsw.1.len(), count * Self::GSW_COUNT);
+
+        let i0 = Instant::now();
+        let c_rlwes = cs_reg
+            .iter()
+            .flat_map(|c_reg| {
+                Self::answer_query_expand::<T_PROJ_SHORT, Z_PROJ_SHORT, DROP_PROJ_SHORT>(
+                    c_reg,
+                    auto_params_short,
+                )
+            })
+            .collect_vec();
+        assert_eq!(c_rlwes.len(), rlwe_total);
 
         let i1 = Instant::now();
+        let c_gsws = Self::answer_query_expand::<T_PROJ_LONG, Z_PROJ_LONG, DROP_PROJ_LONG>(
+            c_gsw,
+            auto_params_long,
+        );
 
-        // First dimension
-        let c_firstdim = Self::answer_first_dim(db, &rlwes);
-        let firstdim_saved = c_firstdim[0].clone(); // save for noise logging
         let i2 = Instant::now();
+        let mut c_gsws_iter = c_gsws
+            .chunks_exact(T_GSW)
+            .map(|cs| Self::gsw_prepare(&Self::rlwe_to_gsw(rlwe_to_gsw_params, cs)));
+        let mut c_rlwes_iter = c_rlwes.into_iter();
+
+        let expanded = (0..count)
+            .map(|_| {
+                let c_rlwes = (&mut c_rlwes_iter).take(Self::RLWE_COUNT).collect_vec();
+                let c_gsws_fold = (&mut c_gsws_iter).take(Self::GSW_FOLD_COUNT).collect_vec();
+                let c_gsws_rot = (&mut c_gsws_iter).take(Self::GSW_ROT_COUNT).collect_vec();
+                (c_rlwes, c_gsws_fold, c_gsws_rot)
+            })
+            .collect_vec();
+        assert_eq!(c_gsws_iter.next(), None);
 
-        // Folding
-        let c_fold = Self::answer_fold(c_firstdim, gsws_fold.as_slice());
         let i3 = Instant::now();
 
-        // Rotate select
-        let c_rot = Self::answer_rotate(&c_fold, gsws_rot.as_slice());
-        let i4 = Instant::now();
-
-        // Project
-        let c_proj = Self::answer_project(pp, &c_rot);
-        let i5 = Instant::now();
-
         if let Some(time_stats) = time_stats {
-            time_stats.add("answer_first_dim", i2 - i1);
-            time_stats.add("answer_fold", i3 - i2);
-            time_stats.add("answer_rotate", i4 - i3);
-            time_stats.add("answer_project", i5 - i4);
+            time_stats.add("answer_query_expand_reg", i1 - i0);
+            time_stats.add("answer_query_expand_gsw", i2 - i1);
+            time_stats.add("answer_query_expand_reg_to_gsw", i3 - i2);
         }
 
-        if let Some((s_enc, _, _)) = qk {
-            if log_enabled!(Info) {
-                let e_rlwe = Self::noise_subgaussian_bits(s_enc, &rlwe_saved);
-                let e_firstdim = Self::noise_subgaussian_bits(s_enc, &firstdim_saved);
-                let e_fold = Self::noise_subgaussian_bits(s_enc, &c_fold);
-                let e_rot = Self::noise_subgaussian_bits(s_enc, &c_rot);
-                let e_proj = Self::noise_subgaussian_bits(s_enc, &c_proj);
-
-                info!("measured noise query expanded rlwe: {}", e_rlwe);
-                info!("measured noise first dim: {}", e_firstdim);
-                info!("measured noise fold: {}", e_fold);
-                info!("measured noise rotate: {}", e_rot);
-                // TODO: note that project noise is lower on the coefficients that are projected away. So reporting this average is a bit inaccurate.
-                info!("measured noise project*: {}", e_proj);
-            }
+        // TODO measure and report noise through this phase? Difficult because need to know the exact encoding (since they are not rounded to q/p)
+        expanded
+    }
+
+    fn answer_one_expanded(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        expanded: &<Self as Respire>::QueryOneExpanded,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> <Self as Respire>::AnswerOne {
+        let mut noise = Vec::new();
+        let trace = (qk.is_some() && log_enabled!(Info)).then_some(&mut noise);
+        let c_proj = Self::answer_expanded_traced(pp, db, expanded, qk, time_stats, trace);
+        for (stage, bits) in noise {
+            info!("measured noise {}: {}", stage.name(), bits);
         }
-
         c_proj
     }
 
@@ -1031,19 +2451,7 @@ respire_impl!(Respire, {
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::AnswerOneCompressed {
         let begin = Instant::now();
-        let mut scalar_cts = Vec::with_capacity(Self::RESPONSE_CHUNK_SIZE);
-        let (_, _, _, vectorize_params) = pp;
-        for vec_idx in 0..N_VEC {
-            let mut scalar_ct = Matrix::zero();
-            for pack_idx in 0..Self::PACK_RATIO_RESPONSE {
-                let idx = vec_idx * Self::PACK_RATIO_RESPONSE + pack_idx;
-                if idx < chunk.len() {
-                    scalar_ct += &Self::rlwe_mul_x_pow(&chunk[idx], pack_idx * (D1 / D2));
-                }
-            }
-            scalar_cts.push(scalar_ct)
-        }
-        let vec = Self::scal_to_vec(vectorize_params, scalar_cts.as_slice().try_into().unwrap());
+        let vec = Self::answer_pack_chunk(pp, chunk);
         let compressed =
             Self::answer_compress_vec(pp, &vec, chunk.len().div_ceil(Self::PACK_RATIO_RESPONSE));
 
@@ -1061,26 +2469,53 @@ respire_impl!(Respire, {
         compressed
     }
 
+    ///
+    /// Packs a chunk of answers into a single vector RLWE encoding: up to `PACK_RATIO_RESPONSE`
+    /// answers per ring element, then scalar to vector conversion.
+    ///
+    fn answer_pack_chunk(
+        pp: &<Self as PIR>::PublicParams,
+        chunk: &[<Self as Respire>::AnswerOne],
+    ) -> <Self as Respire>::VecRLWEEncoding {
+        let (_, _, _, vectorize_params) = &**pp;
+        let mut scalar_cts = Vec::with_capacity(Self::RESPONSE_CHUNK_SIZE);
+        for vec_idx in 0..N_VEC {
+            let mut scalar_ct = Matrix::zero();
+            for pack_idx in 0..Self::PACK_RATIO_RESPONSE {
+                let idx = vec_idx * Self::PACK_RATIO_RESPONSE + pack_idx;
+                if idx < chunk.len() {
+                    scalar_ct[(0, 0)] += &chunk[idx][(0, 0)].mul_x_pow(pack_idx * (D1 / D2));
+                    scalar_ct[(1, 0)] += &chunk[idx][(1, 0)].mul_x_pow(pack_idx * (D1 / D2));
+                }
+            }
+            scalar_cts.push(scalar_ct)
+        }
+        Self::scal_to_vec(vectorize_params, scalar_cts.as_slice().try_into().unwrap())
+    }
+
     fn answer_compress_vec(
-        (_, _, (a_t, b_mat), _): &<Self as PIR>::PublicParams,
+        pp: &<Self as PIR>::PublicParams,
         (c_r, c_m): &<Self as Respire>::VecRLWEEncoding,
         truncate_len: usize,
     ) -> <Self as Respire>::AnswerOneCompressed {
-        let c_r = IntModCyclo::<D1, Q1>::from(c_r);
-        let c_m = c_m.map_ring(|r| IntModCyclo::<D1, Q1>::from(r));
-        let mut cr_scaled = IntModCyclo::zero();
+        let (_, _, (a_t, b_mat), _) = &**pp;
+        let c_r = c_r.to_coeff();
+        let c_m = c_m.map_ring::<<Self as Respire>::RingQ1, _>(|r| r.to_coeff());
+        let mut cr_scaled = IntModCyclo::<D1, Q2>::zero();
         for (cr_scaled_coeff, c0_coeff) in cr_scaled.coeff.iter_mut().zip(c_r.coeff) {
             let numer = Q2 as u128 * u64::from(c0_coeff) as u128;
             let denom = Q1 as u128;
             let div = (numer + denom / 2) / denom;
             *cr_scaled_coeff = IntMod::from(div as u64);
         }
-        let g_inv_cr_scaled = gadget_inverse_scalar::<_, Z_COMPRESS, T_COMPRESS>(&cr_scaled)
-            .map_ring(|x| IntModCycloEval::from(x));
+        let g_inv_cr_scaled =
+            gadget_inverse_scalar::<IntModCyclo<D1, Q2>, Z_COMPRESS, T_COMPRESS>(&cr_scaled)
+                .map_ring::<IntModCycloEval<D1, Q2>, _>(|x| IntModCycloEval::from(x));
         let c_r_hat: IntModCyclo<D2, Q2> =
             IntModCyclo::from(&(a_t * &g_inv_cr_scaled)[(0, 0)]).project_dim();
         let c_m_hat_trunc = {
-            let b_g_inv = (b_mat * &g_inv_cr_scaled).map_ring(|r| IntModCyclo::from(r));
+            let b_g_inv = (b_mat * &g_inv_cr_scaled)
+                .map_ring::<IntModCyclo<D1, Q2>, _>(|r| IntModCyclo::from(r));
             let mut result = vec![IntModCyclo::<D1, Q3>::zero(); truncate_len];
             for i in 0..truncate_len {
                 for (result_coeff, (c1_coeff, b_t_g_inv_coeff)) in result[i]
@@ -1114,6 +2549,366 @@ respire_impl!(Respire, {
         ret
     }
 
+    /// Like `extract_one`, but returns the records as elements mod `P`, e.g. for sums. See
+    /// `query_sum_one` for why a sum may reveal more than its value.
+    fn extract_sum_one(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<Self as Respire>::Record> {
+        let begin = Instant::now();
+        let ret = Self::extract_records_one(&Self::extract_ring_one(qk, r));
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("extract", end - begin);
+        }
+        ret
+    }
+
+    ///
+    /// Like `extract_one`, but also reports the rounding margins of each record (see
+    /// `RecordConfidence`), and flags records as likely corrupted according to `config`.
+    ///
+    fn extract_one_checked(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+        config: &ExtractConfig,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<(<Self as PIR>::RecordBytes, RecordConfidence)> {
+        let begin = Instant::now();
+        let unrounded = Self::extract_ring_one_unrounded(qk, r);
+        let records = Self::extract_bytes_one(
+            &unrounded.map_ring::<IntModCyclo<D2, P>, _>(|r| r.round_down_into()),
+        );
+
+        // Position of each coefficient within its rounding interval, as in `round_down_into`
+        let ratio = Q3 / P;
+        let margin = |x: &IntMod<Q3>| {
+            let offset = (u64::from(*x) + ratio / 2) % ratio;
+            2_f64 * min(offset, ratio - offset) as f64 / ratio as f64
+        };
+        let ret = records
+            .into_iter()
+            .enumerate()
+            .map(|(k, record)| {
+                let (i, j) = (k / Self::PACK_RATIO_RESPONSE, k % Self::PACK_RATIO_RESPONSE);
+                let margins = unrounded[(i, 0)]
+                    .coeff
+                    .iter()
+                    .skip(j)
+                    .step_by(D2 / D3)
+                    .map(margin)
+                    .collect_vec();
+                let min_margin = margins.iter().copied().fold(f64::INFINITY, f64::min);
+                let mean_margin = margins.iter().sum::<f64>() / margins.len() as f64;
+                let checksum_ok = config
+                    .checksum
+                    .then(|| record_checksum_ok(record.as_bytes()));
+                let likely_corrupted =
+                    min_margin < config.margin_threshold || checksum_ok == Some(false);
+                let confidence = RecordConfidence {
+                    min_margin,
+                    mean_margin,
+                    checksum_ok,
+                    likely_corrupted,
+                };
+                (record, confidence)
+            })
+            .collect_vec();
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("extract", end - begin);
+        }
+        ret
+    }
+
+    /// Same as `extract_one_checked`, for a full response, as in `extract`.
+    fn extract_checked(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as PIR>::Response,
+        config: &ExtractConfig,
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<(<Self as PIR>::RecordBytes, RecordConfidence)> {
+        let mut result = r
+            .iter()
+            .flat_map(|r_one| {
+                Self::extract_one_checked(qk, r_one, config, time_stats.as_deref_mut())
+            })
+            .collect_vec();
+        result.truncate(Self::BATCH_SIZE);
+        result
+    }
+
+    ///
+    /// Checks the shape of a query packing `num_indices` indices. Whether it encodes single
+    /// indices cannot be checked without the query key.
+    ///
+    fn validate_query_packed(
+        (cs_reg, (_, vec_gsw)): &<Self as Respire>::QueryPacked,
+        num_indices: usize,
+    ) -> Result<(), QueryError> {
+        let rlwe_total = num_indices * Self::RLWE_COUNT;
+        if cs_reg.len() != rlwe_total.div_ceil(D1) {
+            return Err(QueryError::RlweCiphertexts {
+                expected: rlwe_total.div_ceil(D1),
+                actual: cs_reg.len(),
+            });
+        }
+        for (ct_idx, (_, vec_reg)) in cs_reg.iter().enumerate() {
+            let expected = min(D1, rlwe_total - ct_idx * D1);
+            if vec_reg.len() != expected {
+                return Err(QueryError::RlweCount {
+                    expected,
+                    actual: vec_reg.len(),
+                });
+            }
+        }
+        if vec_gsw.len() != num_indices * Self::GSW_COUNT {
+            return Err(QueryError::GswCount {
+                expected: num_indices * Self::GSW_COUNT,
+                actual: vec_gsw.len(),
+            });
+        }
+        Ok(())
+    }
+
+    ///
+    /// Serializes the packed queries of a full batch, as `PIR::query` makes them: the 32 byte
+    /// seeds of all ciphertexts, then all their coefficients, `ceil(log2(Q1))` bits each. This is
+    /// the size `RespireParamsExpanded::query_size` counts. Lengths are implied by `BATCH_SIZE`,
+    /// so none are written.
+    ///
+    fn encode_query_packed(qs: &[<Self as Respire>::QueryPacked]) -> Vec<u8> {
+        let cts = || {
+            qs.iter()
+                .flat_map(|(cs_reg, c_gsw)| cs_reg.iter().chain(iter::once(c_gsw)))
+        };
+        let mut bytes = Vec::new();
+        for (seed, _) in cts() {
+            bytes.extend_from_slice(seed);
+        }
+        let mut writer = BitWriter::new(&mut bytes);
+        for (_, coeffs) in cts() {
+            for x in coeffs.iter() {
+                writer.write(u64::from(*x), ceil_log(2, Q1));
+            }
+        }
+        writer.finish();
+        bytes
+    }
+
+    fn decode_query_packed(mut bytes: &[u8]) -> io::Result<Vec<<Self as Respire>::QueryPacked>> {
+        // Coefficients per ciphertext of each packed query, the GSW ciphertext last
+        let layout = (0..Self::BATCH_SIZE)
+            .step_by(Self::QUERY_PACK_SIZE)
+            .map(|i| {
+                let rlwe_total =
+                    min(Self::QUERY_PACK_SIZE, Self::BATCH_SIZE - i) * Self::RLWE_COUNT;
+                (0..rlwe_total.div_ceil(D1))
+                    .map(|ct_idx| min(D1, rlwe_total - ct_idx * D1))
+                    .chain(iter::once(rlwe_total / Self::RLWE_COUNT * Self::GSW_COUNT))
+                    .collect_vec()
+            })
+            .collect_vec();
+        let mut seeds = Vec::new();
+        for _ in layout.iter().flatten() {
+            let mut seed = [0_u8; 32];
+            bytes.read_exact(&mut seed)?;
+            seeds.push(seed);
+        }
+        let mut seeds = seeds.into_iter();
+        let mut reader = BitReader::new(bytes);
+        let mut qs = Vec::with_capacity(layout.len());
+        for lens in layout {
+            let mut cts = Vec::with_capacity(lens.len());
+            for len in lens {
+                let coeffs = (0..len)
+                    .map(|_| reader.read_int_mod(ceil_log(2, Q1)))
+                    .collect::<io::Result<Vec<_>>>()?;
+                cts.push((seeds.next().unwrap(), coeffs));
+            }
+            let c_gsw = cts.pop().unwrap();
+            qs.push((cts, c_gsw));
+        }
+        if !reader.finish()?.is_empty() {
+            return Err(invalid_data("trailing bytes after query"));
+        }
+        Ok(qs)
+    }
+
+    ///
+    /// Serializes the compressed answers of a full batch, as `PIR::answer` makes them. Each answer
+    /// is written as its `Q2` part, then its `Q3` parts, with `ceil(log2(Q2))` resp.
+    /// `ceil(log2(Q3))` bits per coefficient, padded to a byte; see
+    /// `RespireParamsExpanded::response_one_size`.
+    ///
+    fn encode_answers_compressed(r: &[<Self as Respire>::AnswerOneCompressed]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (c_r, c_m) in r {
+            let mut writer = BitWriter::new(&mut bytes);
+            for x in c_r.coeff.iter() {
+                writer.write(u64::from(*x), ceil_log(2, Q2));
+            }
+            for x in c_m.iter().flat_map(|m| m.coeff.iter()) {
+                writer.write(u64::from(*x), ceil_log(2, Q3));
+            }
+            writer.finish();
+        }
+        bytes
+    }
+
+    fn decode_answers_compressed(
+        mut bytes: &[u8],
+    ) -> io::Result<Vec<<Self as Respire>::AnswerOneCompressed>> {
+        let mut r = Vec::new();
+        for i in (0..Self::BATCH_SIZE).step_by(Self::RESPONSE_CHUNK_SIZE) {
+            let chunk_len = min(Self::RESPONSE_CHUNK_SIZE, Self::BATCH_SIZE - i);
+            let mut reader = BitReader::new(bytes);
+            let mut c_r = IntModCyclo::<D2, Q2>::zero();
+            for x in c_r.coeff.iter_mut() {
+                *x = reader.read_int_mod(ceil_log(2, Q2))?;
+            }
+            let mut c_m =
+                vec![IntModCyclo::<D2, Q3>::zero(); chunk_len.div_ceil(Self::PACK_RATIO_RESPONSE)];
+            for x in c_m.iter_mut().flat_map(|m| m.coeff.iter_mut()) {
+                *x = reader.read_int_mod(ceil_log(2, Q3))?;
+            }
+            bytes = reader.finish()?;
+            r.push((c_r, c_m));
+        }
+        if !bytes.is_empty() {
+            return Err(invalid_data("trailing bytes after response"));
+        }
+        Ok(r)
+    }
+
+    ///
+    /// Serializes the public parameters, as uploaded by a client or spilled to disk by
+    /// `PublicParamsStore`. Ring elements are written as their evaluations, little endian.
+    ///
+    fn encode_public_params(pp: &<Self as PIR>::PublicParams) -> Vec<u8> {
+        let (
+            (auto_params_short, auto_params_long),
+            rlwe_to_gsw_params,
+            compress_params,
+            vectorize_params,
+        ) = &**pp;
+        let mut bytes = Vec::new();
+        write_len(&mut bytes, auto_params_short.len());
+        for (m, tau_power) in auto_params_short.iter() {
+            write_matrix(m, &mut bytes, Self::write_ring_q1);
+            write_len(&mut bytes, *tau_power);
+        }
+        write_len(&mut bytes, auto_params_long.len());
+        for (m, tau_power) in auto_params_long.iter() {
+            write_matrix(m, &mut bytes, Self::write_ring_q1);
+            write_len(&mut bytes, *tau_power);
+        }
+        write_matrix(rlwe_to_gsw_params, &mut bytes, Self::write_ring_q1);
+        write_matrix::<_, _, IntModCycloEval<D1, Q2>>(
+            &compress_params.0,
+            &mut bytes,
+            write_ring_eval,
+        );
+        write_matrix::<_, _, IntModCycloEval<D1, Q2>>(
+            &compress_params.1,
+            &mut bytes,
+            write_ring_eval,
+        );
+        write_len(&mut bytes, vectorize_params.len());
+        for (m0, m1) in vectorize_params.iter() {
+            write_matrix(m0, &mut bytes, Self::write_ring_q1);
+            write_matrix(m1, &mut bytes, Self::write_ring_q1);
+        }
+        bytes
+    }
+
+    ///
+    /// Inverse of `encode_public_params`. Fails on malformed input, e.g. unreduced values,
+    /// lengths that do not match the parameters, or automorphisms other than the ones `setup`
+    /// generates, but cannot detect well-formed tampering.
+    ///
+    fn decode_public_params(mut bytes: &[u8]) -> io::Result<<Self as PIR>::PublicParams> {
+        let bytes = &mut bytes;
+        let auto_iters = floor_log(2, D1 as u64);
+        // Both levels of automorphism keys use the same automorphisms as `setup`
+        let read_tau_power = |bytes: &mut &[u8], i: usize| {
+            if read_len(bytes)? != (D1 >> i) + 1 {
+                return Err(invalid_data("unexpected automorphism in public params"));
+            }
+            Ok((D1 >> i) + 1)
+        };
+        expect_len(bytes, auto_iters)?;
+        let auto_params_short = (0..auto_iters)
+            .map(|i| {
+                Ok((
+                    read_matrix(bytes, Self::read_ring_q1)?,
+                    read_tau_power(bytes, i)?,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        expect_len(bytes, auto_iters)?;
+        let auto_params_long = (0..auto_iters)
+            .map(|i| {
+                Ok((
+                    read_matrix(bytes, Self::read_ring_q1)?,
+                    read_tau_power(bytes, i)?,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let rlwe_to_gsw_params = read_matrix(bytes, Self::read_ring_q1)?;
+        let compress_params = (
+            read_matrix::<_, _, IntModCycloEval<D1, Q2>>(bytes, read_ring_eval)?,
+            read_matrix::<_, _, IntModCycloEval<D1, Q2>>(bytes, read_ring_eval)?,
+        );
+        expect_len(bytes, N_VEC)?;
+        let vectorize_params = (0..N_VEC)
+            .map(|_| {
+                Ok((
+                    read_matrix(bytes, Self::read_ring_q1)?,
+                    read_matrix(bytes, Self::read_ring_q1)?,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if !bytes.is_empty() {
+            return Err(invalid_data("trailing bytes after public params"));
+        }
+        Ok(Arc::new((
+            (auto_params_short, auto_params_long),
+            rlwe_to_gsw_params,
+            compress_params,
+            vectorize_params,
+        )))
+    }
+
+    ///
+    /// Bytes the public parameters take in memory. Each evaluation is a `u64` in memory, so this
+    /// is about twice the length of `encode_public_params`.
+    ///
+    fn public_params_memory_size(pp: &<Self as PIR>::PublicParams) -> usize {
+        let (
+            (auto_params_short, auto_params_long),
+            rlwe_to_gsw_params,
+            (a_t, b_mat),
+            vectorize_params,
+        ) = &**pp;
+        auto_params_short
+            .iter()
+            .map(|(m, _)| matrix_bytes(m))
+            .sum::<usize>()
+            + auto_params_long
+                .iter()
+                .map(|(m, _)| matrix_bytes(m))
+                .sum::<usize>()
+            + matrix_bytes(rlwe_to_gsw_params)
+            + matrix_bytes::<1, T_COMPRESS, IntModCycloEval<D1, Q2>>(a_t)
+            + matrix_bytes::<N_VEC, T_COMPRESS, IntModCycloEval<D1, Q2>>(b_mat)
+            + vectorize_params
+                .iter()
+                .map(|(m0, m1)| matrix_bytes(m0) + matrix_bytes(m1))
+                .sum::<usize>()
+    }
+
     fn params() -> RespireParamsExpanded {
         RespireParamsExpanded {
             Q1,
@@ -1125,10 +2920,13 @@ respire_impl!(Respire, {
             M_GSW,
             Z_PROJ_SHORT,
             T_PROJ_SHORT,
+            DROP_PROJ_SHORT,
             Z_PROJ_LONG,
             T_PROJ_LONG,
+            DROP_PROJ_LONG,
             Z_RLWE_TO_GSW,
             T_RLWE_TO_GSW,
+            DROP_RLWE_TO_GSW,
             Z_VECTORIZE,
             T_VECTORIZE,
             BATCH_SIZE,
@@ -1154,238 +2952,243 @@ respire_impl!(Respire, {
     }
 
     fn params_error_rate_estimate() -> f64 {
-        info!("*** Error estimates (bits) ***");
-        // We use square subgaussian widths as units
-        let error_width_sq: f64 = ((ERROR_WIDTH_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let error_width_vec_sq: f64 = ((ERROR_WIDTH_VEC_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let error_width_switch_sq: f64 =
-            ((ERROR_WIDTH_COMPRESS_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        let secret_bound_sq: f64 = (SECRET_BOUND as f64).powi(2);
-        let secret_width_vec_sq: f64 =
-            ((SECRET_WIDTH_VEC_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-        // let secret_width_switch_sq: f64 =
-        //     ((SECRET_WIDTH_COMPRESS_MILLIONTHS as f64) / 1_000_000_f64).powi(2);
-
-        let log_d: usize = ceil_log(2, D1 as u64);
-
-        let e_to_bits = |e: f64| -> f64 { e.log2() / 2_f64 };
-
-        let gadget_factor = |t: usize, z: u64| -> f64 {
-            assert!(z >= 2);
-
-            let z_factor = match z {
-                2 => {
-                    // With probability <= 2^(-48.421), a random binary gadget will have <= 1200 equal 1
-                    // N(log(1 - sum(binomial(2048, x) * (1/2)^2048, x, 0, 1200), 2)) = -48.4216972197488
-                    const BINARY_FACTOR: f64 = 1200_f64 / 2048_f64;
-                    BINARY_FACTOR
-                }
-                // 3 => {
-                //     // With probability <= 2^(-48.506), a random ternary gadget will have <= 1528 coefficients equal +- 1
-                //     // N(log(1 - sum(binomial(2048, x) * (2/3)^x * (1/3)^(2048-x), x, 0, 1528), 2)) = -48.5067228501629
-                //     const TERNARY_FACTOR: f64 = 1528_f64 / 2048_f64;
-                //     TERNARY_FACTOR
-                // }
-                _ => {
-                    // TODO noise: verify this factor is right
-                    // const CHERNOFF_FACTOR: f64 = 0.6_f64;
-                    const CHERNOFF_FACTOR: f64 = 1.0_f64;
-                    ((z / 2) as f64).powi(2) * CHERNOFF_FACTOR
-                }
-            };
+        Self::params().error_rate_estimate()
+    }
 
-            (t as f64) * z_factor
-        };
+    fn params_public_param_size() -> usize {
+        Self::params().public_param_size()
+    }
 
-        let select_noise = |e_gsw_sq: f64, e_reg_sq: f64, depth: usize| -> f64 {
-            // m = 2t; t is absorbed into gadget_factor()
-            e_reg_sq + (depth as f64) * 2_f64 * (D1 as f64) * gadget_factor(T_GSW, Z_GSW) * e_gsw_sq
-        };
+    fn params_query_one_size() -> usize {
+        Self::params().query_one_size()
+    }
 
-        let proj_noise = |e_sq: f64, t_auto: usize, z_auto: u64, depth: usize| -> f64 {
-            let ct = (4usize.pow(depth as u32) - 1) / 3;
-            e_sq + ct as f64 * (D1 as f64) * gadget_factor(t_auto, z_auto) * error_width_sq
-        };
+    fn params_record_one_size() -> usize {
+        Self::params().record_one_size()
+    }
 
-        info!("Initial: {}", e_to_bits(error_width_sq));
+    fn params_response_one_size(trunc_len: usize) -> usize {
+        Self::params().response_one_size(trunc_len)
+    }
+});
 
-        // Query expansion
-        let e_reg = proj_noise(error_width_sq, T_PROJ_SHORT, Z_PROJ_SHORT, log_d);
-        info!("Query expand rlwe: {}", e_to_bits(e_reg));
-        let e_gsw_raw = proj_noise(error_width_sq, T_PROJ_LONG, Z_PROJ_LONG, log_d);
-        info!("Query expand GSW (raw): {}", e_to_bits(e_gsw_raw));
-        let e_gsw = {
-            // RLWE to GSW
-            let initial_component = (D1 as f64) * e_gsw_raw * secret_bound_sq;
-            let gadget_component =
-                // m = 2t; t is absorbed into gadget_factor()
-                2_f64 * (D1 as f64) * gadget_factor(T_RLWE_TO_GSW, Z_RLWE_TO_GSW) * error_width_sq;
-            let e_converted = initial_component + gadget_component;
-            info!(
-                "    RLWE to GSW initial component: {}",
-                e_to_bits(initial_component),
-            );
-            info!(
-                "    RLWE to GSW gadget component: {}",
-                e_to_bits(gadget_component),
-            );
-            e_converted
-        };
-        info!("Query expand GSW (converted): {}", e_to_bits(e_gsw));
+respire_impl!(ShardRespire, {
+    fn shard_columns(
+        db: &<Self as PIR>::Database,
+        num_shards: usize,
+    ) -> Vec<<Self as PIR>::Database> {
+        let num_columns = db.slab_len() / Self::PACKED_DIM1_SIZE;
+        assert!((1..=num_columns).contains(&num_shards));
+        let ranges = (0..num_shards)
+            .map(|k| (k * num_columns / num_shards)..((k + 1) * num_columns / num_shards))
+            .collect_vec();
 
-        // First dimension (NU1)
-        let e_firstdim =
-            (Self::PACKED_DIM1_SIZE as f64) * (D1 as f64) * ((P / 2) as f64).powi(2) * e_reg;
-        info!("First dimension: {}", e_to_bits(e_firstdim));
+        let mut shards = ranges
+            .iter()
+            .map(|range| Vec::with_capacity(db.num_slabs() * range.len() * Self::PACKED_DIM1_SIZE))
+            .collect_vec();
+        db.scan(&mut |_, slab| {
+            for (shard, range) in shards.iter_mut().zip(ranges.iter()) {
+                shard.extend_from_slice(
+                    &slab[range.start * Self::PACKED_DIM1_SIZE..range.end * Self::PACKED_DIM1_SIZE],
+                );
+            }
+        })
+        .expect("failed to read the database");
 
-        // Folding (NU2)
-        let e_fold = select_noise(e_gsw, e_firstdim, Self::NU2);
-        info!("Fold: {}", e_to_bits(e_fold));
+        shards
+            .into_iter()
+            .zip(ranges.iter())
+            .map(|(words, range)| {
+                Box::new(InMemoryStorage::new(
+                    words,
+                    range.len() * Self::PACKED_DIM1_SIZE,
+                )) as <Self as PIR>::Database
+            })
+            .collect_vec()
+    }
 
-        // Rotating (NU3)
-        let e_rot = select_noise(e_gsw, e_fold, Self::NU3);
-        info!("Rotate select: {}", e_to_bits(e_rot));
+    fn expanded_first_dim(
+        (rlwes, _, _): &<Self as Respire>::QueryOneExpanded,
+    ) -> &[<Self as Respire>::RLWEEncoding] {
+        rlwes
+    }
 
-        // Proj/select (NU3) + ring packing
-        let e_proj_component = proj_noise(0_f64, T_PROJ_LONG, Z_PROJ_LONG, Self::NU3);
-        info!(
-            "    Projection *new* error component: {}",
-            e_to_bits(e_proj_component)
-        );
-        let ring_num_records = min(Self::BATCH_SIZE, Self::PACK_RATIO_RESPONSE);
-        let e_pack_ring = e_rot + e_proj_component * ring_num_records as f64;
-        info!(
-            "Ring packing ({} record(s)): {}",
-            ring_num_records,
-            e_to_bits(e_pack_ring)
-        );
+    fn answer_shard(
+        db: &<Self as PIR>::Database,
+        rlwes: &[<Self as Respire>::RLWEEncoding],
+    ) -> io::Result<Vec<<Self as Respire>::RLWEEncoding>> {
+        Self::answer_first_dim(db, rlwes)
+    }
 
-        // Vector packing
-        let e_pack_vec = {
-            // Scalar to vector conversion
-            let e_init = e_pack_ring;
-            let vec_num_elems = min(
-                Self::BATCH_SIZE.div_ceil(Self::PACK_RATIO_RESPONSE),
-                Self::N_VEC,
-            );
-            let e_extra = vec_num_elems as f64
-                * (D1 as f64)
-                * gadget_factor(T_VECTORIZE, Z_VECTORIZE)
-                * error_width_vec_sq;
-            info!(
-                "    Vector packing *new* error component ({} ring elem(s)): {}",
-                vec_num_elems,
-                e_to_bits(e_extra),
-            );
-            let e_full = e_init + e_extra;
-            info!("Vector packing: {}", e_to_bits(e_full));
-            e_full
-        };
+    fn answer_finish(
+        pp: &<Self as PIR>::PublicParams,
+        first_dim: Vec<<Self as Respire>::RLWEEncoding>,
+        (_, gsws_fold, gsws_rot): <Self as Respire>::QueryOneExpanded,
+    ) -> <Self as Respire>::AnswerOne {
+        assert_eq!(first_dim.len(), Self::PACKED_DIM2_SIZE);
+        let c_fold = Self::answer_fold(first_dim, gsws_fold.as_slice());
+        let c_rot = Self::answer_rotate(&c_fold, gsws_rot.as_slice());
+        Self::answer_project(pp, &c_rot)
+    }
 
-        let e_preswitch = e_pack_vec;
-        info!("***");
-        info!(
-            "Preswitch noise: {:.3} total bits; approx {:.3} of margin",
-            e_to_bits(e_preswitch),
-            (Q1 as f64).log2() - (P as f64).log2() - e_to_bits(e_preswitch) - 3_f64 // 3 bits = 8 widths
-        );
-        assert_eq!(Z_COMPRESS, 2);
-        let e_preswitch = 8_f64 * e_preswitch;
-
-        let e_subg_preswitch = e_preswitch * (Q3 as f64).powi(2) / (Q1 as f64).powi(2);
-        let e_subg_gadget = (Q3 as f64).powi(2) / (4_f64 * (Q2 as f64).powi(2))
-            * ((D1 as f64) * secret_width_vec_sq
-                + 4_f64
-                    * (D1 as f64)
-                    * gadget_factor(T_COMPRESS, Z_COMPRESS)
-                    * error_width_switch_sq);
-        let e_subg = e_subg_preswitch + e_subg_gadget;
-        let e_round = (2f64 + Q3 as f64 / Q1 as f64 * (Q1 % P) as f64) / 2f64;
-        let threshold = Q3 / (2 * P);
+    fn write_rlwe(c: &<Self as Respire>::RLWEEncoding, w: &mut dyn Write) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(4 * D1 * size_of::<u32>());
+        Self::write_ring_q1(&c[(0, 0)], &mut bytes);
+        Self::write_ring_q1(&c[(1, 0)], &mut bytes);
+        w.write_all(&bytes)
+    }
 
-        info!(
-            "Switch rounding term noise bound (absolute / threshold): {} / {}",
-            e_round, threshold
-        );
+    fn read_rlwe(r: &mut dyn Read) -> io::Result<<Self as Respire>::RLWEEncoding> {
+        let mut bytes = vec![0_u8; 4 * D1 * size_of::<u32>()];
+        r.read_exact(&mut bytes)?;
+        let mut bytes = bytes.as_slice();
+        let mut c = <Self as Respire>::RLWEEncoding::zero();
+        c[(0, 0)] = Self::read_ring_q1(&mut bytes)?;
+        c[(1, 0)] = Self::read_ring_q1(&mut bytes)?;
+        Ok(c)
+    }
+});
 
-        info!("Switch subgaussian term noise widths (absolute / threshold):");
+respire_impl!({
+    /// Number of selections of a fold level done together; bounds the decomposition scratch space.
+    pub const FOLD_BATCH_SIZE: usize = 8;
+
+    /// Checks that a query has the shape the server expects, before answering it.
+    pub fn validate_query(qs: &<Self as PIR>::Query) -> Result<(), QueryError> {
+        let num_packed = Self::BATCH_SIZE.div_ceil(Self::QUERY_PACK_SIZE);
+        if qs.len() != num_packed {
+            return Err(QueryError::BatchSize {
+                expected: num_packed,
+                actual: qs.len(),
+            });
+        }
+        qs.iter().enumerate().try_for_each(|(i, q)| {
+            let num_indices = min(
+                Self::QUERY_PACK_SIZE,
+                Self::BATCH_SIZE - i * Self::QUERY_PACK_SIZE,
+            );
+            Self::validate_query_packed(q, num_indices)
+        })
+    }
 
-        info!(
-            "    preswitch: {:.3} / {}",
-            e_subg_preswitch.sqrt(),
-            threshold
-        );
-        info!("    gadget: {:.3} / {}", e_subg_gadget.sqrt(), threshold);
-        info!("    total: {:.3} / {}", e_subg.sqrt(), threshold);
+    /// The first dimension selection and last dimensions index of database index `idx`.
+    fn index_selection(idx: usize) -> (Vec<bool>, usize) {
+        assert!(idx < Self::DB_SIZE);
+        let last_dims_size = 2usize.pow((Self::NU2 + Self::NU3) as u32);
+        let (idx_i, idx_j) = (idx / last_dims_size, idx % last_dims_size);
+        let rows = (0..Self::RLWE_COUNT).map(|i| i == idx_i).collect_vec();
+        (rows, idx_j)
+    }
 
-        use std::f64::consts::PI;
-        let error_rate = 2_f64
-            * (D3 as f64)
-            * f64::exp(-PI * (0.5_f64 * (Q3 / P) as f64 - e_round).powi(2) / e_subg);
+    /// Appends the evaluations of `r` as little endian `u32`s; `Q1A` and `Q1B` fit in a `u32`.
+    fn write_ring_q1(r: &<Self as Respire>::RingQ1Fast, bytes: &mut Vec<u8>) {
+        let (lo, hi) = r.residues();
+        for x in lo.iter().chain(hi.iter()) {
+            bytes.extend_from_slice(&(*x as u32).to_le_bytes());
+        }
+    }
 
-        info!("Error rate: 2^({})", error_rate.log2());
-        info!("***");
+    fn read_ring_q1(bytes: &mut &[u8]) -> io::Result<<Self as Respire>::RingQ1Fast> {
+        let mut r = <Self as Respire>::RingQ1Fast::zero();
+        let (lo, hi) = r.residues_mut();
+        for x in lo.iter_mut() {
+            *x = u64::from(read_int_mod::<Q1A>(bytes, size_of::<u32>())?);
+        }
+        for x in hi.iter_mut() {
+            *x = u64::from(read_int_mod::<Q1B>(bytes, size_of::<u32>())?);
+        }
+        Ok(r)
+    }
 
-        clamp(error_rate, 0_f64, 1_f64)
+    ///
+    /// Same as `answer_one`, but if `trace` is given, the noise after each stage is measured with
+    /// the query key and appended to it, as `log2` subgaussian widths.
+    ///
+    pub fn answer_one_traced(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        q: &<Self as Respire>::QueryOne,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        mut time_stats: Option<&mut Stats<Duration>>,
+        trace: Option<&mut Vec<(NoiseStage, f64)>>,
+    ) -> <Self as Respire>::AnswerOne {
+        let expanded = Self::answer_query_unpack(pp, q, qk, time_stats.as_deref_mut());
+        Self::answer_expanded_traced(pp, db, &expanded, qk, time_stats, trace)
     }
 
-    fn params_public_param_size() -> usize {
-        let automorph_elems = floor_log(2, D1 as u64) * (T_PROJ_SHORT + T_PROJ_LONG);
-        let reg_to_gsw_elems = 2 * T_RLWE_TO_GSW;
-        let scal_to_vec_elems = N_VEC * T_VECTORIZE;
-        let q_elem_size = D1 * ceil_log(2, Q1) / 8;
+    ///
+    /// Same as `answer_one_expanded`, traced as in `answer_one_traced`.
+    ///
+    pub fn answer_expanded_traced(
+        pp: &<Self as PIR>::PublicParams,
+        db: &<Self as PIR>::Database,
+        (rlwes, gsws_fold, gsws_rot): &<Self as Respire>::QueryOneExpanded,
+        qk: Option<&<Self as PIR>::QueryKey>,
+        time_stats: Option<&mut Stats<Duration>>,
+        trace: Option<&mut Vec<(NoiseStage, f64)>>,
+    ) -> <Self as Respire>::AnswerOne {
+        let trace = qk.zip(trace);
 
-        let compress_elems = N_VEC * T_COMPRESS;
-        let q2_elem_size = D1 * ceil_log(2, Q2) / 8;
+        let rlwe_saved = trace.is_some().then(|| rlwes[0].clone());
 
-        // This code assumes we implement use PRG trick to compress the randomness components of the
-        // public params. Technically though, this hasn't been implemented.
+        let i1 = Instant::now();
 
-        info!(
-            "automorph pp: {:.3} KiB",
-            (automorph_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "rlwe to GSW pp: {:.3} KiB",
-            (reg_to_gsw_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "scal to vec pp: {:.3} KiB",
-            (scal_to_vec_elems * q_elem_size) as f64 / 1024_f64
-        );
-        info!(
-            "compress pp: {:.3} KiB",
-            (compress_elems * q2_elem_size) as f64 / 1024_f64
-        );
-        return (automorph_elems + reg_to_gsw_elems + scal_to_vec_elems) * q_elem_size
-            + compress_elems * q2_elem_size;
-    }
+        // First dimension; `PIR::answer` has no way to report a failed read
+        let c_firstdim = Self::answer_first_dim(db, rlwes).expect("failed to read the database");
+        let firstdim_saved = trace.is_some().then(|| c_firstdim[0].clone());
+        let i2 = Instant::now();
+
+        // Folding
+        let c_fold = Self::answer_fold(c_firstdim, gsws_fold.as_slice());
+        let i3 = Instant::now();
 
-    fn params_query_one_size() -> usize {
-        (Self::RLWE_COUNT + Self::GSW_COUNT) * ceil_log(2, Q1) / 8
-    }
+        // Rotate select
+        let c_rot = Self::answer_rotate(&c_fold, gsws_rot.as_slice());
+        let i4 = Instant::now();
 
-    fn params_record_one_size() -> usize {
-        let log_p = floor_log(2, P);
-        D3 * log_p / 8
-    }
+        // Project
+        let c_proj = Self::answer_project(pp, &c_rot);
+        let i5 = Instant::now();
 
-    fn params_response_one_size(trunc_len: usize) -> usize {
-        // Technically we can do ceil(d * (log(q2) + len * log(q1)) by packing into a single large integer.
-        // But for simplicity assume each IntMod<Q1> / IntMod<Q2> is serialized individually.
-        let log_q1 = ceil_log(2, Q3);
-        let log_q2 = ceil_log(2, Q2);
-        ((D2 as f64) * (log_q2 as f64 + (trunc_len as f64) * log_q1 as f64) / 8_f64).ceil() as usize
+        if let Some(time_stats) = time_stats {
+            time_stats.add("answer_first_dim", i2 - i1);
+            time_stats.add("answer_fold", i3 - i2);
+            time_stats.add("answer_rotate", i4 - i3);
+            time_stats.add("answer_project", i5 - i4);
+        }
+
+        if let Some(((s_enc, _, _), trace)) = trace {
+            let measured = [
+                (NoiseStage::QueryExpandRlwe, rlwe_saved.as_ref().unwrap()),
+                (NoiseStage::FirstDim, firstdim_saved.as_ref().unwrap()),
+                (NoiseStage::Fold, &c_fold),
+                (NoiseStage::Rotate, &c_rot),
+                (NoiseStage::Project, &c_proj),
+            ];
+            for (stage, c) in measured {
+                trace.push((stage, Self::noise_subgaussian_bits(s_enc, c)));
+            }
+        }
+
+        c_proj
     }
-});
 
-respire_impl!({
     pub fn extract_ring_one(
+        qk: &<Self as PIR>::QueryKey,
+        r: &<Self as Respire>::AnswerOneCompressed,
+    ) -> <Self as Respire>::RecordPackedSmall {
+        Self::extract_ring_one_unrounded(qk, r)
+            .map_ring::<IntModCyclo<D2, P>, _>(|r| r.round_down_into())
+    }
+
+    /// The decryption of a response mod `Q3`, before rounding to `P`.
+    pub fn extract_ring_one_unrounded(
         (_, _, s_small): &<Self as PIR>::QueryKey,
         (c_r_hat, c_m_hat_trunc): &<Self as Respire>::AnswerOneCompressed,
-    ) -> <Self as Respire>::RecordPackedSmall {
-        let neg_s_small_cr =
-            (-&(s_small * &IntModCycloEval::from(c_r_hat))).map_ring(|r| IntModCyclo::from(r));
+    ) -> Matrix<N_VEC, 1, IntModCyclo<D2, Q3>> {
+        let neg_s_small_cr = (-&(s_small * &IntModCycloEval::from(c_r_hat)))
+            .map_ring::<IntModCyclo<D2, Q2>, _>(|r| IntModCyclo::from(r));
         let mut result = Matrix::<N_VEC, 1, IntModCyclo<D2, Q3>>::zero();
         for i in 0..c_m_hat_trunc.len() {
             for (result_coeff, neg_s_small_c0_coeff) in result[(i, 0)]
@@ -1400,12 +3203,12 @@ respire_impl!({
             }
             result[(i, 0)] += &c_m_hat_trunc[i];
         }
-        result.map_ring(|r| r.round_down_into())
+        result
     }
 
-    pub fn extract_bytes_one(
+    pub fn extract_records_one(
         r: &<Self as Respire>::RecordPackedSmall,
-    ) -> Vec<<Self as PIR>::RecordBytes> {
+    ) -> Vec<<Self as Respire>::Record> {
         let mut result = Vec::with_capacity(Self::RESPONSE_CHUNK_SIZE);
         for i in 0..N_VEC {
             for j in 0..Self::PACK_RATIO_RESPONSE {
@@ -1418,106 +3221,163 @@ respire_impl!({
                     .collect_vec()
                     .try_into()
                     .unwrap();
-                result.push(RecordBytesImpl {
-                    it: Self::decode_record(&IntModCyclo::from(record_coeffs)),
-                });
+                result.push(IntModCyclo::from(record_coeffs));
             }
         }
         result
     }
 
+    pub fn extract_bytes_one(
+        r: &<Self as Respire>::RecordPackedSmall,
+    ) -> Vec<<Self as PIR>::RecordBytes> {
+        Self::extract_records_one(r)
+            .iter()
+            .map(|record| RecordBytesImpl {
+                it: Self::decode_record(record),
+            })
+            .collect()
+    }
+
     pub fn answer_query_unpack(
-        ((auto_params_short, auto_params_long), rlwe_to_gsw_params, _, _): &<Self as PIR>::PublicParams,
-        ((seed_reg, vec_reg), (seed_gsw, vec_gsw)): &<Self as Respire>::QueryOne,
-        _: Option<&<Self as PIR>::QueryKey>,
+        pp: &<Self as PIR>::PublicParams,
+        q: &<Self as Respire>::QueryOne,
+        qk: Option<&<Self as PIR>::QueryKey>,
         time_stats: Option<&mut Stats<Duration>>,
     ) -> <Self as Respire>::QueryOneExpanded {
-        let inv = <Self as Respire>::RingQ1Fast::from(mod_inverse(D1 as u64, Q1));
-        let mut c_rlwes = {
-            let mut c1_reg = IntModCyclo::zero();
-            for (i, coeff) in vec_reg.iter().copied().enumerate() {
-                c1_reg.coeff[reverse_bits_fast::<D1>(i)] = coeff;
-            }
-            let mut c_reg = Self::rlwe_recover_from_seeded((
-                *seed_reg,
-                <Self as Respire>::RingQ1Fast::from(&c1_reg),
-            ));
-            c_reg[(0, 0)] *= &inv;
-            c_reg[(1, 0)] *= &inv;
-            vec![c_reg]
-        };
-
-        let mut c_gsws = {
-            let mut c1_gsw = IntModCyclo::zero();
-            for (i, coeff) in vec_gsw.iter().copied().enumerate() {
-                c1_gsw.coeff[reverse_bits_fast::<D1>(i)] = coeff;
-            }
-            let mut c_gsw = Self::rlwe_recover_from_seeded((
-                *seed_gsw,
-                <Self as Respire>::RingQ1Fast::from(&c1_gsw),
-            ));
-            c_gsw[(0, 0)] *= &inv;
-            c_gsw[(1, 0)] *= &inv;
-            vec![c_gsw]
-        };
+        let mut expanded = Self::answer_query_unpack_packed(pp, q, qk, time_stats);
+        assert_eq!(expanded.len(), 1);
+        expanded.pop().unwrap()
+    }
 
-        assert_eq!(1 << auto_params_short.len(), D1);
-        assert_eq!(1 << auto_params_long.len(), D1);
+    ///
+    /// Recovers a compressed query ciphertext and expands it into one ciphertext per transmitted
+    /// coefficient, in order. The tree always has depth `log2(D1)`.
+    ///
+    pub fn answer_query_expand<const LEN: usize, const BASE: u64, const DROP: usize>(
+        (seed, vec): &<Self as Respire>::RLWEEncodingCompressed,
+        auto_params: &[<Self as Respire>::AutoParams<LEN>],
+    ) -> Vec<<Self as Respire>::RLWEEncoding>
+    where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
+        assert!(vec.len() <= D1);
+        assert_eq!(1 << auto_params.len(), D1);
 
-        let i0 = Instant::now();
-        for (i, auto_params_short) in auto_params_short.iter().enumerate() {
-            c_rlwes = Self::do_proj_iter::<T_PROJ_SHORT, Z_PROJ_SHORT>(
-                i,
-                c_rlwes.as_slice(),
-                auto_params_short,
-            );
+        let inv = <Self as Respire>::RingQ1Fast::from(mod_inverse(D1 as u64, Q1));
+        let mut c1 = IntModCyclo::zero();
+        for (i, coeff) in vec.iter().copied().enumerate() {
+            c1.coeff[reverse_bits_fast::<D1>(i)] = coeff;
+        }
+        let mut c =
+            Self::rlwe_recover_from_seeded((*seed, <Self as Respire>::RingQ1Fast::from(&c1)));
+        c[(0, 0)] *= &inv;
+        c[(1, 0)] *= &inv;
+
+        let mut cs = vec![c];
+        for (i, auto_params) in auto_params.iter().enumerate() {
+            cs = Self::do_proj_iter::<LEN, BASE, DROP>(i, cs.as_slice(), auto_params);
             let denom = D1 >> (i + 1);
-            c_rlwes.truncate((Self::RLWE_COUNT + denom - 1) / denom);
+            cs.truncate(vec.len().div_ceil(denom));
         }
-        assert_eq!(c_rlwes.len(), Self::RLWE_COUNT);
+        assert_eq!(cs.len(), vec.len());
+        cs
+    }
 
-        let i1 = Instant::now();
-        for (i, auto_params_long) in auto_params_long.iter().enumerate() {
-            c_gsws = Self::do_proj_iter::<T_PROJ_LONG, Z_PROJ_LONG>(
-                i,
-                c_gsws.as_slice(),
-                auto_params_long,
-            );
-            let denom = D1 >> (i + 1);
-            c_gsws.truncate((Self::GSW_COUNT + denom - 1) / denom);
+    ///
+    /// Packs the `PACK_RATIO_DB` records of packed record `chunk_idx`, and returns the words
+    /// `encode_db` stores for it, one per evaluation point.
+    ///
+    fn encode_records_packed<F: Fn(usize) -> <Self as PIR>::RecordBytes>(
+        records_generator: &F,
+        chunk_idx: usize,
+    ) -> [u64; D1] {
+        let chunk = (Self::PACK_RATIO_DB * chunk_idx..Self::PACK_RATIO_DB * (chunk_idx + 1))
+            .map(|idx| Self::encode_record(&records_generator(idx)));
+        let mut record_packed = IntModCyclo::<D1, P>::zero();
+        for (record_in_chunk, record) in chunk.enumerate() {
+            for (coeff_idx, coeff) in record.coeff.iter().enumerate() {
+                record_packed.coeff[Self::PACK_RATIO_DB * coeff_idx + record_in_chunk] = *coeff;
+            }
         }
-        assert_eq!(c_gsws.len(), Self::GSW_COUNT);
+        let value = <Self as Respire>::RingQ1Fast::from(&record_packed.include_into::<Q1>());
+        let (lo, hi) = value.residues();
+        let mut packed_value = [0u64; D1];
+        for i in 0..D1 {
+            packed_value[i] = (hi[i] << 32) | lo[i];
+        }
+        packed_value
+    }
 
-        let i2 = Instant::now();
-        let mut c_gsws_iter = c_gsws
-            .chunks_exact(T_GSW)
-            .map(|cs| Self::rlwe_to_gsw(rlwe_to_gsw_params, cs));
+    ///
+    /// Same as `encode_db`, but writes the database file (see `db_storage`) directly, so the
+    /// database never has to fit in memory. Packed records are encoded in the order they are
+    /// stored in a slab, and each slab gets one contiguous write per `buffer_bytes` of encoded
+    /// records. The file can then be served with `MmapStorage` or `ChunkedFileStorage`.
+    ///
+    pub fn encode_db_file<F: Fn(usize) -> <Self as PIR>::RecordBytes>(
+        path: &Path,
+        records_generator: F,
+        buffer_bytes: usize,
+        time_stats: Option<&mut Stats<Duration>>,
+    ) -> io::Result<()> {
+        let begin = Instant::now();
+        assert!(Q1A <= u32::MAX as u64);
+        assert!(Q1B <= u32::MAX as u64);
 
-        let c_gsws_fold = (0..Self::GSW_FOLD_COUNT)
-            .map(|_| c_gsws_iter.next().unwrap())
-            .collect();
-        let c_gsws_rot = (0..Self::GSW_ROT_COUNT)
-            .map(|_| c_gsws_iter.next().unwrap())
-            .collect();
-        assert_eq!(c_gsws_iter.next(), None);
+        let num_slabs = D1 / SIMD_LANES;
+        let slab_len = Self::PACKED_DB_SIZE;
+        let block_len = (buffer_bytes / (D1 * size_of::<u64>())).clamp(1, slab_len);
+        let mut writer = DbFileWriter::create(path, num_slabs, slab_len)?;
+        let mut block = vec![Vec::with_capacity(block_len); num_slabs];
+        for start in (0..slab_len).step_by(block_len) {
+            for slab in block.iter_mut() {
+                slab.clear();
+            }
+            for db_idx_t in start..min(start + block_len, slab_len) {
+                // Undo the transpose of `encode_db`
+                let (db_j, db_i) = (
+                    db_idx_t / Self::PACKED_DIM1_SIZE,
+                    db_idx_t % Self::PACKED_DIM1_SIZE,
+                );
+                let db_idx = db_i * Self::PACKED_DIM2_SIZE + db_j;
+                let record_packed = Self::encode_records_packed(&records_generator, db_idx);
 
-        let i3 = Instant::now();
+                #[cfg(not(target_feature = "avx2"))]
+                for (slab, word) in block.iter_mut().zip(record_packed) {
+                    slab.push(word);
+                }
 
-        if let Some(time_stats) = time_stats {
-            time_stats.add("answer_query_expand_reg", i1 - i0);
-            time_stats.add("answer_query_expand_gsw", i2 - i1);
-            time_stats.add("answer_query_expand_reg_to_gsw", i3 - i2);
+                #[cfg(target_feature = "avx2")]
+                for (slab, words) in block.iter_mut().zip(record_packed.chunks_exact(SIMD_LANES)) {
+                    slab.push(Aligned32(words.try_into().unwrap()));
+                }
+            }
+            for (slab_idx, slab) in block.iter().enumerate() {
+                writer.write_segment(slab_idx, start, slab)?;
+            }
         }
+        writer.finish()?;
 
-        // TODO measure and report noise through this phase? Difficult because need to know the exact encoding (since they are not rounded to q/p)
-        (c_rlwes, c_gsws_fold, c_gsws_rot)
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats {
+            time_stats.add("encode", end - begin);
+        }
+        Ok(())
     }
 
+    ///
+    /// Computes one RLWE encoding per column of `db`. The database may be a column shard (see
+    /// `ShardRespire::shard_columns`), in which case only its columns are computed. Fails if a
+    /// file backed database cannot be read.
+    ///
     pub fn answer_first_dim(
         db: &<Self as PIR>::Database,
         rlwes: &[<Self as Respire>::RLWEEncoding],
-    ) -> Vec<<Self as Respire>::RLWEEncoding> {
+    ) -> io::Result<Vec<<Self as Respire>::RLWEEncoding>> {
         assert_eq!(rlwes.len(), Self::PACKED_DIM1_SIZE);
+        assert_eq!(db.num_slabs(), D1 / SIMD_LANES);
+        assert!(db.slab_len().is_multiple_of(Self::PACKED_DIM1_SIZE));
+        let num_columns = db.slab_len() / Self::PACKED_DIM1_SIZE;
 
         // Flatten + transpose the ciphertexts
         let mut c0s: Vec<SimdVec> = Vec::with_capacity((D1 / SIMD_LANES) * Self::PACKED_DIM1_SIZE);
@@ -1526,31 +3386,25 @@ respire_impl!({
         #[cfg(not(target_feature = "avx2"))]
         for eval_idx in 0..D1 {
             for c in rlwes.iter() {
-                let c0_lo = u64::from(c[(0, 0)].proj1.evals[eval_idx]);
-                let c0_hi = u64::from(c[(0, 0)].proj2.evals[eval_idx]);
-                c0s.push((c0_hi << 32) | c0_lo);
+                let (c0_lo, c0_hi) = c[(0, 0)].residues();
+                c0s.push((c0_hi[eval_idx] << 32) | c0_lo[eval_idx]);
 
-                let c1_lo = u64::from(c[(1, 0)].proj1.evals[eval_idx]);
-                let c1_hi = u64::from(c[(1, 0)].proj2.evals[eval_idx]);
-                c1s.push((c1_hi << 32) | c1_lo);
+                let (c1_lo, c1_hi) = c[(1, 0)].residues();
+                c1s.push((c1_hi[eval_idx] << 32) | c1_lo[eval_idx]);
             }
         }
 
         #[cfg(target_feature = "avx2")]
         for eval_vec_idx in 0..(D1 / SIMD_LANES) {
             for c in rlwes.iter() {
+                let (c0_lo, c0_hi) = c[(0, 0)].residues();
+                let (c1_lo, c1_hi) = c[(1, 0)].residues();
                 let mut c0_vec: SimdVec = Aligned32([0_u64; 4]);
                 let mut c1_vec: SimdVec = Aligned32([0_u64; 4]);
                 for lane_idx in 0..SIMD_LANES {
                     let from_idx = eval_vec_idx * SIMD_LANES + lane_idx;
-
-                    let c0_lo = u64::from(c[(0, 0)].proj1.evals[from_idx]);
-                    let c0_hi = u64::from(c[(0, 0)].proj2.evals[from_idx]);
-                    c0_vec.0[lane_idx] = (c0_hi << 32) | c0_lo;
-
-                    let c1_lo = u64::from(c[(1, 0)].proj1.evals[from_idx]);
-                    let c1_hi = u64::from(c[(1, 0)].proj2.evals[from_idx]);
-                    c1_vec.0[lane_idx] = (c1_hi << 32) | c1_lo;
+                    c0_vec.0[lane_idx] = (c0_hi[from_idx] << 32) | c0_lo[from_idx];
+                    c1_vec.0[lane_idx] = (c1_hi[from_idx] << 32) | c1_lo[from_idx];
                 }
                 c0s.push(c0_vec);
                 c1s.push(c1_vec);
@@ -1558,7 +3412,7 @@ respire_impl!({
         }
 
         // First dimension processing
-        let mut result: Vec<<Self as Respire>::RLWEEncoding> = (0..Self::PACKED_DIM2_SIZE)
+        let mut result: Vec<<Self as Respire>::RLWEEncoding> = (0..num_columns)
             .map(|_| <Self as Respire>::RLWEEncoding::zero())
             .collect();
 
@@ -1570,8 +3424,8 @@ respire_impl!({
         // Here db_(i, j) are scalars; ct_i are 2 x 1 matrices.
 
         #[cfg(not(target_feature = "avx2"))]
-        for eval_idx in 0..D1 {
-            for j in 0..Self::PACKED_DIM2_SIZE {
+        let mut process_slab = |eval_idx: usize, slab: &[SimdVec]| {
+            for j in 0..num_columns {
                 let mut sum0_proj1 = 0_u64;
                 let mut sum0_proj2 = 0_u64;
                 let mut sum1_proj1 = 0_u64;
@@ -1586,7 +3440,7 @@ respire_impl!({
                     let lhs1_proj1 = lhs1 as u32 as u64;
                     let lhs1_proj2 = lhs1 >> 32;
 
-                    let rhs = db[eval_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i];
+                    let rhs = slab[j * Self::PACKED_DIM1_SIZE + i];
                     let rhs_proj1 = rhs as u32 as u64;
                     let rhs_proj2 = rhs >> 32;
 
@@ -1603,18 +3457,20 @@ respire_impl!({
                     }
                 }
 
-                result[j][(0, 0)].proj1.evals[eval_idx] = IntMod::from(sum0_proj1);
-                result[j][(0, 0)].proj2.evals[eval_idx] = IntMod::from(sum0_proj2);
-                result[j][(1, 0)].proj1.evals[eval_idx] = IntMod::from(sum1_proj1);
-                result[j][(1, 0)].proj2.evals[eval_idx] = IntMod::from(sum1_proj2);
+                let (res0_proj1, res0_proj2) = result[j][(0, 0)].residues_mut();
+                res0_proj1[eval_idx] = sum0_proj1;
+                res0_proj2[eval_idx] = sum0_proj2;
+                let (res1_proj1, res1_proj2) = result[j][(1, 0)].residues_mut();
+                res1_proj1[eval_idx] = sum1_proj1;
+                res1_proj2[eval_idx] = sum1_proj2;
             }
-        }
+        };
 
         #[cfg(target_feature = "avx2")]
-        for eval_vec_idx in 0..(D1 / SIMD_LANES) {
+        let mut process_slab = |eval_vec_idx: usize, slab: &[SimdVec]| {
             use std::arch::x86_64::*;
             unsafe {
-                for j in 0..Self::PACKED_DIM2_SIZE {
+                for j in 0..num_columns {
                     let mut sum0_proj1 = _mm256_setzero_si256();
                     let mut sum0_proj2 = _mm256_setzero_si256();
                     let mut sum1_proj1 = _mm256_setzero_si256();
@@ -1627,9 +3483,9 @@ respire_impl!({
                         let lhs1_ptr = c1s.get_unchecked(eval_vec_idx * Self::PACKED_DIM1_SIZE + i)
                             as *const SimdVec
                             as *const __m256i;
-                        let rhs_ptr = db.get_unchecked(
-                            eval_vec_idx * Self::PACKED_DB_SIZE + j * Self::PACKED_DIM1_SIZE + i,
-                        ) as *const SimdVec as *const __m256i;
+                        let rhs_ptr = slab.get_unchecked(j * Self::PACKED_DIM1_SIZE + i)
+                            as *const SimdVec
+                            as *const __m256i;
 
                         let lhs0_proj1 = _mm256_load_si256(lhs0_ptr);
                         let lhs0_proj2 = _mm256_srli_epi64::<32>(lhs0_proj1);
@@ -1685,96 +3541,111 @@ respire_impl!({
                         }
                     }
 
-                    let sum0_proj1_ptr = result[j][(0, 0)]
-                        .proj1
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1A>
-                        as *mut __m256i;
-                    let sum0_proj2_ptr = result[j][(0, 0)]
-                        .proj2
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1B>
-                        as *mut __m256i;
-                    let sum1_proj1_ptr = result[j][(1, 0)]
-                        .proj1
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1A>
-                        as *mut __m256i;
-                    let sum1_proj2_ptr = result[j][(1, 0)]
-                        .proj2
-                        .evals
-                        .get_unchecked_mut(eval_vec_idx * SIMD_LANES)
-                        as *mut IntMod<Q1B>
-                        as *mut __m256i;
-                    _mm256_store_si256(sum0_proj1_ptr, sum0_proj1);
-                    _mm256_store_si256(sum0_proj2_ptr, sum0_proj2);
-                    _mm256_store_si256(sum1_proj1_ptr, sum1_proj1);
-                    _mm256_store_si256(sum1_proj2_ptr, sum1_proj2);
+                    // The residues of both ring types are 32-byte aligned arrays
+                    let (res0_proj1, res0_proj2) = result[j][(0, 0)].residues_mut();
+                    let offset = eval_vec_idx * SIMD_LANES;
+                    _mm256_store_si256(
+                        res0_proj1.get_unchecked_mut(offset) as *mut u64 as *mut __m256i,
+                        sum0_proj1,
+                    );
+                    _mm256_store_si256(
+                        res0_proj2.get_unchecked_mut(offset) as *mut u64 as *mut __m256i,
+                        sum0_proj2,
+                    );
+                    let (res1_proj1, res1_proj2) = result[j][(1, 0)].residues_mut();
+                    _mm256_store_si256(
+                        res1_proj1.get_unchecked_mut(offset) as *mut u64 as *mut __m256i,
+                        sum1_proj1,
+                    );
+                    _mm256_store_si256(
+                        res1_proj2.get_unchecked_mut(offset) as *mut u64 as *mut __m256i,
+                        sum1_proj2,
+                    );
                 }
             }
-        }
+        };
 
-        result
+        // The slabs are scanned in order, so a file backed database is read sequentially
+        db.scan(&mut process_slab)?;
+
+        Ok(result)
     }
 
+    ///
+    /// Folds the first dimension results with one GSW selection per level. The selections of a
+    /// level all use the same GSW encoding, so they are done `FOLD_BATCH_SIZE` pairs at a time: the
+    /// differences are decomposed together, then multiplied by the prepared GSW in one pass, which
+    /// reads each of its evaluation points once per batch instead of once per pair.
+    ///
     pub fn answer_fold(
         first_dim_folded: Vec<<Self as Respire>::RLWEEncoding>,
-        gsws: &[<Self as Respire>::GSWEncoding],
+        gsws: &[<Self as Respire>::GSWPrepared],
     ) -> <Self as Respire>::RLWEEncoding {
         assert_eq!(gsws.len(), Self::NU2);
         let fold_size: usize = 2usize.pow(Self::NU2 as u32);
 
+        // Scratch space shared by every batch in the fold tree
+        let batch_size = (fold_size / 2).clamp(1, Self::FOLD_BATCH_SIZE);
+        let mut diff = <Self as Respire>::RLWEEncoding::zero();
+        let mut g_invs =
+            vec![Matrix::<M_GSW, 1, <Self as Respire>::RingQ1Fast>::zero(); batch_size];
+
         let mut curr = first_dim_folded;
         let mut curr_size = fold_size;
-        for gsw_idx in 0..NU2 {
+        for gsw in gsws.iter() {
             curr.truncate(curr_size);
-            for fold_idx in 0..curr_size / 2 {
-                curr[fold_idx] = Self::select_hom(
-                    &curr[fold_idx],
-                    &curr[curr_size / 2 + fold_idx],
-                    &gsws[gsw_idx],
-                );
+            let (lo, hi) = curr.split_at_mut(curr_size / 2);
+            for (c0s, c1s) in lo.chunks_mut(batch_size).zip(hi.chunks(batch_size)) {
+                Self::select_hom_batch_assign(c0s, c1s, gsw, &mut diff, &mut g_invs);
             }
             curr_size /= 2;
         }
-        curr.remove(0)
+        curr.swap_remove(0)
     }
 
     pub fn answer_rotate(
         ct: &<Self as Respire>::RLWEEncoding,
-        gsws_rot: &[<Self as Respire>::GSWEncoding],
+        gsws_rot: &[<Self as Respire>::GSWPrepared],
     ) -> <Self as Respire>::RLWEEncoding {
         assert_eq!(gsws_rot.len(), Self::NU3);
-        let mut ct_curr = ct.clone();
+        let mut diff = <Self as Respire>::RLWEEncoding::zero();
+        let mut g_inv = [Matrix::<M_GSW, 1, <Self as Respire>::RingQ1Fast>::zero()];
+        let mut ct_rot = [<Self as Respire>::RLWEEncoding::zero()];
+
+        let mut ct_curr = [ct.clone()];
         for (iter_num, gsw) in gsws_rot.iter().enumerate() {
             let rot = 1 << (Self::NU3 - 1 - iter_num);
-            ct_curr =
-                Self::select_hom(&ct_curr, &Self::rlwe_mul_x_pow(&ct_curr, 2 * D1 - rot), gsw);
+            for row in 0..2 {
+                ct_curr[0][(row, 0)].mul_x_pow_into(2 * D1 - rot, &mut ct_rot[0][(row, 0)]);
+            }
+            Self::select_hom_batch_assign(&mut ct_curr, &ct_rot, gsw, &mut diff, &mut g_inv);
         }
+        let [ct_curr] = ct_curr;
         ct_curr
     }
 
     pub fn answer_project(
-        ((_, auto_params_long), _, _, _): &<Self as PIR>::PublicParams,
+        pp: &<Self as PIR>::PublicParams,
         ct: &<Self as Respire>::RLWEEncoding,
     ) -> <Self as Respire>::RLWEEncoding {
+        let ((_, auto_params_long), _, _, _) = &**pp;
         let mut ct_curr = ct.clone();
         let num_proj = Self::NU3;
         let inv = <Self as Respire>::RingQ1Fast::from(mod_inverse(
             2_usize.pow(num_proj as u32) as u64,
             Q1,
         ));
-        ct_curr[(0, 0)] *= &inv;
-        ct_curr[(1, 0)] *= &inv;
+        ct_curr *= &inv;
 
+        let mut ct_auto = <Self as Respire>::RLWEEncoding::zero();
+        let mut g_inv = Matrix::<T_PROJ_LONG, 1, <Self as Respire>::RingQ1Fast>::zero();
         for (iter_num, auto_params_long) in auto_params_long.iter().enumerate().take(num_proj) {
-            ct_curr = Self::do_proj_iter_one::<T_PROJ_LONG, Z_PROJ_LONG>(
+            Self::do_proj_iter_one::<T_PROJ_LONG, Z_PROJ_LONG, DROP_PROJ_LONG>(
                 iter_num,
-                &ct_curr,
+                &mut ct_curr,
                 auto_params_long,
+                &mut ct_auto,
+                &mut g_inv,
             );
         }
         ct_curr
@@ -1893,14 +3764,14 @@ respire_impl!({
         s_encode: &<Self as Respire>::EncodingSecret,
         c: &<Self as Respire>::RLWEEncoding,
     ) -> <Self as Respire>::RingQ1 {
-        <Self as Respire>::RingQ1::from(&(&c[(1, 0)] - &(&c[(0, 0)] * s_encode)))
+        (&c[(1, 0)] - &(&c[(0, 0)] * s_encode)).to_coeff()
     }
 
     pub fn decode_vec_rlwe(
         s_vec: &<Self as Respire>::VecEncodingSecret,
         (c_r, c_m): &<Self as Respire>::VecRLWEEncoding,
     ) -> Matrix<N_VEC, 1, <Self as Respire>::RingQ1> {
-        (c_m - &(s_vec * c_r)).map_ring(|r| <Self as Respire>::RingQ1::from(r))
+        (c_m - &(s_vec * c_r)).map_ring::<<Self as Respire>::RingQ1, _>(|r| r.to_coeff())
     }
 
     pub fn encode_gsw(
@@ -1931,8 +3802,7 @@ respire_impl!({
             * &gadget_inverse::<<Self as Respire>::RingQ1Fast, 2, M_GSW, 2, Z_GSW, T_GSW>(
                 &scaled_ident,
             );
-        let result_q = <Self as Respire>::RingQ1::from(&result_q_fast_mat[(0, 1)]);
-        <Self as Respire>::RingQ1::from(result_q)
+        result_q_fast_mat[(0, 1)].to_coeff()
     }
 
     pub fn rlwe_sub_hom(
@@ -1954,9 +3824,58 @@ respire_impl!({
         c1: &<Self as Respire>::RLWEEncoding,
         b: &<Self as Respire>::GSWEncoding,
     ) -> <Self as Respire>::RLWEEncoding {
-        let c1_sub_c0 = Self::rlwe_sub_hom(c1, c0);
-        let c1_sub_c0_mul_b = Self::hybrid_mul_hom(&c1_sub_c0, b);
-        c0 + &c1_sub_c0_mul_b
+        let mut result = c0.clone();
+        let mut diff = Matrix::zero();
+        let mut g_inv = Matrix::zero();
+        Self::select_hom_assign(&mut result, c1, b, &mut diff, &mut g_inv);
+        result
+    }
+
+    ///
+    /// In-place version of `select_hom`, overwriting `c0` with the selection of `c0` or `c1`. The
+    /// arguments `diff` and `g_inv` are scratch space; their contents on entry are ignored.
+    ///
+    pub fn select_hom_assign(
+        c0: &mut <Self as Respire>::RLWEEncoding,
+        c1: &<Self as Respire>::RLWEEncoding,
+        b: &<Self as Respire>::GSWEncoding,
+        diff: &mut <Self as Respire>::RLWEEncoding,
+        g_inv: &mut Matrix<M_GSW, 1, <Self as Respire>::RingQ1Fast>,
+    ) {
+        Matrix::sub_into(diff, c1, c0);
+        gadget_inverse_into::<<Self as Respire>::RingQ1Fast, 2, M_GSW, 1, Z_GSW, T_GSW>(
+            diff, g_inv,
+        );
+        c0.add_eq_mul(b, g_inv);
+    }
+
+    ///
+    /// Batched version of `select_hom_assign` with a prepared GSW encoding, overwriting each
+    /// `c0s[i]` with the selection of `c0s[i]` or `c1s[i]`. The arguments `diff` and `g_invs` are
+    /// scratch space, `g_invs` with at least `c0s.len()` entries.
+    ///
+    pub fn select_hom_batch_assign(
+        c0s: &mut [<Self as Respire>::RLWEEncoding],
+        c1s: &[<Self as Respire>::RLWEEncoding],
+        b: &<Self as Respire>::GSWPrepared,
+        diff: &mut <Self as Respire>::RLWEEncoding,
+        g_invs: &mut [Matrix<M_GSW, 1, <Self as Respire>::RingQ1Fast>],
+    ) {
+        assert_eq!(c0s.len(), c1s.len());
+        let g_invs = &mut g_invs[..c0s.len()];
+        for ((c0, c1), g_inv) in c0s.iter().zip(c1s.iter()).zip(g_invs.iter_mut()) {
+            Matrix::sub_into(diff, c1, c0);
+            gadget_inverse_into::<<Self as Respire>::RingQ1Fast, 2, M_GSW, 1, Z_GSW, T_GSW>(
+                diff, g_inv,
+            );
+        }
+        <<Self as Respire>::RingQ1Fast as PreparedMatrixMul<2, M_GSW>>::add_eq_mul_prepared(
+            b, c0s, g_invs,
+        );
+    }
+
+    pub fn gsw_prepare(c: &<Self as Respire>::GSWEncoding) -> <Self as Respire>::GSWPrepared {
+        <<Self as Respire>::RingQ1Fast as PreparedMatrixMul<2, M_GSW>>::prepare(c)
     }
 
     pub fn rlwe_mul_x_pow(
@@ -1973,39 +3892,62 @@ respire_impl!({
         c.map_ring(|x| x.mul_x_pow(k))
     }
 
-    pub fn auto_setup<const LEN: usize, const BASE: u64>(
+    pub fn auto_setup<const LEN: usize, const BASE: u64, const DROP: usize>(
         tau_power: usize,
         s_encode: &<Self as Respire>::RingQ1Fast,
-    ) -> <Self as Respire>::AutoParams<LEN> {
+    ) -> <Self as Respire>::AutoParams<LEN>
+    where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
         let mut rng = ChaCha20Rng::from_entropy();
         let a_t: Matrix<1, LEN, <Self as Respire>::RingQ1Fast> = Matrix::rand_uniform(&mut rng);
         let e_t =
             Self::rand_discrete_gaussian_matrix::<ERROR_WIDTH_MILLIONTHS, 1, LEN, _>(&mut rng);
         let mut bottom = &a_t * s_encode;
         bottom += &e_t;
-        bottom -= &(&build_gadget::<<Self as Respire>::RingQ1Fast, 1, LEN, BASE, LEN>()
-            * &s_encode.auto(tau_power));
+        bottom -=
+            &(&build_gadget_approx::<<Self as Respire>::RingQ1Fast, 1, LEN, BASE, LEN, DROP>()
+                * &s_encode.auto(tau_power));
         (Matrix::stack(&a_t, &bottom), tau_power)
     }
 
-    pub fn auto_hom<const LEN: usize, const BASE: u64>(
+    pub fn auto_hom<const LEN: usize, const BASE: u64, const DROP: usize>(
+        auto_params: &<Self as Respire>::AutoParams<LEN>,
+        c: &<Self as Respire>::RLWEEncoding,
+    ) -> <Self as Respire>::RLWEEncoding
+    where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
+        let mut result = Matrix::zero();
+        let mut g_inv_tau_c0 = Matrix::zero();
+        Self::auto_hom_into::<LEN, BASE, DROP>(auto_params, c, &mut result, &mut g_inv_tau_c0);
+        result
+    }
+
+    ///
+    /// Same as `auto_hom`, but writes the result into `out`. The argument `g_inv_tau_c0` is scratch
+    /// space for the gadget decomposition; its contents on entry are ignored.
+    ///
+    pub fn auto_hom_into<const LEN: usize, const BASE: u64, const DROP: usize>(
         (w_mat, tau_power): &<Self as Respire>::AutoParams<LEN>,
         c: &<Self as Respire>::RLWEEncoding,
-    ) -> <Self as Respire>::RLWEEncoding {
+        out: &mut <Self as Respire>::RLWEEncoding,
+        g_inv_tau_c0: &mut Matrix<LEN, 1, <Self as Respire>::RingQ1Fast>,
+    ) where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
         let c0 = &c[(0, 0)];
         let c1 = &c[(1, 0)];
-        let mut g_inv_tau_c0 = Matrix::<LEN, 1, <Self as Respire>::RingQ1Fast>::zero();
 
-        <<Self as Respire>::RingQ1Fast as RingElementDecomposable<BASE, LEN>>::decompose_into_mat(
+        <<Self as Respire>::RingQ1Fast as RingElementDecomposable<BASE, LEN>>::decompose_approx_into_mat::<DROP, LEN, 1>(
             &c0.auto(*tau_power),
-            &mut g_inv_tau_c0,
+            g_inv_tau_c0,
             0,
             0,
         );
-        let mut result = w_mat * &g_inv_tau_c0;
+        Matrix::mul_into(out, w_mat, g_inv_tau_c0);
 
-        result[(1, 0)] += &c1.auto(*tau_power);
-        result
+        out[(1, 0)] += &c1.auto(*tau_power);
     }
 
     ///
@@ -2018,40 +3960,52 @@ respire_impl!({
     /// * `cts`: the input ciphertexts
     /// * `auto_params`: the automorphism public params, which should have power equal to `D1 / 2^which_iter + 1`
     ///
-    pub fn do_proj_iter<const LEN: usize, const BASE: u64>(
+    pub fn do_proj_iter<const LEN: usize, const BASE: u64, const DROP: usize>(
         which_iter: usize,
         cts: &[<Self as Respire>::RLWEEncoding],
         auto_params: &<Self as Respire>::AutoParams<LEN>,
-    ) -> Vec<<Self as Respire>::RLWEEncoding> {
+    ) -> Vec<<Self as Respire>::RLWEEncoding>
+    where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
         assert_eq!(auto_params.1, (D1 >> which_iter) + 1);
         let len = cts.len();
         let mut cts_new = Vec::with_capacity(2 * len);
         cts_new.resize(2 * len, Matrix::zero());
-        for (j, ct) in cts.iter().enumerate() {
-            let shift_exp = 1 << which_iter;
-            let shift_auto_exp = (shift_exp * auto_params.1) % (2 * D1);
 
-            let ct_shifted = Self::rlwe_mul_x_pow(ct, 2 * D1 - shift_exp);
-            let ct_auto = Self::auto_hom::<LEN, BASE>(auto_params, ct);
-            let ct_auto_shifted = Self::rlwe_mul_x_pow(&ct_auto, 2 * D1 - shift_auto_exp);
+        let shift_exp = 1 << which_iter;
+        let shift_auto_exp = (shift_exp * auto_params.1) % (2 * D1);
+        let mut ct_auto = <Self as Respire>::RLWEEncoding::zero();
+        let mut g_inv = Matrix::<LEN, 1, <Self as Respire>::RingQ1Fast>::zero();
+        for (j, ct) in cts.iter().enumerate() {
+            Self::auto_hom_into::<LEN, BASE, DROP>(auto_params, ct, &mut ct_auto, &mut g_inv);
+            Matrix::add_into(&mut cts_new[2 * j], ct, &ct_auto);
 
-            cts_new[2 * j] = ct + &ct_auto;
-            cts_new[2 * j + 1] = &ct_shifted + &ct_auto_shifted;
+            let ct_odd = &mut cts_new[2 * j + 1];
+            for r in 0..2 {
+                ct_odd[(r, 0)] = ct[(r, 0)].mul_x_pow(2 * D1 - shift_exp);
+                ct_odd[(r, 0)] += &ct_auto[(r, 0)].mul_x_pow(2 * D1 - shift_auto_exp);
+            }
         }
         cts_new
     }
 
     ///
-    /// Same as do_proj_iter, but only does one side of the projection
+    /// Same as do_proj_iter, but only does one side of the projection, in place on `ct`. The
+    /// arguments `ct_auto` and `g_inv` are scratch space for `auto_hom_into`.
     ///
-    pub fn do_proj_iter_one<const LEN: usize, const BASE: u64>(
+    pub fn do_proj_iter_one<const LEN: usize, const BASE: u64, const DROP: usize>(
         which_iter: usize,
-        ct: &<Self as Respire>::RLWEEncoding,
+        ct: &mut <Self as Respire>::RLWEEncoding,
         auto_params: &<Self as Respire>::AutoParams<LEN>,
-    ) -> <Self as Respire>::RLWEEncoding {
+        ct_auto: &mut <Self as Respire>::RLWEEncoding,
+        g_inv: &mut Matrix<LEN, 1, <Self as Respire>::RingQ1Fast>,
+    ) where
+        RingQ1Fast: RingElementDecomposable<BASE, LEN>,
+    {
         assert_eq!(auto_params.1, (D1 >> which_iter) + 1);
-        let ct_auto = Self::auto_hom::<LEN, BASE>(auto_params, ct);
-        ct + &ct_auto
+        Self::auto_hom_into::<LEN, BASE, DROP>(auto_params, ct, ct_auto, g_inv);
+        *ct += ct_auto;
     }
 
     pub fn rlwe_to_gsw_setup(
@@ -2065,12 +4019,13 @@ respire_impl!({
             );
         let mut bottom = &a_t * s_encode;
         bottom += &e_mat;
-        let g_vec = build_gadget::<
+        let g_vec = build_gadget_approx::<
             <Self as Respire>::RingQ1Fast,
             1,
             T_RLWE_TO_GSW,
             Z_RLWE_TO_GSW,
             T_RLWE_TO_GSW,
+            DROP_RLWE_TO_GSW,
         >();
         let mut s_encode_tensor_g =
             Matrix::<1, M_RLWE_TO_GSW, <Self as Respire>::RingQ1Fast>::zero();
@@ -2090,13 +4045,14 @@ respire_impl!({
         for (i, ci) in cs.iter().enumerate() {
             c_hat.copy_into(ci, 0, i);
         }
-        let g_inv_c_hat = gadget_inverse::<
+        let g_inv_c_hat = gadget_inverse_approx::<
             <Self as Respire>::RingQ1Fast,
             2,
             M_RLWE_TO_GSW,
             T_GSW,
             Z_RLWE_TO_GSW,
             T_RLWE_TO_GSW,
+            DROP_RLWE_TO_GSW,
         >(&c_hat);
         let v_g_inv_c_hat = v_mat * &g_inv_c_hat;
         result.copy_into(&v_g_inv_c_hat, 0, 0);
@@ -2158,17 +4114,21 @@ respire_impl!({
         s_scal_to_vec: &<Self as Respire>::VectorizeParams,
         cs: &[<Self as Respire>::RLWEEncoding; N_VEC],
     ) -> <Self as Respire>::VecRLWEEncoding {
-        let mut result_rand = <Self as Respire>::RingQ1Fast::zero();
+        let mut result_rand = Matrix::<1, 1, <Self as Respire>::RingQ1Fast>::zero();
         let mut result_embed = Matrix::<N_VEC, 1, <Self as Respire>::RingQ1Fast>::zero();
+        let mut g_inv = Matrix::<T_VECTORIZE, 1, <Self as Respire>::RingQ1Fast>::zero();
         for (i, c) in cs.iter().enumerate() {
             let c0 = &c[(0, 0)];
             let c1 = &c[(1, 0)];
-            let g_inv = gadget_inverse_scalar::<_, Z_VECTORIZE, T_VECTORIZE>(c0);
-            result_rand += &(&s_scal_to_vec[i].0 * &g_inv)[(0, 0)];
-            result_embed += &(&s_scal_to_vec[i].1 * &g_inv);
+            <<Self as Respire>::RingQ1Fast as RingElementDecomposable<
+                Z_VECTORIZE,
+                T_VECTORIZE,
+            >>::decompose_into_mat(c0, &mut g_inv, 0, 0);
+            result_rand.add_eq_mul(&s_scal_to_vec[i].0, &g_inv);
+            result_embed.add_eq_mul(&s_scal_to_vec[i].1, &g_inv);
             result_embed[(i, 0)] += c1;
         }
-        (result_rand, result_embed)
+        (result_rand[(0, 0)].clone(), result_embed)
     }
 
     pub fn encode_record(bytes: &RecordBytesImpl<BYTES_PER_RECORD>) -> <Self as Respire>::Record {
@@ -2199,24 +4159,39 @@ respire_impl!({
         bytes.try_into().unwrap()
     }
 
-    pub fn noise_variance(
+    /// The absolute noise of each coefficient of `c`.
+    pub fn noise_coefficients(
         s_scal: &<Self as Respire>::EncodingSecret,
         c: &<Self as Respire>::RLWEEncoding,
-    ) -> f64 {
+    ) -> Vec<u64> {
         let decoded: <Self as Respire>::RingQ1 = Self::decode_rlwe(&s_scal, c);
         let message: <Self as Respire>::RingP = decoded.round_down_into();
         let noise: <Self as Respire>::RingQ1 = &decoded - &message.scale_up_into();
+        noise.coeff.iter().map(|e| e.norm()).collect()
+    }
 
-        let mut sum = 0_f64;
-        let mut samples = 0_usize;
-
-        for e in noise.coeff.iter() {
-            let e_sq = (e.norm() as f64) * (e.norm() as f64);
-            sum += e_sq;
-            samples += 1;
+    pub fn noise_coefficients_vec(
+        s_vec: &<Self as Respire>::VecEncodingSecret,
+        (cr, cm): &<Self as Respire>::VecRLWEEncoding,
+    ) -> Vec<u64> {
+        let mut result = Vec::with_capacity(N_VEC * D1);
+        for i in 0..N_VEC {
+            let mut fake_ct = Matrix::zero();
+            fake_ct[(0, 0)] = cr.clone();
+            fake_ct[(1, 0)] = cm[(i, 0)].clone();
+            let fake_s = s_vec[(i, 0)].clone();
+            result.extend(Self::noise_coefficients(&fake_s, &fake_ct));
         }
+        result
+    }
 
-        sum / samples as f64
+    pub fn noise_variance(
+        s_scal: &<Self as Respire>::EncodingSecret,
+        c: &<Self as Respire>::RLWEEncoding,
+    ) -> f64 {
+        let noise = Self::noise_coefficients(s_scal, c);
+        let sum: f64 = noise.iter().map(|&e| (e as f64) * (e as f64)).sum();
+        sum / noise.len() as f64
     }
 
     fn variance_to_subgaussian_bits(x: f64) -> f64 {
@@ -2233,49 +4208,260 @@ respire_impl!({
 
     pub fn noise_subgaussian_bits_vec(
         s_vec: &<Self as Respire>::VecEncodingSecret,
-        (cr, cm): &<Self as Respire>::VecRLWEEncoding,
+        c: &<Self as Respire>::VecRLWEEncoding,
     ) -> f64 {
-        let mut total = 0_f64;
-        for i in 0..N_VEC {
-            let mut fake_ct = Matrix::zero();
-            fake_ct[(0, 0)] = cr.clone();
-            fake_ct[(1, 0)] = cm[(i, 0)].clone();
-            let fake_s = s_vec[(i, 0)].clone();
-            total += Self::noise_variance(&fake_s, &fake_ct);
+        let noise = Self::noise_coefficients_vec(s_vec, c);
+        let sum: f64 = noise.iter().map(|&e| (e as f64) * (e as f64)).sum();
+        Self::variance_to_subgaussian_bits(sum / noise.len() as f64)
+    }
+
+    ///
+    /// Runs `trials` query/answer cycles against a database of random records and compares the
+    /// noise measured after each stage with the analytic estimates. Each trial packs
+    /// `min(BATCH_SIZE, RESPONSE_CHUNK_SIZE)` answers into one response, as the estimates assume.
+    /// This encodes the whole database, so it is meant for small parameter sets.
+    ///
+    pub fn noise_profile<G: Rng>(trials: usize, rng: &mut G) -> NoiseProfile {
+        let records = (0..Self::DB_SIZE)
+            .map(|_| {
+                let mut bytes = [0_u8; BYTES_PER_RECORD];
+                rng.fill(bytes.as_mut_slice());
+                bytes
+            })
+            .collect_vec();
+        let (db, _) = <Self as PIR>::encode_db(
+            |idx| <Self as PIR>::RecordBytes::from_bytes(&records[idx]).unwrap(),
+            None,
+        );
+        let (qk, pp) = <Self as PIR>::setup(None);
+        let (_, s_vec, _) = &qk;
+
+        let answers_per_trial = min(BATCH_SIZE, Self::RESPONSE_CHUNK_SIZE);
+        let mut measured = Vec::new();
+        let mut coefficients = Vec::new();
+        let mut failures = 0;
+        for _ in 0..trials {
+            let indices = (0..answers_per_trial)
+                .map(|_| rng.gen_range(0..Self::DB_SIZE))
+                .collect_vec();
+            let answers = indices
+                .iter()
+                .map(|&idx| {
+                    let q = Self::query_one(&qk, idx, None);
+                    Self::answer_one_traced(&pp, &db, &q, Some(&qk), None, Some(&mut measured))
+                })
+                .collect_vec();
+
+            let vec = Self::answer_pack_chunk(&pp, &answers);
+            let noise = Self::noise_coefficients_vec(s_vec, &vec);
+            let sum: f64 = noise.iter().map(|&e| (e as f64) * (e as f64)).sum();
+            measured.push((
+                NoiseStage::VectorPack,
+                Self::variance_to_subgaussian_bits(sum / noise.len() as f64),
+            ));
+            coefficients.extend(noise);
+
+            let truncate_len = answers.len().div_ceil(Self::PACK_RATIO_RESPONSE);
+            let compressed = Self::answer_compress_vec(&pp, &vec, truncate_len);
+            let extracted = Self::extract_one(&qk, &compressed, None);
+            failures += indices
+                .iter()
+                .zip(extracted)
+                .filter(|(&idx, record)| record.as_bytes() != records[idx])
+                .count();
         }
-        Self::variance_to_subgaussian_bits(total / N_VEC as f64)
+
+        NoiseProfile::new(
+            Self::params(),
+            trials * answers_per_trial,
+            failures,
+            &measured,
+            &coefficients,
+        )
     }
 
     pub fn params_query_size() -> usize {
-        Self::BATCH_SIZE * Self::params_query_one_size()
+        Self::params().query_size()
     }
 
     pub fn params_record_size() -> usize {
-        Self::BATCH_SIZE * Self::params_record_one_size()
+        Self::params().record_size()
     }
 
     ///
     /// size, number of full vectors, remainder size
     ///
     pub fn params_response_info() -> (usize, usize, usize) {
-        let num_ring_elem = Self::BATCH_SIZE.div_ceil(Self::PACK_RATIO_RESPONSE);
-        let num_full_vecs = num_ring_elem / Self::N_VEC;
-        let num_rem = num_ring_elem % Self::N_VEC;
-
-        let full_vec_size = Self::params_response_one_size(Self::N_VEC);
-        let rem_vec_size = if num_rem > 0 {
-            Self::params_response_one_size(num_rem)
-        } else {
-            0
-        };
-        (
-            num_full_vecs * full_vec_size + rem_vec_size,
-            num_full_vecs,
-            num_rem,
-        )
+        Self::params().response_info()
     }
 
     pub fn params_rate() -> f64 {
-        (Self::params_record_size() as f64) / (Self::params_response_info().0 as f64)
+        Self::params().rate()
     }
 });
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Appends values of a given number of bits to a byte buffer, least significant bit first.
+struct BitWriter<'a> {
+    bytes: &'a mut Vec<u8>,
+    acc: u128,
+    acc_bits: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(bytes: &'a mut Vec<u8>) -> Self {
+        Self {
+            bytes,
+            acc: 0,
+            acc_bits: 0,
+        }
+    }
+
+    fn write(&mut self, value: u64, width: usize) {
+        debug_assert!(width == 64 || value >> width == 0);
+        self.acc |= (value as u128) << self.acc_bits;
+        self.acc_bits += width;
+        while self.acc_bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.acc_bits -= 8;
+        }
+    }
+
+    /// Pads the last byte with zero bits.
+    fn finish(self) {
+        if self.acc_bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+    }
+}
+
+/// Reads values written by a `BitWriter`.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    acc: u128,
+    acc_bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            acc: 0,
+            acc_bits: 0,
+        }
+    }
+
+    /// Reads a value of `width` bits, which must be reduced mod `N`.
+    fn read_int_mod<const N: u64>(&mut self, width: usize) -> io::Result<IntMod<N>> {
+        while self.acc_bits < width {
+            let (&byte, rest) = self
+                .bytes
+                .split_first()
+                .ok_or(io::ErrorKind::UnexpectedEof)?;
+            self.acc |= (byte as u128) << self.acc_bits;
+            self.acc_bits += 8;
+            self.bytes = rest;
+        }
+        let value = (self.acc & ((1_u128 << width) - 1)) as u64;
+        self.acc >>= width;
+        self.acc_bits -= width;
+        if value >= N {
+            return Err(invalid_data("unreduced value"));
+        }
+        Ok(IntMod::from(value))
+    }
+
+    /// Checks that the padding of the last byte is zero, and returns the remaining bytes.
+    fn finish(self) -> io::Result<&'a [u8]> {
+        if self.acc != 0 {
+            return Err(invalid_data("nonzero padding"));
+        }
+        Ok(self.bytes)
+    }
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) {
+    bytes.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn read_len(bytes: &mut &[u8]) -> io::Result<usize> {
+    let mut word = [0_u8; 8];
+    bytes.read_exact(&mut word)?;
+    Ok(u64::from_le_bytes(word) as usize)
+}
+
+fn expect_len(bytes: &mut &[u8], expected: usize) -> io::Result<()> {
+    if read_len(bytes)? != expected {
+        return Err(invalid_data("unexpected length in public params"));
+    }
+    Ok(())
+}
+
+/// Reads a little endian word of `width` bytes, which must be reduced mod `N`.
+fn read_int_mod<const N: u64>(bytes: &mut &[u8], width: usize) -> io::Result<IntMod<N>> {
+    let mut word = [0_u8; 8];
+    bytes.read_exact(&mut word[..width])?;
+    let value = u64::from_le_bytes(word);
+    if value >= N {
+        return Err(invalid_data("unreduced value"));
+    }
+    Ok(IntMod::from(value))
+}
+
+fn write_ring_eval<const D: usize, const N: u64>(r: &IntModCycloEval<D, N>, bytes: &mut Vec<u8>) {
+    for x in r.evals.iter() {
+        bytes.extend_from_slice(&u64::from(*x).to_le_bytes());
+    }
+}
+
+fn read_ring_eval<const D: usize, const N: u64>(
+    bytes: &mut &[u8],
+) -> io::Result<IntModCycloEval<D, N>> {
+    let mut r = IntModCycloEval::zero();
+    for x in r.evals.iter_mut() {
+        *x = read_int_mod(bytes, size_of::<u64>())?;
+    }
+    Ok(r)
+}
+
+fn write_matrix<const N: usize, const M: usize, R: RingElement>(
+    m: &Matrix<N, M, R>,
+    bytes: &mut Vec<u8>,
+    write_ring: fn(&R, &mut Vec<u8>),
+) where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    for i in 0..N {
+        for j in 0..M {
+            write_ring(&m[(i, j)], bytes);
+        }
+    }
+}
+
+/// Bytes the entries of `m` take in memory.
+fn matrix_bytes<const N: usize, const M: usize, R: RingElement>(_: &Matrix<N, M, R>) -> usize
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    N * M * size_of::<R>()
+}
+
+fn read_matrix<const N: usize, const M: usize, R: RingElement>(
+    bytes: &mut &[u8],
+    read_ring: fn(&mut &[u8]) -> io::Result<R>,
+) -> io::Result<Matrix<N, M, R>>
+where
+    for<'a> &'a R: RingElementRef<R>,
+{
+    let mut m = Matrix::zero();
+    for i in 0..N {
+        for j in 0..M {
+            m[(i, j)] = read_ring(bytes)?;
+        }
+    }
+    Ok(m)
+}
@@ -1,10 +1,13 @@
-use crate::pir::pir::{PIRRecordBytes, Stats, PIR};
-use crate::pir::respire::{RespireParams, RespireParamsExpanded};
+use crate::pir::pir::{PIRRecordBytes, PIRSummary, Stats, PIR};
+use crate::pir::respire::{ParamsErrors, RespireParams, RespireParamsExpanded};
+use crate::pir::security::DEFAULT_MIN_SECURITY_BITS;
 use crate::respire;
+use clap::builder::RangedU64ValueParser;
 use clap::Parser;
 use itertools::Itertools;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
 use std::time::{Duration, Instant};
 
 //
@@ -31,6 +34,8 @@ impl FactoryParams {
     pub const fn single_record_256(nu1: usize, nu2: usize) -> Self {
         // *** NOTES ***
         //
+        // `respire-bench param-search` (see `pir::param_search`) searches these choices automatically.
+        //
         // Other 256 bytes, p = 16:
         // q2 = 1032193, width = 46.0
         // q2 = 2056193, width = 70.0
@@ -111,6 +116,10 @@ impl FactoryParams {
         }
     }
 
+    pub const fn validate(&self) -> Result<(), ParamsErrors> {
+        self.expand().validate()
+    }
+
     pub const fn expand(&self) -> RespireParams {
         RespireParams {
             Q1A: 268369921,
@@ -118,8 +127,11 @@ impl FactoryParams {
             D1: 2048,
             T_GSW: 8,
             T_RLWE_TO_GSW: 4,
+            DROP_RLWE_TO_GSW: 0,
             T_PROJ_SHORT: self.T_PROJ_SHORT,
+            DROP_PROJ_SHORT: 0,
             T_PROJ_LONG: self.T_PROJ_LONG,
+            DROP_PROJ_LONG: 0,
             T_VECTORIZE: 2,
             BATCH_SIZE: self.BATCH_SIZE,
             N_VEC: self.N_VEC,
@@ -147,6 +159,18 @@ pub const RESPIRE_TEST_PARAMS: RespireParamsExpanded =
 
 pub type RespireTest = respire!(RESPIRE_TEST_PARAMS);
 
+/// The smallest single record parameters: a 64 KiB database, fast enough to run many queries.
+pub const RESPIRE_SMALL_PARAMS: RespireParamsExpanded =
+    FactoryParams::single_record_256(3, 3).expand().expand();
+
+pub type RespireSmall = respire!(RESPIRE_SMALL_PARAMS);
+
+/// Batches of 8 records, over the same database size as `RESPIRE_SMALL_PARAMS`.
+pub const RESPIRE_BATCH_PARAMS: RespireParamsExpanded =
+    FactoryParams::batch_256(8, 2, 3, 3).expand().expand();
+
+pub type RespireBatch = respire!(RESPIRE_BATCH_PARAMS);
+
 #[cfg(not(target_feature = "avx2"))]
 pub fn has_avx2() -> bool {
     false
@@ -157,9 +181,47 @@ pub fn has_avx2() -> bool {
     true
 }
 
+pub struct TrialResult {
+    pub indices: Vec<usize>,
+    pub times: Stats<Duration>,
+    pub end_to_end: Duration,
+    /// The indices whose record was not retrieved correctly.
+    pub failed_indices: Vec<usize>,
+}
+
 pub struct RunResult {
     pub init_times: Stats<Duration>,
-    pub all_trial_times: Vec<Stats<Duration>>,
+    pub init_end_to_end: Duration,
+    pub trials: Vec<TrialResult>,
+}
+
+impl RunResult {
+    pub fn failures(&self) -> usize {
+        self.trials.iter().map(|t| t.failed_indices.len()).sum()
+    }
+}
+
+/// Record `i` of the databases `run_pir` encodes, so other processes can serve the same data.
+pub fn harness_record<ThePIR: PIR>(i: usize) -> ThePIR::RecordBytes {
+    let mut record = vec![0_u8; ThePIR::BYTES_PER_RECORD];
+    record[0] = (i % 256) as u8;
+    record[1] = ((i / 256) % 256) as u8;
+    record[2] = 42_u8;
+    record[3] = 0_u8;
+    record[4] = (i % 100) as u8;
+    record[5] = ((i / 100) % 100) as u8;
+    record[6] = ((i / 100 / 100) % 100) as u8;
+    record[7] = ((i / 100 / 100 / 100) % 100) as u8;
+    // for i in 8..256 {
+    //     record[i] = random();
+    // }
+    ThePIR::RecordBytes::from_bytes(record.as_slice()).unwrap()
+}
+
+/// A record with every byte set to `i mod 256`, so tests can tell which record came back.
+pub fn filled_record<ThePIR: PIR>(i: usize) -> ThePIR::RecordBytes {
+    let record = vec![(i % 256) as u8; ThePIR::BYTES_PER_RECORD];
+    ThePIR::RecordBytes::from_bytes(record.as_slice()).unwrap()
 }
 
 pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
@@ -176,21 +238,7 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
     ThePIR::print_summary();
     eprintln!("========");
 
-    let records_generator = |i: usize| {
-        let mut record = vec![0_u8; ThePIR::BYTES_PER_RECORD];
-        record[0] = (i % 256) as u8;
-        record[1] = ((i / 256) % 256) as u8;
-        record[2] = 42_u8;
-        record[3] = 0_u8;
-        record[4] = (i % 100) as u8;
-        record[5] = ((i / 100) % 100) as u8;
-        record[6] = ((i / 100 / 100) % 100) as u8;
-        record[7] = ((i / 100 / 100 / 100) % 100) as u8;
-        // for i in 8..256 {
-        //     record[i] = random();
-        // }
-        ThePIR::RecordBytes::from_bytes(record.as_slice()).unwrap()
-    };
+    let records_generator = harness_record::<ThePIR>;
 
     let mut init_times = Stats::new();
     let begin = Instant::now();
@@ -212,8 +260,9 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
     }
     eprintln!("Init time (end-to-end): {:?}", end - begin);
     eprintln!("========");
+    let init_end_to_end = end - begin;
 
-    let mut all_trial_times = Vec::new();
+    let mut trials = Vec::new();
 
     let mut run_trial = |indices: &[usize]| {
         eprintln!("Running trial on indices {:?}", &indices);
@@ -222,8 +271,13 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
 
         let begin = Instant::now();
         let (q, st) = ThePIR::query(&qk, indices, &db_hint, Some(&mut trial_times));
-        let response = ThePIR::answer(&pp, &db, &q, Some(&qk), Some(&mut trial_times));
-        let extracted = ThePIR::extract(&qk, &response, &st, Some(&mut trial_times));
+        let extracted = match ThePIR::try_answer(&pp, &db, &q, Some(&qk), Some(&mut trial_times)) {
+            Ok(response) => Some(ThePIR::extract(&qk, &response, &st, Some(&mut trial_times))),
+            Err(e) => {
+                eprintln!("server rejected the query: {}", e);
+                None
+            }
+        };
         let end = Instant::now();
 
         trial_times.add(
@@ -239,18 +293,30 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
             eprintln!("    {}: {:?}", stat, value);
         }
         eprintln!("Trial time (end-to-end): {:?}", end - begin);
-        all_trial_times.push(trial_times);
 
-        for (idx, decoded_record) in indices.iter().copied().zip(extracted) {
+        // A rejected query fails every index it asked for
+        let mut failed_indices = Vec::new();
+        if extracted.is_none() {
+            failed_indices.extend_from_slice(indices);
+        }
+        for (idx, decoded_record) in indices.iter().copied().zip(extracted.into_iter().flatten()) {
             if decoded_record.as_bytes() != records_generator(idx).as_bytes() {
                 eprintln!("**** **** **** **** ERROR **** **** **** ****");
                 eprintln!("protocol failed");
                 eprintln!("idx = {}", idx);
                 eprintln!("decoded record = {:?}", decoded_record.as_bytes());
                 eprintln!("actual record = {:?}", records_generator(idx).as_bytes());
+                failed_indices.push(idx);
             }
         }
         eprintln!("========");
+
+        trials.push(TrialResult {
+            indices: indices.to_vec(),
+            times: trial_times,
+            end_to_end: end - begin,
+            failed_indices,
+        });
     };
 
     for chunk in iter.chunks(ThePIR::BATCH_SIZE).into_iter() {
@@ -260,48 +326,31 @@ pub fn run_pir<ThePIR: PIR, I: Iterator<Item = usize>>(iter: I) -> RunResult {
 
     RunResult {
         init_times,
-        all_trial_times,
+        init_end_to_end,
+        trials,
     }
 }
 
-#[macro_export]
-macro_rules! generate_main {
-    ($name: path) => {
-        fn main() {
-            $crate::pir::respire_harness::harness_main::<$name>();
-        }
-    };
-}
-
-#[derive(Parser, Debug)]
-struct Args {
-    trials: usize,
+/// Mean and standard deviation of one stage over all trials, in nanoseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct StageSummary {
+    pub stage: &'static str,
+    pub mean_ns: u64,
+    pub stddev_ns: u64,
 }
 
-pub fn harness_main<ThePIR: PIR>() {
-    env_logger::init();
-    let args = Args::parse();
-
-    let mut rng = ChaCha20Rng::from_entropy();
-    let record_gen = |_| rng.gen_range(0_usize..ThePIR::NUM_RECORDS);
-    let run_result =
-        run_pir::<ThePIR, _>((0usize..args.trials * ThePIR::BATCH_SIZE).map(record_gen));
-
-    let trial_times = run_result
-        .all_trial_times
-        .iter()
-        .map(|tt| tt.as_vec())
-        .collect_vec();
-    let stat_names = trial_times[0].iter().map(|x| x.0).collect_vec();
+/// One summary per stage, in the order the stages ran; empty if there are no trials.
+pub fn summarize_trials(trials: &[TrialResult]) -> Vec<StageSummary> {
+    let trial_times = trials.iter().map(|t| t.times.as_vec()).collect_vec();
+    let Some(first) = trial_times.first() else {
+        return Vec::new();
+    };
+    let stat_names = first.iter().map(|x| x.0).collect_vec();
     for tt in trial_times.iter() {
         assert_eq!(tt.iter().map(|x| x.0).collect_vec(), stat_names);
     }
 
-    let mut means = Vec::with_capacity(trial_times.len());
-    let mut stddevs = Vec::with_capacity(trial_times.len());
-
-    eprintln!("Summary times:");
-
+    let mut result = Vec::with_capacity(stat_names.len());
     for (stat_i, stat_name) in stat_names.iter().copied().enumerate() {
         let mut sum = 0_f64;
         let mut sum_sq = 0_f64;
@@ -312,25 +361,145 @@ pub fn harness_main<ThePIR: PIR>() {
         }
 
         let mean = sum / trial_times.len() as f64;
-        let stddev = (sum_sq / trial_times.len() as f64 - mean.powi(2)).sqrt();
-
-        let mean = mean.round() as u64;
-        let stddev = stddev.round() as u64;
-        means.push(mean);
-        stddevs.push(stddev);
-        eprintln!(
-            "    {}: {:?} mean, {:?} stddev ({:.3}%)",
-            stat_name,
-            Duration::from_nanos(mean),
-            Duration::from_nanos(stddev),
-            stddev as f64 / mean as f64 / 100_f64,
+        let stddev = (sum_sq / trial_times.len() as f64 - mean.powi(2))
+            .max(0_f64)
+            .sqrt();
+        result.push(StageSummary {
+            stage: stat_name,
+            mean_ns: mean.round() as u64,
+            stddev_ns: stddev.round() as u64,
+        });
+    }
+    result
+}
+
+#[derive(Serialize)]
+pub struct TrialReport {
+    pub indices: Vec<usize>,
+    pub times_ns: Stats<u64>,
+    pub end_to_end_ns: u64,
+    pub correct: bool,
+    pub failed_indices: Vec<usize>,
+}
+
+/// A whole run as a single document: parameters, estimated costs, and measured times.
+#[derive(Serialize)]
+pub struct RunReport<Params> {
+    pub summary: PIRSummary<Params>,
+    pub avx2: bool,
+    pub init_times_ns: Stats<u64>,
+    pub init_end_to_end_ns: u64,
+    pub trials: Vec<TrialReport>,
+    pub trial_times: Vec<StageSummary>,
+    pub failures: usize,
+}
+
+fn as_nanos(stats: &Stats<Duration>) -> Stats<u64> {
+    stats.map(|d| d.as_nanos() as u64)
+}
+
+impl<Params> RunReport<Params> {
+    pub fn new<ThePIR: PIR<Params = Params>>(run_result: &RunResult) -> Self {
+        RunReport {
+            summary: ThePIR::summary(),
+            avx2: has_avx2(),
+            init_times_ns: as_nanos(&run_result.init_times),
+            init_end_to_end_ns: run_result.init_end_to_end.as_nanos() as u64,
+            trials: run_result
+                .trials
+                .iter()
+                .map(|t| TrialReport {
+                    indices: t.indices.clone(),
+                    times_ns: as_nanos(&t.times),
+                    end_to_end_ns: t.end_to_end.as_nanos() as u64,
+                    correct: t.failed_indices.is_empty(),
+                    failed_indices: t.failed_indices.clone(),
+                })
+                .collect(),
+            trial_times: summarize_trials(&run_result.trials),
+            failures: run_result.failures(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("Summary times:\n");
+        for s in self.trial_times.iter() {
+            out += &format!(
+                "    {}: {:?} mean, {:?} stddev ({:.3}%)\n",
+                s.stage,
+                Duration::from_nanos(s.mean_ns),
+                Duration::from_nanos(s.stddev_ns),
+                s.stddev_ns as f64 / s.mean_ns as f64 * 100_f64,
+            );
+        }
+        out += "mean, stddev in CSV format (times in nanoseconds):\n";
+        out += &format!("{}\n", self.trial_times.iter().map(|s| s.stage).join(", "));
+        out += &format!(
+            "{}\n",
+            self.trial_times.iter().map(|s| s.mean_ns).join(", ")
+        );
+        out += &format!(
+            "{}\n",
+            self.trial_times.iter().map(|s| s.stddev_ns).join(", ")
         );
+        out
     }
 
-    eprintln!("mean, stddev in CSV format (times in nanoseconds):");
-    eprintln!("{}", stat_names.join(", "));
-    eprintln!("{}", means.iter().map(u64::to_string).join(", "));
-    eprintln!("{}", stddevs.iter().map(u64::to_string).join(", "));
+    /// One row per trial, with times in nanoseconds; indices are separated by `;`.
+    pub fn to_csv(&self) -> String {
+        let stat_names = self
+            .trials
+            .first()
+            .map(|t| t.times_ns.as_vec().into_iter().map(|x| x.0).collect_vec())
+            .unwrap_or_default();
+        let mut out = format!(
+            "trial, indices, correct, {}, end_to_end\n",
+            stat_names.join(", ")
+        );
+        for (i, t) in self.trials.iter().enumerate() {
+            out += &format!(
+                "{}, {}, {}, {}, {}\n",
+                i,
+                t.indices.iter().join(";"),
+                t.correct,
+                t.times_ns.as_vec().iter().map(|x| x.1).join(", "),
+                t.end_to_end_ns,
+            );
+        }
+        out
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct HarnessArgs {
+    /// Number of trials (queries of BATCH_SIZE records) to run
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub trials: usize,
+    /// Refuse to run parameter sets with a lower estimated security level (bits)
+    #[arg(long, default_value_t = DEFAULT_MIN_SECURITY_BITS)]
+    pub min_security_bits: f64,
+}
+
+///
+/// Runs the trials, prints a summary of the times on stderr, and returns the full report. Fails
+/// without running anything if the estimated security is below `args.min_security_bits`.
+///
+pub fn harness_run<ThePIR: PIR>(args: &HarnessArgs) -> Result<RunReport<ThePIR::Params>, String> {
+    let security_bits = ThePIR::summary().security_bits;
+    if security_bits < args.min_security_bits {
+        return Err(format!(
+            "estimated security {:.1} bits is below the floor of {} bits",
+            security_bits, args.min_security_bits
+        ));
+    }
+
+    let mut rng = ChaCha20Rng::from_entropy();
+    let record_gen = |_| rng.gen_range(0_usize..ThePIR::NUM_RECORDS);
+    let run_result =
+        run_pir::<ThePIR, _>((0usize..args.trials * ThePIR::BATCH_SIZE).map(record_gen));
+    let report = RunReport::new::<ThePIR>(&run_result);
+    eprint!("{}", report.to_text());
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -340,7 +509,24 @@ mod test {
     use crate::math::int_mod_cyclo::IntModCyclo;
     use crate::math::int_mod_poly::IntModPoly;
     use crate::math::matrix::Matrix;
-    use crate::pir::respire::Respire;
+    use crate::math::ring_elem::RingElement;
+    use crate::math::utils::ceil_log;
+    use crate::pir::cuckoo_respire::CuckooRespireImpl;
+    use crate::pir::db_storage::{write_db_file, ChunkedFileStorage, MmapStorage};
+    use crate::pir::merkle;
+    use crate::pir::noise_profile::NoiseStage;
+    use crate::pir::pir::{record_with_checksum, RECORD_CHECKSUM_LEN};
+    use crate::pir::respire::{AggregateError, ExtractConfig, ParamsError, QueryError, Respire};
+    use crate::pir::shard::{
+        serve_shard_connection, shard_buckets, ShardCoordinator, ShardRespire,
+    };
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+
+    use RespireBatch as Batch;
+    use RespireSmall as Small;
+    use RESPIRE_BATCH_PARAMS as BATCH;
+    use RESPIRE_SMALL_PARAMS as SMALL;
 
     #[test]
     fn test_regev() {
@@ -370,18 +556,37 @@ mod test {
         let auto_key = RespireTest::auto_setup::<
             { RESPIRE_TEST_PARAMS.T_PROJ_SHORT },
             { RESPIRE_TEST_PARAMS.Z_PROJ_SHORT },
+            { RESPIRE_TEST_PARAMS.DROP_PROJ_SHORT },
         >(3, &s);
         let x = <RespireTest as Respire>::RingP::from(IntModPoly::x());
         let encrypt = RespireTest::encode_rlwe(&s, &x.scale_up_into());
         let encrypt_auto = RespireTest::auto_hom::<
             { RESPIRE_TEST_PARAMS.T_PROJ_SHORT },
             { RESPIRE_TEST_PARAMS.Z_PROJ_SHORT },
+            { RESPIRE_TEST_PARAMS.DROP_PROJ_SHORT },
         >(&auto_key, &encrypt);
         let decrypt: <RespireTest as Respire>::RingP =
             RespireTest::decode_rlwe(&s, &encrypt_auto).round_down_into();
         assert_eq!(decrypt, &(&x * &x) * &x);
     }
 
+    #[test]
+    fn test_auto_hom_approx() {
+        // Keep the long base, but drop its lowest digits.
+        const DROP: usize = 4;
+        const LEN: usize = RESPIRE_TEST_PARAMS.T_PROJ_LONG - DROP;
+        const BASE: u64 = RESPIRE_TEST_PARAMS.Z_PROJ_LONG;
+
+        let s = RespireTest::encode_setup();
+        let auto_key = RespireTest::auto_setup::<LEN, BASE, DROP>(3, &s);
+        let x = <RespireTest as Respire>::RingP::from(IntModPoly::x());
+        let encrypt = RespireTest::encode_rlwe(&s, &x.scale_up_into());
+        let encrypt_auto = RespireTest::auto_hom::<LEN, BASE, DROP>(&auto_key, &encrypt);
+        let decrypt: <RespireTest as Respire>::RingP =
+            RespireTest::decode_rlwe(&s, &encrypt_auto).round_down_into();
+        assert_eq!(decrypt, &(&x * &x) * &x);
+    }
+
     #[test]
     fn test_regev_to_gsw() {
         let s = RespireTest::encode_setup();
@@ -401,6 +606,44 @@ mod test {
         assert_eq!(decrypted.round_down_into(), mu);
     }
 
+    #[test]
+    fn test_select_hom_assign() {
+        let s = RespireTest::encode_setup();
+        let mu0 = <RespireTest as Respire>::RingP::from(3_u64);
+        let mu1 = <RespireTest as Respire>::RingP::from(9_u64);
+        let c0 = RespireTest::encode_rlwe(&s, &mu0.scale_up_into());
+        let c1 = RespireTest::encode_rlwe(&s, &mu1.scale_up_into());
+
+        let mut diff = Matrix::zero();
+        let mut g_inv = Matrix::zero();
+        for (bit, mu) in [(0_u64, &mu0), (1_u64, &mu1)] {
+            let b = RespireTest::encode_gsw(&s, &IntModCyclo::from(bit));
+            let expected = RespireTest::select_hom(&c0, &c1, &b);
+
+            let mut actual = c0.clone();
+            RespireTest::select_hom_assign(&mut actual, &c1, &b, &mut diff, &mut g_inv);
+            assert_eq!(actual, expected);
+
+            // The batched selection with a prepared GSW encoding gives the same result
+            let mut batch = vec![c0.clone(), c1.clone(), c0.clone()];
+            let mut g_invs = vec![Matrix::zero(); 3];
+            RespireTest::select_hom_batch_assign(
+                &mut batch,
+                &[c1.clone(), c0.clone(), c1.clone()],
+                &RespireTest::gsw_prepare(&b),
+                &mut diff,
+                &mut g_invs,
+            );
+            assert_eq!(batch[0], expected);
+            assert_eq!(batch[2], expected);
+            assert_eq!(batch[1], RespireTest::select_hom(&c1, &c0, &b));
+
+            let decoded: <RespireTest as Respire>::RingP =
+                RespireTest::decode_rlwe(&s, &actual).round_down_into();
+            assert_eq!(&decoded, mu);
+        }
+    }
+
     #[test]
     fn test_scal_to_vec() {
         let s_scal = RespireTest::encode_setup();
@@ -455,6 +698,588 @@ mod test {
     //     assert_eq!(bytes, decoded);
     // }
 
+    #[test]
+    fn test_params_validate() {
+        const VALID: Result<(), ParamsErrors> = FactoryParams::batch_256(194, 8, 7, 7).validate();
+        assert_eq!(VALID, Ok(()));
+        assert_eq!(FactoryParams::single_record_256(9, 9).validate(), Ok(()));
+        assert_eq!(FactoryParams::single_record_256(12, 1).validate(), Ok(()));
+
+        let invalid = FactoryParams {
+            P: 12,
+            Q2: 16760831,
+            D3: 1024,
+            ..FactoryParams::single_record_256(9, 9)
+        };
+        let errors = invalid.validate().unwrap_err();
+        assert_eq!(
+            errors.iter().collect_vec(),
+            vec![
+                ParamsError::D3DoesNotDivideD2,
+                ParamsError::Q2NotNttPrime,
+                ParamsError::PNotPowerOfTwo,
+                ParamsError::PDoesNotDivideQ3,
+            ]
+        );
+        assert!(errors.to_string().contains("P must divide Q3"));
+    }
+
+    #[test]
+    fn test_params_security() {
+        let params = FactoryParams::single_record_256(9, 9).expand();
+        assert!(params.expand_secure(DEFAULT_MIN_SECURITY_BITS).is_ok());
+
+        // A narrow compression error over a large modulus is easy to attack
+        let weak = RespireParams {
+            Q2: 4294955009,
+            ERROR_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+            SECRET_WIDTH_COMPRESS_MILLIONTHS: 1_000_000,
+            ..params
+        };
+        let err = weak.expand_secure(DEFAULT_MIN_SECURITY_BITS).unwrap_err();
+        assert!(err.contains("D2/Q2"), "{err}");
+    }
+
+    #[test]
+    fn test_noise_profile() {
+        let mut rng = ChaCha20Rng::from_seed([7; 32]);
+        let profile = Small::noise_profile(2, &mut rng);
+        assert_eq!(profile.answers, 2);
+        assert_eq!(profile.failures, 0);
+        assert_eq!(profile.stages.len(), NoiseStage::ALL.len());
+        for stage in profile.stages.iter() {
+            assert!(stage.mean_bits <= stage.max_bits, "{profile}");
+        }
+        assert_eq!(profile.optimistic_stages().count(), 0, "{profile}");
+        assert!(!profile.tail.is_optimistic(), "{profile}");
+    }
+
+    #[test]
+    fn test_gadget_drop() {
+        const DROPPED: RespireParamsExpanded = RespireParams {
+            DROP_PROJ_SHORT: 1,
+            DROP_PROJ_LONG: 2,
+            DROP_RLWE_TO_GSW: 1,
+            ..FactoryParams::single_record_256(3, 3).expand()
+        }
+        .expand();
+        type Dropped = respire!(DROPPED);
+        assert!(DROPPED.noise_breakdown().error_rate.log2() < -40_f64);
+
+        let run_result = run_pir::<Dropped, _>([0_usize, 201].into_iter());
+        assert_eq!(run_result.failures(), 0);
+
+        // The measured noise must stay within the estimate that accounts for the dropped digits
+        let mut rng = ChaCha20Rng::from_seed([8; 32]);
+        let profile = Dropped::noise_profile(2, &mut rng);
+        assert_eq!(profile.failures, 0);
+        assert_eq!(profile.optimistic_stages().count(), 0, "{profile}");
+        assert!(!profile.tail.is_optimistic(), "{profile}");
+    }
+
+    #[test]
+    fn test_noise_breakdown_presets() {
+        // log2 of the error rate, then the widths in bits after query expansion (RLWE, GSW),
+        // first dimension, fold, rotate, project, ring pack and vector pack. Computed by the
+        // estimator before it moved onto `pir::noise`; the rewrite must not change them.
+        let presets = [
+            (
+                FactoryParams::single_record_256(3, 3),
+                -258.7688,
+                [
+                    32.9886, 31.0682, 42.9886, 45.3653, 45.7230, 45.7230, 45.7230, 45.7230,
+                ],
+            ),
+            (
+                FactoryParams::single_record_256(8, 8),
+                -139.2944,
+                [
+                    32.9886, 31.0682, 45.4886, 46.3195, 46.4334, 46.4334, 46.4334, 46.4334,
+                ],
+            ),
+            (
+                FactoryParams::single_record_256(9, 9),
+                -110.0791,
+                [
+                    32.9886, 31.0682, 45.9886, 46.5630, 46.6461, 46.6461, 46.6461, 46.6461,
+                ],
+            ),
+            (
+                FactoryParams::batch_256(8, 2, 3, 3),
+                -293.4660,
+                [
+                    32.9886, 31.0682, 42.9886, 45.3653, 45.7230, 45.7230, 45.7230, 45.7230,
+                ],
+            ),
+            (
+                FactoryParams::batch_256(13, 4, 8, 8),
+                -126.0916,
+                [
+                    32.9886, 31.0682, 45.4886, 46.3195, 46.4334, 46.4334, 46.4334, 46.4334,
+                ],
+            ),
+            (
+                FactoryParams::batch_256(194, 8, 7, 7),
+                -156.5892,
+                [
+                    32.9886, 31.0682, 44.9886, 46.1182, 46.2652, 46.2652, 46.2652, 46.2652,
+                ],
+            ),
+        ];
+        for (factory, error_rate_log2, widths) in presets {
+            let params = factory.expand().expand();
+            let breakdown = params.noise_breakdown();
+            let actual = [
+                breakdown.query_expand_rlwe,
+                breakdown.query_expand_gsw,
+                breakdown.first_dim,
+                breakdown.fold,
+                breakdown.rotate,
+                breakdown.project,
+                breakdown.ring_pack,
+                breakdown.vector_pack,
+            ]
+            .map(|noise| noise.width_bits());
+            for (actual, expected) in actual.iter().zip(widths) {
+                assert!((actual - expected).abs() < 1e-3, "{params:?}: {actual:?}");
+            }
+            let actual = params.error_rate_estimate().log2();
+            assert!(
+                (actual - error_rate_log2).abs() < 1e-3,
+                "{params:?}: {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_report() {
+        let run_result = run_pir::<Small, _>([0_usize, 201].into_iter());
+        assert_eq!(run_result.failures(), 0);
+        let report = RunReport::new::<Small>(&run_result);
+        assert_eq!(report.trial_times.last().unwrap().stage, "total");
+        assert_eq!(report.summary.params.NU1, 3);
+        assert_eq!(report.summary.query_size, SMALL.query_size());
+        assert_eq!(report.trials[1].indices, [201]);
+        assert!(report.trials[1].correct);
+        assert_eq!(report.failures, 0);
+
+        let csv = report.to_csv();
+        let lines = csv.lines().collect_vec();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("trial, indices, correct, "));
+        assert!(lines[2].starts_with("1, 201, true, "));
+    }
+
+    #[test]
+    fn test_no_trials() {
+        assert!(HarnessArgs::try_parse_from(["harness", "0"]).is_err());
+        assert_eq!(
+            HarnessArgs::try_parse_from(["harness", "2"])
+                .unwrap()
+                .trials,
+            2
+        );
+        assert!(summarize_trials(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_harness_run_security_floor() {
+        let args =
+            HarnessArgs::try_parse_from(["harness", "1", "--min-security-bits", "1000"]).unwrap();
+        let err = harness_run::<RespireTest>(&args).err().unwrap();
+        assert!(err.contains("below the floor"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_query() {
+        let (db, _) = Small::encode_db(|_| Default::default(), None);
+        let (qk, pp) = Small::setup(None);
+        let (mut q, _) = Small::query(&qk, &[5], &(), None);
+        assert_eq!(Small::validate_query(&q), Ok(()));
+        q[0].1 .1.pop();
+        assert!(matches!(
+            Small::validate_query(&q),
+            Err(QueryError::GswCount { .. })
+        ));
+        assert!(matches!(
+            Small::try_answer(&pp, &db, &q, None, None),
+            Err(QueryError::GswCount { .. })
+        ));
+        q.clear();
+        assert_eq!(
+            Small::validate_query(&q),
+            Err(QueryError::BatchSize {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_packed() {
+        assert_eq!(Batch::QUERY_PACK_SIZE, BATCH.query_pack_size());
+        assert!(BATCH.query_size() < BATCH.BATCH_SIZE * BATCH.query_one_size());
+
+        let indices = [0_usize, 1, 37, 100, 128, 200, 254, 255];
+        let (qk, _) = Batch::setup(None);
+        let (q, _) = Batch::query(&qk, &indices, &(), None);
+        assert_eq!(q.len(), 1);
+        assert_eq!(Batch::validate_query_packed(&q[0], indices.len()), Ok(()));
+        assert_eq!(run_pir::<Batch, _>(indices.into_iter()).failures(), 0);
+
+        type Cuckoo = CuckooRespireImpl<4, 8, 400, Batch>;
+        let cuckoo_indices = [3_usize, 150, 151, 399];
+        assert_eq!(
+            run_pir::<Cuckoo, _>(cuckoo_indices.into_iter()).failures(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_wide_first_dim() {
+        // 2^12 rows: the first dimension is selected by two RLWE query ciphertexts
+        const WIDE: RespireParamsExpanded =
+            FactoryParams::single_record_256(12, 1).expand().expand();
+        type Wide = respire!(WIDE);
+
+        // Doubling the rows adds half a bit to the first dimension noise width
+        let narrow = FactoryParams::single_record_256(11, 1).expand().expand();
+        let first_dim_growth = WIDE.noise_breakdown().first_dim.width_bits()
+            - narrow.noise_breakdown().first_dim.width_bits();
+        assert!((first_dim_growth - 0.5).abs() < 1e-9);
+        assert!(WIDE.noise_breakdown().error_rate.log2() < -30_f64);
+        assert_eq!(
+            WIDE.query_one_size(),
+            3 * 32 + (4096 + Wide::GSW_COUNT) * ceil_log(2, WIDE.Q1) / 8
+        );
+
+        let (qk, _) = Wide::setup(None);
+        let (q, _) = Wide::query(&qk, &[5], &(), None);
+        assert_eq!(q[0].0.len(), 2);
+        assert_eq!(Wide::validate_query_packed(&q[0], 1), Ok(()));
+
+        // A row selected by the second ciphertext
+        let idx = 3000 * Wide::DB_SIZE / Wide::RLWE_COUNT + 5;
+        assert_eq!(run_pir::<Wide, _>([idx].into_iter()).failures(), 0);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut rng = ChaCha20Rng::from_seed([3; 32]);
+        let records = (0..Small::DB_SIZE)
+            .map(|_| {
+                let mut bytes = [0_u8; SMALL.BYTES_PER_RECORD];
+                rng.fill(bytes.as_mut_slice());
+                <Small as PIR>::RecordBytes::from_bytes(&bytes).unwrap()
+            })
+            .collect_vec();
+        let (db, _) = Small::encode_db(|idx| records[idx].clone(), None);
+        let (qk, pp) = Small::setup(None);
+
+        // Records in the same column are DB_SIZE / RLWE_COUNT apart
+        let columns = Small::DB_SIZE / Small::RLWE_COUNT;
+        let indices = [5, 5 + columns, 5 + 4 * columns, 5 + 7 * columns];
+        let q = Small::query_sum_one(&qk, &indices, None).unwrap();
+        let r = Small::answer(&pp, &db, &vec![q], None, None);
+        let expected = indices
+            .iter()
+            .map(|&idx| Small::encode_record(&records[idx]))
+            .fold(<Small as Respire>::Record::zero(), |acc, x| &acc + &x);
+        assert_eq!(Small::extract_sum_one(&qk, &r[0], None)[0], expected);
+
+        assert_eq!(
+            Small::query_sum_one(&qk, &[5, 6], None).unwrap_err(),
+            AggregateError::ColumnMismatch {
+                index: 6,
+                column: 5
+            }
+        );
+        assert_eq!(
+            Small::query_sum_one(&qk, &[5, 5], None).unwrap_err(),
+            AggregateError::DuplicateIndex { index: 5 }
+        );
+        assert_eq!(
+            Small::query_sum_one(&qk, &[], None).unwrap_err(),
+            AggregateError::Empty
+        );
+    }
+
+    #[test]
+    fn test_file_backed_db() {
+        let records_generator = filled_record::<Small>;
+        let (db, _) = Small::encode_db(records_generator, None);
+        let path = std::env::temp_dir().join(format!(
+            "respire-{}-test_file_backed_db",
+            std::process::id()
+        ));
+        write_db_file(&path, db.as_ref()).unwrap();
+
+        let file_dbs: [<Small as PIR>::Database; 2] = [
+            Box::new(MmapStorage::open(&path).unwrap()),
+            Box::new(ChunkedFileStorage::open(&path, 1 << 16, 2).unwrap()),
+        ];
+        let (qk, pp) = Small::setup(None);
+        let (q, st) = Small::query(&qk, &[201], &(), None);
+        for file_db in file_dbs {
+            let r = Small::answer(&pp, &file_db, &q, None, None);
+            assert_eq!(Small::extract(&qk, &r, &st, None), [records_generator(201)]);
+        }
+
+        // Streaming the encoding, 5 packed records at a time, writes the same file
+        let streamed_path = path.with_extension("streamed");
+        let buffer_bytes = 5 * SMALL.D1 * size_of::<u64>();
+        Small::encode_db_file(&streamed_path, records_generator, buffer_bytes, None).unwrap();
+        assert_eq!(
+            std::fs::read(&streamed_path).unwrap(),
+            std::fs::read(&path).unwrap()
+        );
+        std::fs::remove_file(&streamed_path).unwrap();
+
+        // A file that can no longer be read fails the first dimension instead of panicking
+        let chunked = ChunkedFileStorage::open(&path, 1 << 16, 2).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (rlwes, _, _) =
+            Small::answer_query_unpack(&pp, &Small::query_one(&qk, 201, None), None, None);
+        let file_db: <Small as PIR>::Database = Box::new(chunked);
+        assert!(Small::answer_first_dim(&file_db, &rlwes).is_err());
+    }
+
+    #[test]
+    fn test_extract_confidence() {
+        let records_generator = |idx: usize| {
+            let payload = [(idx % 256) as u8; SMALL.BYTES_PER_RECORD - RECORD_CHECKSUM_LEN];
+            <Small as PIR>::RecordBytes::from_bytes(&record_with_checksum(&payload)).unwrap()
+        };
+        let (db, _) = Small::encode_db(records_generator, None);
+        let (qk, pp) = Small::setup(None);
+        let (q, st) = Small::query(&qk, &[201], &(), None);
+        let mut r = Small::answer(&pp, &db, &q, None, None);
+        assert_eq!(Small::extract(&qk, &r, &st, None), [records_generator(201)]);
+
+        let config = ExtractConfig {
+            checksum: true,
+            ..ExtractConfig::for_params(&SMALL)
+        };
+        assert!(config.margin_threshold > 0_f64 && config.margin_threshold < 1_f64);
+        let checked = Small::extract_checked(&qk, &r, &config, None);
+        assert_eq!(checked.len(), 1);
+        let (record, confidence) = checked[0].clone();
+        assert_eq!(record, records_generator(201));
+        assert!(confidence.mean_margin >= confidence.min_margin);
+        assert_eq!(confidence.checksum_ok, Some(true));
+        assert!(!confidence.likely_corrupted);
+
+        // Shift the first coefficient exactly onto a rounding boundary
+        let step = SMALL.Q3 / SMALL.P;
+        let unrounded = u64::from(Small::extract_ring_one_unrounded(&qk, &r[0])[(0, 0)].coeff[0]);
+        let offset = (unrounded + step / 2) % step;
+        let mut boundary = r.clone();
+        boundary[0].1[0].coeff[0] += IntMod::from(step - offset);
+        let (_, boundary_confidence) = Small::extract_checked(&qk, &boundary, &config, None)[0];
+        assert_eq!(boundary_confidence.min_margin, 0_f64);
+        assert!(boundary_confidence.likely_corrupted);
+
+        // A whole step keeps the margins, but changes the record, which the checksum catches
+        r[0].1[0].coeff[0] += IntMod::from(step);
+        let (shifted, shifted_confidence) =
+            Small::extract_checked(&qk, &r, &config, None)[0].clone();
+        assert_ne!(shifted, records_generator(201));
+        assert_eq!(shifted_confidence.min_margin, confidence.min_margin);
+        assert_eq!(shifted_confidence.mean_margin, confidence.mean_margin);
+        assert_eq!(shifted_confidence.checksum_ok, Some(false));
+        assert!(shifted_confidence.likely_corrupted);
+    }
+
+    #[test]
+    fn test_authenticated_records() {
+        type Cuckoo = CuckooRespireImpl<4, 8, 400, Batch>;
+
+        let len = merkle::payload_len(Cuckoo::BYTES_PER_RECORD, Cuckoo::NUM_RECORDS).unwrap();
+        let payload = |idx: usize| vec![(idx % 256) as u8; len];
+        let (db, hint, root) = merkle::encode_authenticated_db::<Cuckoo, _>(payload, None);
+
+        let indices = [3_usize, 150, 151, 399];
+        let (qk, pp) = Cuckoo::setup(None);
+        let (q, st) = Cuckoo::query(&qk, &indices, &hint, None);
+        let r = Cuckoo::answer(&pp, &db, &q, None, None);
+        let mut records = Cuckoo::extract(&qk, &r, &st, None);
+        let payloads = merkle::verify_extracted::<Cuckoo>(&root, &indices, &records).unwrap();
+        assert_eq!(payloads, indices.map(payload));
+
+        // A wrong record, or the right record for another index, is rejected
+        records.swap(0, 1);
+        assert_eq!(
+            merkle::verify_extracted::<Cuckoo>(&root, &indices, &records),
+            Err(merkle::VerifyError::RootMismatch { idx: 3 })
+        );
+        let mut bytes = records[0].as_bytes().to_vec();
+        bytes[0] ^= 1;
+        records[0] = <Cuckoo as PIR>::RecordBytes::from_bytes(&bytes).unwrap();
+        assert!(merkle::verify_extracted::<Cuckoo>(&root, &indices[1..2], &records[..1]).is_err());
+        assert_eq!(
+            merkle::verify_extracted::<Cuckoo>(&root, &indices, &records[..3]),
+            Err(merkle::VerifyError::RecordCount {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_mac_authenticated_records() {
+        // Paths of the benchmarked databases do not fit in their records, a MAC does
+        const SINGLE: RespireParamsExpanded =
+            FactoryParams::single_record_256(8, 8).expand().expand();
+        const BATCH: RespireParamsExpanded =
+            FactoryParams::batch_256(13, 4, 8, 8).expand().expand();
+        for params in [&SINGLE, &BATCH] {
+            assert_eq!(
+                merkle::payload_len(params.BYTES_PER_RECORD, params.db_size()),
+                None
+            );
+            assert!(merkle::mac_payload_len(params.BYTES_PER_RECORD).is_some());
+        }
+
+        let key = [42_u8; 32];
+        let len = merkle::mac_payload_len(Small::BYTES_PER_RECORD).unwrap();
+        let payload = |idx: usize| vec![(idx % 251) as u8; len];
+        let (db, hint) = merkle::encode_mac_db::<Small, _>(&key, payload, None);
+
+        let (qk, pp) = Small::setup(None);
+        let (q, st) = Small::query(&qk, &[201], &hint, None);
+        let r = Small::answer(&pp, &db, &q, None, None);
+        let records = Small::extract(&qk, &r, &st, None);
+        let payloads = merkle::verify_extracted_mac::<Small>(&key, &[201], &records).unwrap();
+        assert_eq!(payloads, [payload(201)]);
+        assert_eq!(
+            merkle::verify_extracted_mac::<Small>(&key, &[202], &records),
+            Err(merkle::VerifyError::MacMismatch { idx: 202 })
+        );
+        assert_eq!(
+            merkle::verify_extracted_mac::<Small>(&key, &[], &records),
+            Err(merkle::VerifyError::RecordCount {
+                expected: 0,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_masks() {
+        let records_generator = filled_record::<Small>;
+        let (db, _) = Small::encode_db(records_generator, None);
+        let (qk, pp) = Small::setup(None);
+
+        // Offline: masks for two queries. Online: each query uses up its own masks.
+        assert_eq!(Small::query_mask_count(1), 2);
+        let mut masks = Small::query_masks(&qk, 2 * Small::query_mask_count(1), None);
+        let mut seeds = Vec::new();
+        for idx in [201, 17] {
+            let q = Small::query_packed_online(&mut masks, &[idx], None);
+            assert_eq!(Small::validate_query_packed(&q, 1), Ok(()));
+            seeds.push(q.1 .0);
+            let r = Small::answer(&pp, &db, &vec![q], None, None);
+            assert_eq!(Small::extract(&qk, &r, &(), None), [records_generator(idx)]);
+        }
+        assert!(masks.is_empty());
+        assert_ne!(seeds[0], seeds[1]);
+    }
+
+    #[test]
+    fn test_multi_plane() {
+        let plane_record = |plane: usize, idx: usize| {
+            let bytes = [((3 * idx + plane) % 256) as u8; SMALL.BYTES_PER_RECORD];
+            <Small as PIR>::RecordBytes::from_bytes(&bytes).unwrap()
+        };
+        let planes = (0..3)
+            .map(|plane| Small::encode_db(|idx| plane_record(plane, idx), None).0)
+            .collect_vec();
+        let (qk, pp) = Small::setup(None);
+
+        let q = Small::query_one(&qk, 201, None);
+        let rs = Small::answer_one_planes(&pp, &planes, &q, None, None);
+        assert_eq!(rs.len(), planes.len());
+        for (plane, r) in rs.iter().enumerate() {
+            assert_eq!(
+                Small::extract_one(&qk, r, None)[0],
+                plane_record(plane, 201)
+            );
+        }
+
+        let (q, st) = Small::query(&qk, &[77], &(), None);
+        let rs = Small::answer_planes(&pp, &planes, &q, None, None);
+        assert_eq!(rs[0], Small::answer(&pp, &planes[0], &q, None, None));
+        for (plane, r) in rs.iter().enumerate() {
+            assert_eq!(Small::extract(&qk, r, &st, None), [plane_record(plane, 77)]);
+        }
+    }
+
+    #[test]
+    fn test_sharded_db() {
+        type Cuckoo = CuckooRespireImpl<4, 8, 400, Batch>;
+
+        let records_generator = filled_record::<Small>;
+
+        // Shard servers run on threads, each answering one coordinator over loopback
+        fn spawn_shards<'scope, R: ShardRespire>(
+            scope: &'scope thread::Scope<'scope, '_>,
+            shards: &'scope [Vec<<R as PIR>::Database>],
+        ) -> Vec<SocketAddr>
+        where
+            <R as PIR>::Database: Sync,
+        {
+            shards
+                .iter()
+                .map(|dbs| {
+                    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                    let addr = listener.local_addr().unwrap();
+                    scope.spawn(move || {
+                        let (stream, _) = listener.accept().unwrap();
+                        serve_shard_connection::<R>(stream, dbs).unwrap();
+                    });
+                    addr
+                })
+                .collect_vec()
+        }
+
+        // 8 columns over 3 shards
+        let (db, _) = Small::encode_db(records_generator, None);
+        let column_shards = Small::shard_columns(&db, 3)
+            .into_iter()
+            .map(|shard| vec![shard])
+            .collect_vec();
+        let (qk, pp) = Small::setup(None);
+        let indices = [201];
+        let (q, st) = Small::query(&qk, &indices, &(), None);
+        let r = thread::scope(|scope| {
+            let addrs = spawn_shards::<Small>(scope, &column_shards);
+            let mut coordinator = ShardCoordinator::<Small>::connect(&addrs).unwrap();
+            assert_eq!(coordinator.num_dbs(), 3);
+            coordinator.answer_columns(&pp, &q, None).unwrap()
+        });
+        assert_eq!(r, Small::answer(&pp, &db, &q, None, None));
+        assert_eq!(
+            Small::extract(&qk, &r, &st, None),
+            indices.map(records_generator)
+        );
+
+        // 8 buckets over 2 shards
+        let (dbs, hint) = Cuckoo::encode_db(records_generator, None);
+        let bucket_shards = shard_buckets(dbs, 2);
+        let (qk, pp) = Cuckoo::setup(None);
+        let indices = [3_usize, 150, 151, 399];
+        let (q, st) = Cuckoo::query(&qk, &indices, &hint, None);
+        let r = thread::scope(|scope| {
+            let addrs = spawn_shards::<Batch>(scope, &bucket_shards);
+            let mut coordinator = ShardCoordinator::<Batch>::connect(&addrs).unwrap();
+            coordinator.answer_buckets(&pp, &q, None).unwrap()
+        });
+        assert_eq!(
+            Cuckoo::extract(&qk, &r, &st, None),
+            indices.map(records_generator)
+        );
+    }
+
     #[test]
     fn test_respire_one() {
         run_pir::<RespireTest, _>([711_711].into_iter());
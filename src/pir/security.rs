@@ -0,0 +1,360 @@
+//! Lattice security estimates for the (R)LWE instances used by Respire.
+//!
+//! These are core-SVP style estimates of the primal uSVP and dual attacks, in the spirit of the
+//! lattice estimator: BKZ with block size `beta` on a lattice of dimension `d` is costed at
+//! `0.292 beta + 16.4 + log2(8 d)` bits, and the root Hermite factor `delta(beta)` follows the
+//! usual asymptotic formula. They ignore hybrid and algebraic attacks, so they are a sanity check
+//! rather than a replacement for a full estimate.
+
+use std::f64::consts::{E, LN_2, PI, SQRT_2};
+use std::fmt;
+
+/// Security level below which parameter sets are refused by default.
+pub const DEFAULT_MIN_SECURITY_BITS: f64 = 128_f64;
+
+/// An LWE instance with dimension `n`, modulus `q`, and error and secret standard deviations.
+#[derive(Clone, Copy, Debug)]
+pub struct LWEInstance {
+    pub n: usize,
+    pub q: f64,
+    pub stddev_e: f64,
+    pub stddev_s: f64,
+}
+
+impl LWEInstance {
+    /// Gaussian error and secret of the given subgaussian width.
+    pub const fn gaussian(n: usize, q: f64, width_e: f64, width_s: f64) -> Self {
+        Self {
+            n,
+            q,
+            stddev_e: width_to_stddev(width_e),
+            stddev_s: width_to_stddev(width_s),
+        }
+    }
+}
+
+/// Standard deviation of a discrete Gaussian of width `s`, i.e. `s / sqrt(2 pi)`.
+pub const fn width_to_stddev(width: f64) -> f64 {
+    width / sqrt(2_f64 * PI)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SecurityEstimate {
+    pub primal_usvp_bits: f64,
+    pub dual_bits: f64,
+}
+
+impl SecurityEstimate {
+    pub const fn estimate(lwe: &LWEInstance) -> Self {
+        Self {
+            primal_usvp_bits: primal_usvp_bits(lwe),
+            dual_bits: dual_bits(lwe),
+        }
+    }
+
+    /// The cost of the cheapest attack.
+    pub const fn bits(&self) -> f64 {
+        self.primal_usvp_bits.min(self.dual_bits)
+    }
+}
+
+impl fmt::Display for SecurityEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} bits (primal uSVP {:.1}, dual {:.1})",
+            self.bits(),
+            self.primal_usvp_bits,
+            self.dual_bits
+        )
+    }
+}
+
+//
+// The estimates are evaluated at compile time by `respire!`, where the `f64` methods from `std`
+// are not available. These agree with them to within a few ulps.
+//
+
+/// `2^k` for `-1022 <= k <= 1023`.
+const fn pow2(k: i64) -> f64 {
+    f64::from_bits(((k + 1023) as u64) << 52)
+}
+
+/// Natural logarithm.
+pub const fn ln(x: f64) -> f64 {
+    if x.is_nan() || x < 0_f64 {
+        return f64::NAN;
+    }
+    if x == 0_f64 {
+        return f64::NEG_INFINITY;
+    }
+    if x == f64::INFINITY {
+        return f64::INFINITY;
+    }
+    // Write x = m 2^e with m in [sqrt(1/2), sqrt(2))
+    let (x, mut e) = if x < f64::MIN_POSITIVE {
+        (x * pow2(54), -54_i64)
+    } else {
+        (x, 0_i64)
+    };
+    let bits = x.to_bits();
+    e += ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    if m > SQRT_2 {
+        m /= 2_f64;
+        e += 1;
+    }
+
+    // ln(m) = 2 atanh(s) with |s| <= 0.172
+    let s = (m - 1_f64) / (m + 1_f64);
+    let s2 = s * s;
+    let mut term = s;
+    let mut sum = 0_f64;
+    let mut k = 1;
+    while k <= 21 {
+        sum += term / k as f64;
+        term *= s2;
+        k += 2;
+    }
+    e as f64 * LN_2 + 2_f64 * sum
+}
+
+/// The exponential function.
+pub const fn exp(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    if x > 709.8_f64 {
+        return f64::INFINITY;
+    }
+    if x < -745.2_f64 {
+        return 0_f64;
+    }
+    // exp(x) = exp(r) 2^k with |r| <= ln(2) / 2
+    let k = (x / LN_2 + if x < 0_f64 { -0.5_f64 } else { 0.5_f64 }) as i64;
+    let r = x - k as f64 * LN_2;
+    let mut term = 1_f64;
+    let mut sum = 1_f64;
+    let mut n = 1;
+    while n <= 14 {
+        term *= r / n as f64;
+        sum += term;
+        n += 1;
+    }
+    // Split the scaling so that each factor is a normal float
+    sum * pow2(k / 2) * pow2(k - k / 2)
+}
+
+pub const fn log2(x: f64) -> f64 {
+    ln(x) / LN_2
+}
+
+pub const fn sqrt(x: f64) -> f64 {
+    if x == 0_f64 {
+        return 0_f64;
+    }
+    // One Newton step corrects the rounding of exp and ln
+    let y = exp(ln(x) / 2_f64);
+    (y + x / y) / 2_f64
+}
+
+const LN_PI: f64 = ln(PI);
+const LN_2_PI_E: f64 = ln(2_f64 * PI * E);
+
+/// `ln(delta(beta))` given `ln(beta)`, i.e. `ln(((pi beta)^(1 / beta) beta / (2 pi e))^(1 / (2 (beta - 1))))`.
+const fn log_delta(beta: f64, ln_beta: f64) -> f64 {
+    ((LN_PI + ln_beta) / beta + ln_beta - LN_2_PI_E) / (2_f64 * (beta - 1_f64))
+}
+
+/// The cost of BKZ-`beta` is `0.292 beta + bkz_overhead_bits(d)`.
+const fn bkz_overhead_bits(d: usize) -> f64 {
+    16.4_f64 + log2(8_f64 * d as f64)
+}
+
+///
+/// The largest block size worth trying in dimension `d`: BKZ alone costs at least `best` beyond it.
+/// Clamped to `[40, d]`.
+///
+const fn beta_cap(best: f64, overhead_bits: f64, d: usize) -> usize {
+    let cap = (best - overhead_bits) / 0.292_f64 + 1_f64;
+    if cap.is_nan() || cap >= d as f64 {
+        d
+    } else if cap < 40_f64 {
+        40
+    } else {
+        cap as usize
+    }
+}
+
+/// The attacks try `m` LWE samples in steps of this size, up to `2n`.
+const fn sample_step(n: usize) -> usize {
+    let m_step = n / 64;
+    if m_step == 0 {
+        1
+    } else {
+        m_step
+    }
+}
+
+/// The logarithms of an `LWEInstance` that the attack costs depend on.
+#[derive(Clone, Copy)]
+struct LogLWEInstance {
+    n: usize,
+    ln_q: f64,
+    ln_e: f64,
+    ln_s: f64,
+}
+
+impl LogLWEInstance {
+    const fn new(lwe: &LWEInstance) -> Self {
+        Self {
+            n: lwe.n,
+            ln_q: ln(lwe.q),
+            ln_e: ln(lwe.stddev_e),
+            ln_s: ln(lwe.stddev_s),
+        }
+    }
+}
+
+const fn primal_usvp_succeeds(lwe: &LogLWEInstance, beta: usize, m: usize) -> bool {
+    let n = lwe.n;
+    let d = (n + m + 1) as f64;
+    let ln_beta = ln(beta as f64);
+    let beta = beta as f64;
+    let lhs = lwe.ln_e + 0.5_f64 * ln_beta;
+    let rhs = (2_f64 * beta - d) * log_delta(beta, ln_beta)
+        + ((m as f64) * lwe.ln_q + (n as f64) * (lwe.ln_e - lwe.ln_s)) / d;
+    lhs <= rhs
+}
+
+///
+/// Primal uSVP attack with the 2016 success condition, on the Kannan embedding with the secret
+/// rescaled to the size of the error.
+///
+pub const fn primal_usvp_bits(lwe: &LWEInstance) -> f64 {
+    let lwe = LogLWEInstance::new(lwe);
+    let n = lwe.n;
+    let m_step = sample_step(n);
+    let mut best = f64::INFINITY;
+    let mut m = m_step;
+    while m <= 2 * n {
+        let d = n + m + 1;
+        let hi = beta_cap(best, bkz_overhead_bits(d), d);
+        if primal_usvp_succeeds(&lwe, hi, m) {
+            let (mut lo, mut hi) = (40, hi);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if primal_usvp_succeeds(&lwe, mid, m) {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            best = best.min(0.292_f64 * lo as f64 + bkz_overhead_bits(d));
+        }
+        m += m_step;
+    }
+    best
+}
+
+///
+/// Dual attack: a BKZ-`beta` reduced basis of the scaled dual lattice gives a short vector that
+/// distinguishes with advantage `exp(-2 pi^2 (stddev / q)^2)`. The `1 / advantage^2` samples needed
+/// are amortized over the `2^(0.2075 beta)` short vectors produced by a sieve.
+///
+pub const fn dual_bits(lwe: &LWEInstance) -> f64 {
+    let lwe = LogLWEInstance::new(lwe);
+    let n = lwe.n;
+    let m_step = sample_step(n);
+    let mut best = f64::INFINITY;
+    let mut m = m_step;
+    while m <= 2 * n {
+        best = best.min(dual_cost_min(&lwe, m, best));
+        m += m_step;
+    }
+    best
+}
+
+const fn dual_cost_bits(lwe: &LogLWEInstance, beta: usize, m: usize, overhead_bits: f64) -> f64 {
+    let n = lwe.n;
+    let d = m + n;
+    let beta_f64 = beta as f64;
+    let log_len = (d - 1) as f64 * log_delta(beta_f64, ln(beta_f64))
+        + (n as f64) * (lwe.ln_q + lwe.ln_s - lwe.ln_e) / d as f64;
+    // log2((stddev / q)^2 4 pi^2), with stddev = exp(ln_e + log_len)
+    let ratio = exp(lwe.ln_e + log_len - lwe.ln_q);
+    let log2_samples = 4_f64 * PI * PI * ratio * ratio / LN_2;
+    0.292_f64 * beta_f64 + overhead_bits + (log2_samples - 0.2075_f64 * beta_f64).max(0_f64)
+}
+
+///
+/// The smallest dual cost with `m` samples over `40 <= beta <= m + n`, if it is below `best`. The
+/// cost is unimodal in `beta`, so this is a binary search on its slope.
+///
+const fn dual_cost_min(lwe: &LogLWEInstance, m: usize, best: f64) -> f64 {
+    let d = m + lwe.n;
+    let overhead_bits = bkz_overhead_bits(d);
+    let (mut lo, mut hi) = (40, beta_cap(best, overhead_bits, d));
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let left = dual_cost_bits(lwe, mid, m, overhead_bits);
+        let right = dual_cost_bits(lwe, mid + 1, m, overhead_bits);
+        if left <= right && left.is_finite() {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    dual_cost_bits(lwe, lo, m, overhead_bits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_security_estimates() {
+        // First dimension ring of the default parameters, with a uniform secret in [-7, 7)
+        let q1 = LWEInstance {
+            stddev_s: 7_f64 / 3_f64.sqrt(),
+            ..LWEInstance::gaussian(2048, 268369921_f64 * 249561089_f64, 9.9, 0_f64)
+        };
+        let estimate = SecurityEstimate::estimate(&q1);
+        assert!(
+            (125_f64..140_f64).contains(&estimate.primal_usvp_bits),
+            "{estimate}"
+        );
+        assert!(
+            (125_f64..140_f64).contains(&estimate.dual_bits),
+            "{estimate}"
+        );
+
+        // Smaller modulus or wider error is harder
+        let smaller_q = LWEInstance {
+            q: 2_f64.powi(40),
+            ..q1
+        };
+        assert!(SecurityEstimate::estimate(&smaller_q).bits() > estimate.bits());
+        let wider = LWEInstance::gaussian(2048, q1.q, 64_f64, 64_f64);
+        assert!(SecurityEstimate::estimate(&wider).bits() > estimate.bits());
+    }
+
+    #[test]
+    fn test_const_math() {
+        for x in [
+            1e-310_f64, 1e-5, 0.3, 1_f64, 1.5, 2_f64, 3_f64, 1e9, 7.2e16, 1e300,
+        ] {
+            assert!(
+                (ln(x) - x.ln()).abs() <= 1e-15_f64 * x.ln().abs().max(1_f64),
+                "{x}"
+            );
+            assert!((sqrt(x) - x.sqrt()).abs() <= 1e-15_f64 * x.sqrt(), "{x}");
+        }
+        for x in [-700_f64, -20_f64, -0.5, 0_f64, 1e-3, 1_f64, 30_f64, 700_f64] {
+            assert!((exp(x) - x.exp()).abs() <= 1e-13_f64 * x.exp(), "{x}");
+        }
+        assert_eq!(ln(0_f64), f64::NEG_INFINITY);
+        assert_eq!(exp(1000_f64), f64::INFINITY);
+        assert_eq!(exp(-1000_f64), 0_f64);
+    }
+}
@@ -0,0 +1,321 @@
+//! Sharding a Respire database across server processes.
+//!
+//! The first dimension is a linear map applied to each column separately, so the columns of a
+//! database (`ShardRespire::shard_columns`), or the buckets of a Cuckoo database
+//! (`shard_buckets`), can be split across shard servers. The coordinator holds the public
+//! parameters and expands each query once. It sends the first dimension ciphertexts to the
+//! shards, then folds, rotates, projects and compresses the combined results. Folding stays on
+//! the coordinator, so a shard only ever sees the first dimension ciphertexts.
+//!
+//! On connecting, a shard sends the number of databases it serves. A request holds, for each of
+//! them, a count and that many first dimension ciphertexts. The response holds a count and the
+//! RLWE encodings of each database's columns. Counts are little endian `u64`, and encodings are
+//! written by `ShardRespire::write_rlwe`.
+
+use crate::pir::pir::{Stats, PIR};
+use crate::pir::respire::Respire;
+use itertools::Itertools;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+pub trait ShardRespire: PIR + Respire {
+    /// Splits the columns of `db` into `num_shards` contiguous ranges, returned in order.
+    fn shard_columns(
+        db: &<Self as PIR>::Database,
+        num_shards: usize,
+    ) -> Vec<<Self as PIR>::Database>;
+    fn expanded_first_dim(
+        expanded: &<Self as Respire>::QueryOneExpanded,
+    ) -> &[<Self as Respire>::RLWEEncoding];
+    /// The first dimension over the columns of `db`, which may be a shard.
+    fn answer_shard(
+        db: &<Self as PIR>::Database,
+        rlwes: &[<Self as Respire>::RLWEEncoding],
+    ) -> io::Result<Vec<<Self as Respire>::RLWEEncoding>>;
+    /// Fold, rotate and project the first dimension results of all columns.
+    fn answer_finish(
+        pp: &<Self as PIR>::PublicParams,
+        first_dim: Vec<<Self as Respire>::RLWEEncoding>,
+        expanded: <Self as Respire>::QueryOneExpanded,
+    ) -> <Self as Respire>::AnswerOne;
+    fn write_rlwe(c: &<Self as Respire>::RLWEEncoding, w: &mut dyn Write) -> io::Result<()>;
+    fn read_rlwe(r: &mut dyn Read) -> io::Result<<Self as Respire>::RLWEEncoding>;
+}
+
+/// Length of the `k`-th of `num_shards` contiguous ranges splitting `len` columns or buckets.
+pub fn shard_len(len: usize, num_shards: usize, k: usize) -> usize {
+    (k + 1) * len / num_shards - k * len / num_shards
+}
+
+/// Splits Cuckoo buckets into `num_shards` contiguous ranges, returned in order.
+pub fn shard_buckets<D>(dbs: Vec<D>, num_shards: usize) -> Vec<Vec<D>> {
+    assert!((1..=dbs.len()).contains(&num_shards));
+    let num_dbs = dbs.len();
+    let mut dbs_iter = dbs.into_iter();
+    (0..num_shards)
+        .map(|k| {
+            (&mut dbs_iter)
+                .take(shard_len(num_dbs, num_shards, k))
+                .collect_vec()
+        })
+        .collect_vec()
+}
+
+/// Serves `dbs` to one coordinator connection at a time.
+pub fn serve_shard<R: ShardRespire>(
+    listener: &TcpListener,
+    dbs: &[<R as PIR>::Database],
+) -> io::Result<()> {
+    for stream in listener.incoming() {
+        serve_shard_connection::<R>(stream?, dbs)?;
+    }
+    Ok(())
+}
+
+/// Answers requests on `stream` until the coordinator closes it.
+pub fn serve_shard_connection<R: ShardRespire>(
+    stream: TcpStream,
+    dbs: &[<R as PIR>::Database],
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    write_u64(&mut writer, dbs.len())?;
+    writer.flush()?;
+
+    loop {
+        let mut first = [0_u8; 8];
+        match reader.read_exact(&mut first) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let num_dbs = u64::from_le_bytes(first) as usize;
+        if num_dbs != dbs.len() {
+            return Err(invalid_data(format!(
+                "expected {} databases, got {}",
+                dbs.len(),
+                num_dbs
+            )));
+        }
+
+        let mut results = Vec::with_capacity(dbs.len());
+        for db in dbs {
+            let count = read_u64(&mut reader)?;
+            if count != R::PACKED_DIM1_SIZE {
+                return Err(invalid_data(format!(
+                    "expected {} first dimension ciphertexts, got {}",
+                    R::PACKED_DIM1_SIZE,
+                    count
+                )));
+            }
+            let rlwes = (0..count)
+                .map(|_| R::read_rlwe(&mut reader))
+                .collect::<io::Result<Vec<_>>>()?;
+            results.push(R::answer_shard(db, &rlwes)?);
+        }
+
+        for result in results {
+            write_u64(&mut writer, result.len())?;
+            for c in result.iter() {
+                R::write_rlwe(c, &mut writer)?;
+            }
+        }
+        writer.flush()?;
+    }
+}
+
+pub struct ShardCoordinator<R: ShardRespire> {
+    // Reader, writer and number of databases of each shard
+    shards: Vec<(BufReader<TcpStream>, BufWriter<TcpStream>, usize)>,
+    phantom: PhantomData<R>,
+}
+
+impl<R: ShardRespire> ShardCoordinator<R> {
+    /// Connects to the shards, in the order of their column or bucket ranges.
+    pub fn connect<A: ToSocketAddrs>(addrs: &[A]) -> io::Result<Self> {
+        let shards = addrs
+            .iter()
+            .map(|addr| {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                let mut reader = BufReader::new(stream.try_clone()?);
+                let num_dbs = read_u64(&mut reader)?;
+                Ok((reader, BufWriter::new(stream), num_dbs))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            shards,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Total number of databases (column shards or buckets) across the shards.
+    pub fn num_dbs(&self) -> usize {
+        self.shards.iter().map(|(_, _, num_dbs)| num_dbs).sum()
+    }
+
+    ///
+    /// Runs the first dimension of database `k` (in shard order) on `rlwes[k]`, which must give
+    /// `num_columns[k]` columns. The requests are all sent before any response is read, so the
+    /// shards work concurrently.
+    ///
+    pub fn first_dim(
+        &mut self,
+        rlwes: &[&[<R as Respire>::RLWEEncoding]],
+        num_columns: &[usize],
+    ) -> io::Result<Vec<Vec<<R as Respire>::RLWEEncoding>>> {
+        assert_eq!(rlwes.len(), self.num_dbs());
+        assert_eq!(num_columns.len(), self.num_dbs());
+        let mut rlwes_iter = rlwes.iter();
+        for (_, writer, num_dbs) in self.shards.iter_mut() {
+            write_u64(writer, *num_dbs)?;
+            for db_rlwes in (&mut rlwes_iter).take(*num_dbs) {
+                write_u64(writer, db_rlwes.len())?;
+                for c in db_rlwes.iter() {
+                    R::write_rlwe(c, writer)?;
+                }
+            }
+            writer.flush()?;
+        }
+
+        let mut result = Vec::with_capacity(rlwes.len());
+        let mut num_columns_iter = num_columns.iter();
+        for (reader, _, num_dbs) in self.shards.iter_mut() {
+            for &expected in (&mut num_columns_iter).take(*num_dbs) {
+                let count = read_u64(reader)?;
+                if count != expected {
+                    return Err(invalid_data(format!(
+                        "expected {} columns, got {}",
+                        expected, count
+                    )));
+                }
+                let columns = (0..count)
+                    .map(|_| R::read_rlwe(reader))
+                    .collect::<io::Result<Vec<_>>>()?;
+                result.push(columns);
+            }
+        }
+        Ok(result)
+    }
+
+    ///
+    /// Answers a query against a database split with `shard_columns`, one column shard per
+    /// shard server.
+    ///
+    pub fn answer_columns(
+        &mut self,
+        pp: &<R as PIR>::PublicParams,
+        qs: &[<R as Respire>::QueryPacked],
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> io::Result<Vec<<R as Respire>::AnswerOneCompressed>> {
+        if let Some((_, _, num_dbs)) = self.shards.iter().find(|(_, _, num_dbs)| *num_dbs != 1) {
+            return Err(invalid_data(format!(
+                "expected one column shard per server, got {}",
+                num_dbs
+            )));
+        }
+        let num_shards = self.num_dbs();
+        if num_shards > R::PACKED_DIM2_SIZE {
+            return Err(invalid_data(format!(
+                "{} shards for {} columns",
+                num_shards,
+                R::PACKED_DIM2_SIZE
+            )));
+        }
+        let num_columns = (0..num_shards)
+            .map(|k| shard_len(R::PACKED_DIM2_SIZE, num_shards, k))
+            .collect_vec();
+        let mut answers = Vec::new();
+        for q in qs {
+            for expanded in R::answer_query_unpack_packed(pp, q, None, time_stats.as_deref_mut()) {
+                let begin = Instant::now();
+                let rlwes = vec![R::expanded_first_dim(&expanded); num_shards];
+                let first_dim = self
+                    .first_dim(&rlwes, &num_columns)?
+                    .into_iter()
+                    .flatten()
+                    .collect_vec();
+                let mid = Instant::now();
+                answers.push(R::answer_finish(pp, first_dim, expanded));
+                let end = Instant::now();
+                if let Some(time_stats) = time_stats.as_deref_mut() {
+                    time_stats.add("answer_first_dim_shards", mid - begin);
+                    time_stats.add("answer_finish", end - mid);
+                }
+            }
+        }
+        Ok(Self::compress(pp, &answers, time_stats))
+    }
+
+    ///
+    /// Answers a Cuckoo query against buckets split with `shard_buckets`. The `k`-th packed index
+    /// is for bucket `k`.
+    ///
+    pub fn answer_buckets(
+        &mut self,
+        pp: &<R as PIR>::PublicParams,
+        qs: &[<R as Respire>::QueryPacked],
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> io::Result<Vec<<R as Respire>::AnswerOneCompressed>> {
+        let mut expanded = Vec::with_capacity(self.num_dbs());
+        for q in qs {
+            expanded.extend(R::answer_query_unpack_packed(
+                pp,
+                q,
+                None,
+                time_stats.as_deref_mut(),
+            ));
+        }
+        if expanded.len() != self.num_dbs() {
+            return Err(invalid_data(format!(
+                "expected {} buckets, the shards serve {}",
+                expanded.len(),
+                self.num_dbs()
+            )));
+        }
+
+        let begin = Instant::now();
+        let rlwes = expanded.iter().map(R::expanded_first_dim).collect_vec();
+        let first_dims = self.first_dim(&rlwes, &vec![R::PACKED_DIM2_SIZE; rlwes.len()])?;
+        let mid = Instant::now();
+        let answers = expanded
+            .into_iter()
+            .zip(first_dims)
+            .map(|(expanded, first_dim)| R::answer_finish(pp, first_dim, expanded))
+            .collect_vec();
+        let end = Instant::now();
+        if let Some(time_stats) = time_stats.as_deref_mut() {
+            time_stats.add("answer_first_dim_shards", mid - begin);
+            time_stats.add("answer_finish", end - mid);
+        }
+        Ok(Self::compress(pp, &answers, time_stats))
+    }
+
+    fn compress(
+        pp: &<R as PIR>::PublicParams,
+        answers: &[<R as Respire>::AnswerOne],
+        mut time_stats: Option<&mut Stats<Duration>>,
+    ) -> Vec<<R as Respire>::AnswerOneCompressed> {
+        answers
+            .chunks(R::RESPONSE_CHUNK_SIZE)
+            .map(|chunk| R::answer_compress_chunk(pp, chunk, None, time_stats.as_deref_mut()))
+            .collect_vec()
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_u64(w: &mut dyn Write, value: usize) -> io::Result<()> {
+    w.write_all(&(value as u64).to_le_bytes())
+}
+
+fn read_u64(r: &mut dyn Read) -> io::Result<usize> {
+    let mut bytes = [0_u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes) as usize)
+}
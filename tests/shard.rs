@@ -0,0 +1,67 @@
+use respire::pir::pir::PIR;
+use respire::pir::respire_harness::{
+    harness_record, RespireSmall as Small, RESPIRE_SMALL_PARAMS as SMALL,
+};
+use respire::pir::shard::ShardCoordinator;
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::process::{Child, Command, Stdio};
+
+/// A `respire-bench shard` process, killed when dropped.
+struct ShardProcess(Child);
+
+impl ShardProcess {
+    fn spawn(num_shards: usize, shard: usize) -> (Self, SocketAddr) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_respire-bench"))
+            .args(["shard", "--db-size", "64k", "--listen", "127.0.0.1:0"])
+            .args(["--num-shards", &num_shards.to_string()])
+            .args(["--shard", &shard.to_string()])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let process = Self(child);
+        let addr = line
+            .trim()
+            .strip_prefix("listening on ")
+            .unwrap_or_else(|| panic!("unexpected shard output {:?}", line))
+            .parse()
+            .unwrap();
+        (process, addr)
+    }
+}
+
+impl Drop for ShardProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn test_shard_processes() {
+    assert_eq!(SMALL.db_size() * SMALL.BYTES_PER_RECORD, 64 << 10);
+    let (shards, addrs): (Vec<_>, Vec<_>) = (0..3).map(|k| ShardProcess::spawn(3, k)).unzip();
+
+    let (qk, pp) = Small::setup(None);
+    let indices = [201];
+    let (q, st) = Small::query(&qk, &indices, &(), None);
+    let mut coordinator = ShardCoordinator::<Small>::connect(&addrs).unwrap();
+    let r = coordinator.answer_columns(&pp, &q, None).unwrap();
+    assert_eq!(
+        Small::extract(&qk, &r, &st, None),
+        indices.map(harness_record::<Small>)
+    );
+
+    // Shards serve one coordinator at a time
+    drop(coordinator);
+
+    // A coordinator expecting a different split rejects the shards' columns
+    let mut coordinator = ShardCoordinator::<Small>::connect(&addrs[..2]).unwrap();
+    let err = coordinator.answer_columns(&pp, &q, None).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    drop(shards);
+}